@@ -0,0 +1,271 @@
+//! Locations of subexpressions and diagnostics of malformed expressions.
+//! The operations on expressions like evaluation and lowering report their
+//! errors with their own error types.
+
+use std::fmt::Display;
+
+use serde_json::Value;
+
+use crate::exprs::{BinaryOp, Expression, UnaryOp};
+
+/// The location of a subexpression: the indices of the children to descend
+/// into from the root. Children are numbered from left to right, e.g. the
+/// condition of an if-then-else is child 0 and its else branch is child 2.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ExprPath(pub Vec<usize>);
+
+impl ExprPath {
+    /// The path of the root expression.
+    pub fn root() -> Self {
+        ExprPath(Vec::new())
+    }
+
+    /// The path of the `index`-th child of the expression at this path.
+    pub fn child(&self, index: usize) -> Self {
+        let mut path = self.0.clone();
+        path.push(index);
+        ExprPath(path)
+    }
+}
+
+/// Renders the path as `root`, followed by the child indices, e.g.
+/// `root.1.0`.
+impl Display for ExprPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("root")?;
+        for index in &self.0 {
+            write!(f, ".{}", index)?;
+        }
+        Ok(())
+    }
+}
+
+impl Expression {
+    /// The subexpression at `path`, if the path exists in this expression.
+    pub fn at_path(&self, path: &ExprPath) -> Option<&Expression> {
+        path.0
+            .iter()
+            .try_fold(self, |expr, &index| expr.children().get(index).copied())
+    }
+
+    /// Check that each operator is applied to operands of the shape the
+    /// specification requires, i.e. derivatives are only applied to
+    /// identifiers. Returns the paths of all offending subexpressions in
+    /// pre-order.
+    pub fn validate_structure(&self) -> Result<(), Vec<ExprPath>> {
+        let mut invalid = Vec::new();
+        let mut worklist = vec![(self, ExprPath::root())];
+        while let Some((expr, at)) = worklist.pop() {
+            if let Expression::Unary(unary) = expr {
+                if unary.op == UnaryOp::Derivative
+                    && !matches!(unary.exp, Expression::Identifier(_))
+                {
+                    invalid.push(at.clone());
+                }
+            }
+            let children = expr.children().into_iter().enumerate().rev();
+            worklist.extend(children.map(|(index, child)| (child, at.child(index))));
+        }
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid)
+        }
+    }
+}
+
+/// Errors of [`Expression::from_json_diagnostic`], located by the
+/// [`ExprPath`] of the offending JSON object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeserializeDiagnostic {
+    /// The `"op"` field of an object names no JANI operator.
+    UnknownOperator {
+        op: String,
+        fields: Vec<String>,
+        at: ExprPath,
+    },
+    /// The object lacks a field that its operator requires, or its `"op"`
+    /// field is not a string.
+    InvalidOperator {
+        op: String,
+        fields: Vec<String>,
+        at: ExprPath,
+    },
+    /// Any other error reported by serde.
+    Other(String),
+}
+
+impl Display for DeserializeDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeserializeDiagnostic::UnknownOperator { op, fields, at } => write!(
+                f,
+                "unknown operator {} with fields {} at {}",
+                op,
+                fields.join(", "),
+                at
+            ),
+            DeserializeDiagnostic::InvalidOperator { op, fields, at } => write!(
+                f,
+                "invalid operator {} with fields {} at {}",
+                op,
+                fields.join(", "),
+                at
+            ),
+            DeserializeDiagnostic::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for DeserializeDiagnostic {}
+
+impl Expression {
+    /// Deserialize an expression like serde does, but report objects with an
+    /// `"op"` field whose operator is unknown or lacks required fields
+    /// instead of serde's generic error for untagged enums.
+    pub fn from_json_diagnostic(value: &Value) -> Result<Expression, DeserializeDiagnostic> {
+        check_operators(value, ExprPath::root())?;
+        serde_json::from_value(value.clone())
+            .map_err(|err| DeserializeDiagnostic::Other(err.to_string()))
+    }
+}
+
+/// Check the operator objects in `value` and return the first error in a
+/// pre-order traversal.
+fn check_operators(value: &Value, at: ExprPath) -> Result<(), DeserializeDiagnostic> {
+    let Some(object) = value.as_object() else {
+        return Ok(());
+    };
+    let Some(op) = object.get("op") else {
+        return Ok(());
+    };
+    let mut fields: Vec<String> = object.keys().cloned().collect();
+    fields.sort();
+    let Some(op) = op.as_str() else {
+        return Err(DeserializeDiagnostic::InvalidOperator {
+            op: op.to_string(),
+            fields,
+            at,
+        });
+    };
+    let has_fields = |names: &[&str]| names.iter().all(|name| object.contains_key(*name));
+    let children: &[&str] = match op {
+        "ite" => &["if", "then", "else"],
+        "nondet" => &["var", "exp"],
+        "call" => &["function", "args"],
+        _ if serde_json::from_value::<UnaryOp>(Value::from(op)).is_ok() => &["exp"],
+        _ if serde_json::from_value::<BinaryOp>(Value::from(op)).is_ok() => &["left", "right"],
+        _ => {
+            return Err(DeserializeDiagnostic::UnknownOperator {
+                op: op.to_owned(),
+                fields,
+                at,
+            })
+        }
+    };
+    if !has_fields(children) {
+        return Err(DeserializeDiagnostic::InvalidOperator {
+            op: op.to_owned(),
+            fields,
+            at,
+        });
+    }
+    match op {
+        // the selected variable is no expression
+        "nondet" => check_operators(&object["exp"], at.child(0)),
+        "call" => match object["args"].as_array() {
+            Some(args) => args
+                .iter()
+                .enumerate()
+                .try_for_each(|(index, arg)| check_operators(arg, at.child(index))),
+            None => Ok(()),
+        },
+        _ => children
+            .iter()
+            .enumerate()
+            .try_for_each(|(index, field)| check_operators(&object[*field], at.child(index))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        exprs::{BinaryExpression, BinaryOp, Expression, UnaryExpression, UnaryOp},
+        test_util::ident,
+    };
+
+    use super::{DeserializeDiagnostic, ExprPath};
+
+    #[test]
+    fn test_expr_path() {
+        let quotient: Expression = BinaryExpression {
+            op: BinaryOp::Divide,
+            left: ident("x"),
+            right: 0.into(),
+        }
+        .into();
+        let expr = ident("y") + quotient.clone();
+        let path = ExprPath::root().child(1);
+        assert_eq!(path.to_string(), "root.1");
+        assert_eq!(expr.at_path(&path), Some(&quotient));
+        assert_eq!(expr.at_path(&path.child(0)), Some(&ident("x")));
+        assert_eq!(expr.at_path(&path.child(2)), None);
+        assert_eq!(expr.at_path(&ExprPath::root()), Some(&expr));
+    }
+
+    #[test]
+    fn test_validate_structure() {
+        let derivative = |exp: Expression| -> Expression {
+            UnaryExpression {
+                op: UnaryOp::Derivative,
+                exp,
+            }
+            .into()
+        };
+        let expr = derivative(ident("x")).lt(1.into());
+        assert_eq!(expr.validate_structure(), Ok(()));
+
+        let expr = derivative(ident("x") + 1.into()).lt(derivative(2.into()));
+        assert_eq!(
+            expr.validate_structure(),
+            Err(vec![ExprPath(vec![0]), ExprPath(vec![1])])
+        );
+    }
+
+    #[test]
+    fn test_from_json_diagnostic() {
+        let value = serde_json::json!({
+            "op": "∧",
+            "left": "b",
+            "right": { "op": "≤", "left": { "op": "plus", "left": "x", "right": 1 }, "right": "y" },
+        });
+        assert_eq!(
+            Expression::from_json_diagnostic(&value),
+            Err(DeserializeDiagnostic::UnknownOperator {
+                op: "plus".to_owned(),
+                fields: vec!["left".to_owned(), "op".to_owned(), "right".to_owned()],
+                at: ExprPath(vec![1, 0]),
+            })
+        );
+        assert_eq!(
+            Expression::from_json_diagnostic(&value)
+                .unwrap_err()
+                .to_string(),
+            "unknown operator plus with fields left, op, right at root.1.0"
+        );
+
+        let value = serde_json::json!({ "op": "ite", "if": true, "then": 1 });
+        assert_eq!(
+            Expression::from_json_diagnostic(&value)
+                .unwrap_err()
+                .to_string(),
+            "invalid operator ite with fields if, op, then at root"
+        );
+
+        let value = serde_json::json!({ "op": "<", "left": "x", "right": 1 });
+        assert_eq!(
+            Expression::from_json_diagnostic(&value),
+            Ok(ident("x").lt(1.into()))
+        );
+    }
+}
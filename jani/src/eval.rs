@@ -0,0 +1,846 @@
+//! Evaluation of JANI expressions to concrete values.
+
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use num::{BigInt, BigRational, FromPrimitive, Integer, One, ToPrimitive, Zero};
+
+use crate::{
+    exprs::{BinaryOp, ConstantValue, Expression, MathConstant, UnaryOp},
+    Identifier,
+};
+
+/// A concrete value of a JANI expression. Numbers are represented exactly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Bool(bool),
+    Int(BigInt),
+    Real(BigRational),
+}
+
+impl Value {
+    /// Convert a [`ConstantValue`] into a value. Numbers are parsed exactly
+    /// from their decimal representation. Mathematical constants cannot be
+    /// represented exactly and result in an error.
+    pub fn from_constant(value: &ConstantValue) -> Result<Value, EvalError> {
+        match value {
+            ConstantValue::Number(number) => {
+                if let Some(int) = number.as_i64() {
+                    Ok(Value::Int(int.into()))
+                } else if let Some(int) = number.as_u64() {
+                    Ok(Value::Int(int.into()))
+                } else if let Ok(int) = BigInt::from_str(&number.to_string()) {
+                    // only with serde_json's arbitrary precision
+                    Ok(Value::Int(int))
+                } else {
+                    parse_decimal(&number.to_string())
+                        .map(Value::Real)
+                        .ok_or(EvalError::Unsupported("number literal"))
+                }
+            }
+            ConstantValue::Boolean(b) => Ok(Value::Bool(*b)),
+            ConstantValue::MathConstant(_) => Err(EvalError::Unsupported("math constant")),
+        }
+    }
+
+    /// Convert this value into a [`ConstantValue`] if it can be represented
+    /// exactly. Integers must fit into 64 bits unless the
+    /// `arbitrary-precision` feature is enabled, and reals must be exactly
+    /// representable as a `f64`.
+    pub fn to_constant(&self) -> Option<ConstantValue> {
+        match self {
+            Value::Bool(b) => Some(ConstantValue::Boolean(*b)),
+            Value::Int(int) => ConstantValue::from_big_int(int),
+            Value::Real(real) => {
+                let float = real.to_f64()?;
+                if BigRational::from_f64(float).as_ref() != Some(real) {
+                    return None;
+                }
+                ConstantValue::try_from(float).ok()
+            }
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "bool",
+            Value::Int(_) => "int",
+            Value::Real(_) => "real",
+        }
+    }
+
+    fn to_bool(&self) -> Result<bool, EvalError> {
+        match self {
+            Value::Bool(b) => Ok(*b),
+            _ => Err(self.mismatch("bool")),
+        }
+    }
+
+    pub(crate) fn to_rational(&self) -> Result<BigRational, EvalError> {
+        match self {
+            Value::Int(int) => Ok(BigRational::from_integer(int.clone())),
+            Value::Real(real) => Ok(real.clone()),
+            Value::Bool(_) => Err(self.mismatch("number")),
+        }
+    }
+
+    fn mismatch(&self, expected: &'static str) -> EvalError {
+        EvalError::TypeMismatch {
+            expected,
+            found: self.type_name(),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Int(int) => write!(f, "{}", int),
+            Value::Real(real) => write!(f, "{}", real),
+        }
+    }
+}
+
+impl MathConstant {
+    /// Compute a rational approximation of this constant that differs from the
+    /// true value by at most `2^-bits`.
+    ///
+    /// The result is only an approximation and must not be used where
+    /// exactness is required. In particular, the exact evaluation with
+    /// [`Expression::eval`] still rejects math constants.
+    pub fn approx(&self, bits: u32) -> BigRational {
+        // compute in fixed-point arithmetic with some guard bits to absorb the
+        // truncation errors of the individual terms
+        let precision = bits as usize + 16;
+        let one = BigInt::one() << precision;
+        let value = match self {
+            MathConstant::EulersNumber => {
+                // e = sum_k 1/k!
+                let mut sum = BigInt::zero();
+                let mut term = one;
+                let mut k = 0u32;
+                while !term.is_zero() {
+                    sum += &term;
+                    k += 1;
+                    term /= k;
+                }
+                sum
+            }
+            MathConstant::Pi => {
+                // Machin's formula: π = 16 atan(1/5) - 4 atan(1/239)
+                16 * atan_inv(&one, 5) - 4 * atan_inv(&one, 239)
+            }
+        };
+        // round to the requested precision
+        let half = BigInt::one() << 15;
+        BigRational::new((value + half) >> 16, BigInt::one() << bits)
+    }
+}
+
+/// Compute `atan(1/x)` in fixed-point arithmetic with the given `one` using
+/// the Taylor series `atan(y) = y - y^3/3 + y^5/5 - ...`.
+fn atan_inv(one: &BigInt, x: u32) -> BigInt {
+    let x_squared = BigInt::from(x) * x;
+    let mut power = one / x;
+    let mut sum = power.clone();
+    let mut n = 1u32;
+    loop {
+        power /= &x_squared;
+        n += 2;
+        let term = &power / n;
+        if term.is_zero() {
+            return sum;
+        }
+        if n % 4 == 1 {
+            sum += term;
+        } else {
+            sum -= term;
+        }
+    }
+}
+
+/// The largest number of bits of the numerator or denominator of a power
+/// that is computed exactly, see [`pow_bits`].
+const MAX_POW_BITS: u64 = 1 << 16;
+
+/// A lower bound for the number of bits of the numerator or denominator of
+/// `base` to the power of `exponent`, computed without the power itself.
+pub(crate) fn pow_bits(base: &BigRational, exponent: u32) -> u64 {
+    let bits = base.numer().bits().max(base.denom().bits());
+    bits.saturating_sub(1).saturating_mul(u64::from(exponent))
+}
+
+/// Parse a decimal number with an optional fraction and exponent (e.g.
+/// `-1.25e-3`) exactly. Returns `None` if the exponent is too large to
+/// compute the number (see [`MAX_POW_BITS`]).
+fn parse_decimal(s: &str) -> Option<BigRational> {
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(pos) => (&s[..pos], s[pos + 1..].parse::<i32>().ok()?),
+        None => (s, 0),
+    };
+    let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = BigInt::from_str(&format!("{}{}", int_part, frac_part)).ok()?;
+    let exponent = exponent.checked_sub(i32::try_from(frac_part.len()).ok()?)?;
+    if pow_bits(
+        &BigRational::from_integer(10.into()),
+        exponent.unsigned_abs(),
+    ) > MAX_POW_BITS
+    {
+        return None;
+    }
+    let scale = BigInt::from(10).pow(exponent.unsigned_abs());
+    Some(if exponent >= 0 {
+        BigRational::from_integer(digits * scale)
+    } else {
+        BigRational::new(digits, scale)
+    })
+}
+
+/// Errors that can occur during the evaluation of a JANI expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    /// The identifier has no value in the environment.
+    UnboundIdentifier(Identifier),
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    DivisionByZero,
+    /// The expression cannot be evaluated exactly.
+    Unsupported(&'static str),
+    /// The expression can only be evaluated with an SMT solver, e.g. by
+    /// lowering it to Z3.
+    RequiresSolver(&'static str),
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::UnboundIdentifier(ident) => write!(f, "unbound identifier {}", ident),
+            EvalError::TypeMismatch { expected, found } => {
+                write!(f, "expected value of type {}, found {}", expected, found)
+            }
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+            EvalError::Unsupported(what) => write!(f, "cannot evaluate {}", what),
+            EvalError::RequiresSolver(what) => write!(f, "evaluating {} requires a solver", what),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+impl EvalError {
+    /// Whether the error means that the value is unknown, e.g. because the
+    /// identifier is not assigned in a partial model. Type mismatches are
+    /// errors in the expression itself and not undefined.
+    pub fn is_undefined(&self) -> bool {
+        !matches!(self, EvalError::TypeMismatch { .. })
+    }
+}
+
+/// The subexpression which prevents an expression from being constant, see
+/// [`Expression::require_constant`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonConstantSite(pub Expression);
+
+impl Display for NonConstantSite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Expression::Identifier(ident) => write!(f, "reference to identifier {}", ident),
+            Expression::NondetSelection(_) => write!(f, "nondeterministic selection"),
+            Expression::Call(call) => write!(f, "call of function {}", call.function),
+            _ => write!(f, "derivative"),
+        }
+    }
+}
+
+impl std::error::Error for NonConstantSite {}
+
+impl Expression {
+    /// Evaluate this expression with the values of identifiers given by `env`.
+    ///
+    /// Boolean connectives are evaluated in Kleene's three-valued logic: if
+    /// one operand is undefined (see [`EvalError::is_undefined`]), but the
+    /// other operand determines the result, e.g. `false ∧ x` with an unbound
+    /// `x`, the result is returned anyway. This way, connectives can be
+    /// decided in partial environments such as models with unknown
+    /// consistency.
+    pub fn eval(&self, env: &HashMap<Identifier, Value>) -> Result<Value, EvalError> {
+        self.eval_inner(env, None)
+    }
+
+    /// Like [`Expression::eval`], but math constants are replaced by
+    /// approximations with the given precision (see [`MathConstant::approx`]).
+    /// The result is only an approximation and must not be used where
+    /// exactness is required.
+    pub fn eval_approx(
+        &self,
+        env: &HashMap<Identifier, Value>,
+        bits: u32,
+    ) -> Result<Value, EvalError> {
+        self.eval_inner(env, Some(bits))
+    }
+
+    /// Whether this expression is a constant expression, see
+    /// [`Expression::require_constant`].
+    pub fn is_constant(&self) -> bool {
+        self.require_constant().is_ok()
+    }
+
+    /// Check that this expression is built only from literals, math constants
+    /// and operators other than the derivative. Otherwise, return the first
+    /// offending subexpression. Identifiers are never constant, so references
+    /// to other constants must be substituted before. Function calls are
+    /// rejected as well since the function body is not known here.
+    pub fn require_constant(&self) -> Result<(), NonConstantSite> {
+        match self {
+            Expression::Constant(_) => Ok(()),
+            Expression::Identifier(_) | Expression::NondetSelection(_) | Expression::Call(_) => {
+                Err(NonConstantSite(self.clone()))
+            }
+            Expression::IfThenElse(ite) => {
+                ite.cond.require_constant()?;
+                ite.left.require_constant()?;
+                ite.right.require_constant()
+            }
+            Expression::Unary(unary) => {
+                if unary.op == UnaryOp::Derivative {
+                    return Err(NonConstantSite(self.clone()));
+                }
+                unary.exp.require_constant()
+            }
+            Expression::Binary(binary) => {
+                binary.left.require_constant()?;
+                binary.right.require_constant()
+            }
+        }
+    }
+
+    fn eval_inner(
+        &self,
+        env: &HashMap<Identifier, Value>,
+        approx_bits: Option<u32>,
+    ) -> Result<Value, EvalError> {
+        let eval = |expr: &Expression| expr.eval_inner(env, approx_bits);
+        match self {
+            Expression::Constant(ConstantValue::MathConstant(constant)) => match approx_bits {
+                Some(bits) => Ok(Value::Real(constant.approx(bits))),
+                None => Err(EvalError::Unsupported("math constant")),
+            },
+            Expression::Constant(value) => Value::from_constant(value),
+            Expression::Identifier(ident) => env
+                .get(ident)
+                .cloned()
+                .ok_or_else(|| EvalError::UnboundIdentifier(ident.clone())),
+            Expression::IfThenElse(ite) => {
+                // the branch not taken may be undefined, e.g. guard a division
+                if eval(&ite.cond)?.to_bool()? {
+                    eval(&ite.left)
+                } else {
+                    eval(&ite.right)
+                }
+            }
+            Expression::Unary(unary) => eval_unary(unary.op, eval(&unary.exp)?),
+            Expression::Binary(binary) => match binary.op {
+                BinaryOp::And | BinaryOp::Or | BinaryOp::Implication => {
+                    eval_connective(binary.op, eval(&binary.left), eval(&binary.right))
+                }
+                _ => eval_binary(binary.op, eval(&binary.left)?, eval(&binary.right)?),
+            },
+            Expression::NondetSelection(_) => {
+                Err(EvalError::RequiresSolver("nondeterministic selection"))
+            }
+            Expression::Call(_) => Err(EvalError::Unsupported("function call")),
+        }
+    }
+}
+
+fn eval_unary(op: UnaryOp, value: Value) -> Result<Value, EvalError> {
+    match op {
+        UnaryOp::Not => Ok(Value::Bool(!value.to_bool()?)),
+        UnaryOp::Floor => Ok(Value::Int(value.to_rational()?.floor().to_integer())),
+        UnaryOp::Ceil => Ok(Value::Int(value.to_rational()?.ceil().to_integer())),
+        UnaryOp::Derivative => Err(EvalError::Unsupported("derivative")),
+        #[cfg(feature = "transcendental")]
+        _ => eval_transcendental(op, value),
+    }
+}
+
+/// Transcendental functions are evaluated using `f64` arithmetic. Thus the
+/// result is only an approximation of the exact value and must not be used
+/// where exactness is required.
+#[cfg(feature = "transcendental")]
+fn eval_transcendental(op: UnaryOp, value: Value) -> Result<Value, EvalError> {
+    let x = value
+        .to_rational()?
+        .to_f64()
+        .ok_or(EvalError::Unsupported("number out of f64 range"))?;
+    let res = match op {
+        UnaryOp::Sin => x.sin(),
+        UnaryOp::Cos => x.cos(),
+        UnaryOp::Tan => x.tan(),
+        UnaryOp::Cot => x.tan().recip(),
+        UnaryOp::Sec => x.cos().recip(),
+        UnaryOp::Csc => x.sin().recip(),
+        UnaryOp::Asin => x.asin(),
+        UnaryOp::Acos => x.acos(),
+        UnaryOp::Atan => x.atan(),
+        UnaryOp::Acot => x.recip().atan(),
+        UnaryOp::Asec => x.recip().acos(),
+        UnaryOp::Acsc => x.recip().asin(),
+        UnaryOp::Sinh => x.sinh(),
+        UnaryOp::Cosh => x.cosh(),
+        UnaryOp::Tanh => x.tanh(),
+        UnaryOp::Coth => x.tanh().recip(),
+        UnaryOp::Sech => x.cosh().recip(),
+        UnaryOp::Csch => x.sinh().recip(),
+        UnaryOp::Asinh => x.asinh(),
+        UnaryOp::Acosh => x.acosh(),
+        UnaryOp::Atanh => x.atanh(),
+        UnaryOp::Acoth => x.recip().atanh(),
+        UnaryOp::Asech => x.recip().acosh(),
+        UnaryOp::Acsch => x.recip().asinh(),
+        _ => unreachable!("{:?} is not transcendental", op),
+    };
+    BigRational::from_f64(res)
+        .map(Value::Real)
+        .ok_or(EvalError::Unsupported("non-finite result"))
+}
+
+/// Evaluate a boolean connective in Kleene's three-valued logic, see
+/// [`Expression::eval`].
+fn eval_connective(
+    op: BinaryOp,
+    left: Result<Value, EvalError>,
+    right: Result<Value, EvalError>,
+) -> Result<Value, EvalError> {
+    // the values of the left and right operand which determine the result
+    let (left_dominant, right_dominant, result) = match op {
+        BinaryOp::And => (false, false, false),
+        BinaryOp::Or => (true, true, true),
+        BinaryOp::Implication => (false, true, true),
+        _ => unreachable!("{:?} is not a boolean connective", op),
+    };
+    let left = left.and_then(|value| value.to_bool());
+    let right = right.and_then(|value| value.to_bool());
+    match (left, right) {
+        (Ok(left), Ok(right)) => eval_binary(op, Value::Bool(left), Value::Bool(right)),
+        (Ok(left), Err(err)) if err.is_undefined() && left == left_dominant => {
+            Ok(Value::Bool(result))
+        }
+        (Err(err), Ok(right)) if err.is_undefined() && right == right_dominant => {
+            Ok(Value::Bool(result))
+        }
+        (Err(err), _) | (_, Err(err)) => Err(err),
+    }
+}
+
+fn eval_binary(op: BinaryOp, left: Value, right: Value) -> Result<Value, EvalError> {
+    match op {
+        BinaryOp::Or | BinaryOp::And | BinaryOp::Implication => {
+            let (left, right) = (left.to_bool()?, right.to_bool()?);
+            Ok(Value::Bool(match op {
+                BinaryOp::Or => left || right,
+                BinaryOp::And => left && right,
+                _ => !left || right,
+            }))
+        }
+        BinaryOp::Equals | BinaryOp::NotEquals => {
+            let equal = match (&left, &right) {
+                (Value::Bool(left), Value::Bool(right)) => left == right,
+                _ => left.to_rational()? == right.to_rational()?,
+            };
+            Ok(Value::Bool(equal == (op == BinaryOp::Equals)))
+        }
+        BinaryOp::Less => Ok(Value::Bool(left.to_rational()? < right.to_rational()?)),
+        BinaryOp::LessOrEqual => Ok(Value::Bool(left.to_rational()? <= right.to_rational()?)),
+        BinaryOp::Greater => Ok(Value::Bool(left.to_rational()? > right.to_rational()?)),
+        BinaryOp::GreaterOrEqual => Ok(Value::Bool(left.to_rational()? >= right.to_rational()?)),
+        BinaryOp::Plus => arith(left, right, |l, r| l + r, |l, r| l + r),
+        BinaryOp::Minus => arith(left, right, |l, r| l - r, |l, r| l - r),
+        BinaryOp::Times => arith(left, right, |l, r| l * r, |l, r| l * r),
+        BinaryOp::Min => arith(left, right, |l, r| l.min(r), |l, r| l.min(r)),
+        BinaryOp::Max => arith(left, right, |l, r| l.max(r), |l, r| l.max(r)),
+        BinaryOp::Modulo => {
+            if right.to_rational()?.is_zero() {
+                return Err(EvalError::DivisionByZero);
+            }
+            arith(
+                left,
+                right,
+                |l, r| l.mod_floor(&r),
+                |l, r| &l - &r * (&l / &r).floor(),
+            )
+        }
+        BinaryOp::Divide => {
+            let right = right.to_rational()?;
+            if right.is_zero() {
+                return Err(EvalError::DivisionByZero);
+            }
+            Ok(Value::Real(left.to_rational()? / right))
+        }
+        BinaryOp::Pow => match (&left, &right) {
+            (Value::Int(_) | Value::Real(_), Value::Int(exponent)) => {
+                let base = left.to_rational()?;
+                let exponent = exponent
+                    .to_i32()
+                    .ok_or(EvalError::Unsupported("power with a huge exponent"))?;
+                if exponent < 0 && base.is_zero() {
+                    return Err(EvalError::DivisionByZero);
+                }
+                if pow_bits(&base, exponent.unsigned_abs()) > MAX_POW_BITS {
+                    return Err(EvalError::Unsupported("power with a huge result"));
+                }
+                let res = num::pow(base, exponent.unsigned_abs() as usize);
+                let res = if exponent < 0 { res.recip() } else { res };
+                match left {
+                    Value::Int(_) if res.is_integer() && !exponent.is_negative() => {
+                        Ok(Value::Int(res.to_integer()))
+                    }
+                    _ => Ok(Value::Real(res)),
+                }
+            }
+            _ => {
+                right.to_rational()?;
+                Err(EvalError::Unsupported("power with a non-integer exponent"))
+            }
+        },
+        // like transcendental functions, logarithms are approximated using
+        // f64 arithmetic
+        BinaryOp::Log => {
+            let to_f64 = |value: Value| {
+                value
+                    .to_rational()?
+                    .to_f64()
+                    .ok_or(EvalError::Unsupported("number out of f64 range"))
+            };
+            let (x, base) = (to_f64(left)?, to_f64(right)?);
+            BigRational::from_f64(x.ln() / base.ln())
+                .map(Value::Real)
+                .ok_or(EvalError::Unsupported("non-finite result"))
+        }
+    }
+}
+
+/// Apply an arithmetic operator. The result is an integer if both operands
+/// are integers and a real otherwise.
+fn arith(
+    left: Value,
+    right: Value,
+    int_op: impl FnOnce(BigInt, BigInt) -> BigInt,
+    real_op: impl FnOnce(BigRational, BigRational) -> BigRational,
+) -> Result<Value, EvalError> {
+    match (left, right) {
+        (Value::Int(left), Value::Int(right)) => Ok(Value::Int(int_op(left, right))),
+        (left, right) => Ok(Value::Real(real_op(
+            left.to_rational()?,
+            right.to_rational()?,
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use num::{BigInt, BigRational, Signed, ToPrimitive};
+
+    use crate::{
+        exprs::{
+            BinaryExpression, BinaryOp, ConstantValue, Expression, IteExpression, MathConstant,
+            Number,
+        },
+        test_util::{binary, ident},
+        Identifier,
+    };
+
+    use super::{parse_decimal, EvalError, NonConstantSite, Value};
+
+    #[test]
+    fn test_eval() {
+        let env = HashMap::from([
+            (Identifier("x".to_owned()), Value::Int(BigInt::from(3))),
+            (
+                Identifier("y".to_owned()),
+                Value::Real(BigRational::new(1.into(), 2.into())),
+            ),
+        ]);
+        let expr = ident("x") * Expression::from(2) - Expression::from(1);
+        assert_eq!(expr.eval(&env), Ok(Value::Int(5.into())));
+
+        let expr = binary(BinaryOp::Divide, ident("x"), Expression::from(2));
+        assert_eq!(
+            expr.eval(&env),
+            Ok(Value::Real(BigRational::new(3.into(), 2.into())))
+        );
+
+        let expr = binary(BinaryOp::Less, ident("y"), ident("x")) & true.into();
+        assert_eq!(expr.eval(&env), Ok(Value::Bool(true)));
+
+        let expr = binary(BinaryOp::Modulo, Expression::from(0) - ident("x"), 2.into());
+        assert_eq!(expr.eval(&env), Ok(Value::Int(1.into())));
+
+        let expr = binary(BinaryOp::Pow, ident("x"), Expression::from(0) - 1.into());
+        assert_eq!(
+            expr.eval(&env),
+            Ok(Value::Real(BigRational::new(1.into(), 3.into())))
+        );
+
+        // log_2(8), approximately
+        let expr = binary(BinaryOp::Log, 8.into(), 2.into());
+        let Ok(Value::Real(res)) = expr.eval(&env) else {
+            panic!("expected a real value");
+        };
+        assert!((res.to_f64().unwrap() - 3.0).abs() < 1e-12);
+        assert_eq!(
+            binary(BinaryOp::Log, 8.into(), 1.into()).eval(&env),
+            Err(EvalError::Unsupported("non-finite result"))
+        );
+    }
+
+    #[test]
+    fn test_eval_kleene() {
+        let env = HashMap::from([(Identifier("b".to_owned()), Value::Bool(true))]);
+        let undefined = || ident("z");
+        let eval = |op, left, right| binary(op, left, right).eval(&env);
+        assert_eq!(
+            eval(BinaryOp::And, false.into(), undefined()),
+            Ok(Value::Bool(false))
+        );
+        assert_eq!(
+            eval(BinaryOp::And, undefined(), false.into()),
+            Ok(Value::Bool(false))
+        );
+        assert_eq!(
+            eval(BinaryOp::Or, true.into(), undefined()),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            eval(BinaryOp::Or, undefined(), ident("b")),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            eval(BinaryOp::Implication, false.into(), undefined()),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            eval(BinaryOp::Implication, undefined(), true.into()),
+            Ok(Value::Bool(true))
+        );
+        // nested connectives and other undefined values
+        let division = binary(BinaryOp::Divide, 1.into(), 0.into()).ge(0.into());
+        assert_eq!(
+            eval(
+                BinaryOp::Or,
+                division,
+                binary(BinaryOp::Or, ident("b"), undefined())
+            ),
+            Ok(Value::Bool(true))
+        );
+
+        // the result is not determined
+        let unbound = Err(EvalError::UnboundIdentifier(Identifier("z".to_owned())));
+        assert_eq!(eval(BinaryOp::And, true.into(), undefined()), unbound);
+        assert_eq!(eval(BinaryOp::Or, undefined(), false.into()), unbound);
+        assert_eq!(
+            eval(BinaryOp::Implication, undefined(), false.into()),
+            unbound
+        );
+        // type mismatches are not undefined
+        assert!(matches!(
+            eval(BinaryOp::And, false.into(), 1.into()),
+            Err(EvalError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        let env = HashMap::new();
+        assert_eq!(
+            ident("z").eval(&env),
+            Err(EvalError::UnboundIdentifier(Identifier("z".to_owned())))
+        );
+        assert_eq!(
+            binary(BinaryOp::Divide, 1.into(), 0.into()).eval(&env),
+            Err(EvalError::DivisionByZero)
+        );
+        assert!(matches!(
+            (Expression::from(1) + true.into()).eval(&env),
+            Err(EvalError::TypeMismatch { .. })
+        ));
+        assert_eq!(
+            binary(BinaryOp::Pow, 3.into(), 50_000_000.into()).eval(&env),
+            Err(EvalError::Unsupported("power with a huge result"))
+        );
+        assert_eq!(
+            binary(
+                BinaryOp::Pow,
+                Expression::from(0) - 1.into(),
+                50_000_001.into()
+            )
+            .eval(&env),
+            Ok(Value::Int((-1).into()))
+        );
+    }
+
+    #[test]
+    fn test_parse_decimal() {
+        assert_eq!(
+            parse_decimal("-1.25e-3"),
+            Some(BigRational::new((-1).into(), 800.into()))
+        );
+        assert_eq!(
+            parse_decimal("1e10000"),
+            Some(BigRational::from_integer(BigInt::from(10).pow(10000)))
+        );
+        assert_eq!(parse_decimal("1e100000000"), None);
+        assert_eq!(parse_decimal("1.5e-2147483648"), None);
+    }
+
+    #[test]
+    fn test_eval_ite_lazy() {
+        let env = HashMap::from([
+            (Identifier("x".to_owned()), Value::Int(0.into())),
+            (Identifier("b".to_owned()), Value::Bool(false)),
+        ]);
+        let guarded: Expression = IteExpression {
+            cond: ident("x").eq_expr(0.into()),
+            left: 0.into(),
+            right: binary(BinaryOp::Divide, 1.into(), ident("x")),
+        }
+        .into();
+        assert_eq!(guarded.eval(&env), Ok(Value::Int(0.into())));
+        let unbound: Expression = IteExpression {
+            cond: ident("b"),
+            left: ident("y"),
+            right: 0.into(),
+        }
+        .into();
+        assert_eq!(unbound.eval(&env), Ok(Value::Int(0.into())));
+    }
+
+    #[cfg(feature = "transcendental")]
+    #[test]
+    fn test_transcendental() {
+        use crate::exprs::{UnaryExpression, UnaryOp};
+
+        let json = r#"{"op":"sin","exp":0}"#;
+        let expr: Expression = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            expr,
+            UnaryExpression {
+                op: UnaryOp::Sin,
+                exp: 0.into()
+            }
+            .into()
+        );
+        assert_eq!(serde_json::to_string(&expr).unwrap(), json);
+
+        let env = HashMap::new();
+        assert_eq!(
+            expr.eval(&env),
+            Ok(Value::Real(BigRational::from_integer(0.into())))
+        );
+
+        let expr: Expression = UnaryExpression {
+            op: UnaryOp::Acot,
+            exp: 1.into(),
+        }
+        .into();
+        let Ok(Value::Real(res)) = expr.eval(&env) else {
+            panic!("expected a real value");
+        };
+        assert!((res.to_f64().unwrap() - std::f64::consts::FRAC_PI_4).abs() < 1e-12);
+
+        // the operators of the extension
+        for op in [
+            "sin", "cos", "tan", "cot", "sec", "csc", "asin", "acos", "atan", "acot", "asec",
+            "acsc", "sinh", "cosh", "tanh", "coth", "sech", "csch", "asinh", "acosh", "atanh",
+            "acoth", "asech", "acsch",
+        ] {
+            let json = format!(r#"{{"op":"{}","exp":0}}"#, op);
+            let expr: Expression = serde_json::from_str(&json).unwrap();
+            assert_eq!(serde_json::to_string(&expr).unwrap(), json);
+        }
+        assert!(serde_json::from_str::<Expression>(r#"{"op":"exp","exp":0}"#).is_err());
+    }
+
+    #[test]
+    fn test_math_constant_approx() {
+        let parse = |digits: &str| {
+            let (int, frac) = digits.split_once('.').unwrap();
+            BigRational::new(
+                format!("{}{}", int, frac).parse().unwrap(),
+                BigInt::from(10).pow(frac.len() as u32),
+            )
+        };
+        let pi = parse("3.14159265358979323846264338327950288419716939937510582097494");
+        let e = parse("2.71828182845904523536028747135266249775724709369995957496696");
+        for bits in [1, 8, 32, 64, 128] {
+            let tolerance = BigRational::new(1.into(), BigInt::from(2).pow(bits));
+            let pi_approx = MathConstant::Pi.approx(bits);
+            assert!((pi_approx - &pi).abs() <= tolerance);
+            let e_approx = MathConstant::EulersNumber.approx(bits);
+            assert!((e_approx - &e).abs() <= tolerance);
+        }
+
+        let expr = Expression::Constant(ConstantValue::MathConstant(MathConstant::Pi)) * 2.into();
+        let env = HashMap::new();
+        assert!(matches!(expr.eval(&env), Err(EvalError::Unsupported(_))));
+        let Ok(Value::Real(res)) = expr.eval_approx(&env, 32) else {
+            panic!("expected a real value");
+        };
+        let tolerance = BigRational::new(1.into(), BigInt::from(2).pow(31));
+        assert!((res - pi * BigInt::from(2)).abs() <= tolerance);
+    }
+
+    #[test]
+    fn test_constant_conversion() {
+        let number = ConstantValue::Number(Number::from_f64(0.125).unwrap());
+        let value = Value::from_constant(&number).unwrap();
+        assert_eq!(value, Value::Real(BigRational::new(1.into(), 8.into())));
+        assert_eq!(value.to_constant(), Some(number));
+        assert_eq!(
+            Value::Real(BigRational::new(1.into(), 3.into())).to_constant(),
+            None
+        );
+    }
+
+    #[cfg(feature = "arbitrary-precision")]
+    #[test]
+    fn test_big_int_constant() {
+        let int: BigInt = "123456789012345678901234567890".parse().unwrap();
+        let number = ConstantValue::from_big_int(&int).unwrap();
+        let json = serde_json::to_string(&Expression::Constant(number.clone())).unwrap();
+        assert_eq!(json, "123456789012345678901234567890");
+
+        let parsed: Expression = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, Expression::Constant(number.clone()));
+        assert_eq!(Value::from_constant(&number), Ok(Value::Int(int.clone())));
+        assert_eq!(
+            Value::Int(-int.clone()).to_constant().unwrap().to_string(),
+            format!("-{}", int)
+        );
+    }
+
+    #[test]
+    fn test_require_constant() {
+        let pi = Expression::Constant(ConstantValue::MathConstant(MathConstant::Pi));
+        let expr: Expression = BinaryExpression {
+            op: BinaryOp::Divide,
+            left: pi * 2.into(),
+            right: Expression::from(3) - 1.into(),
+        }
+        .into();
+        assert!(expr.is_constant());
+        assert_eq!(expr.require_constant(), Ok(()));
+
+        let expr = Expression::from(2) * (Expression::from(1) + ident("x"));
+        assert!(!expr.is_constant());
+        assert_eq!(expr.require_constant(), Err(NonConstantSite(ident("x"))));
+        assert_eq!(
+            NonConstantSite(ident("x")).to_string(),
+            "reference to identifier x"
+        );
+    }
+}
@@ -1,19 +1,21 @@
 //! Expressions in JANI.
 
 use std::{
+    collections::{BTreeMap, HashMap},
     fmt::Display,
     ops::{Add, BitAnd, BitOr, Mul, Not, Sub},
 };
 
+use num::{BigInt, Signed, ToPrimitive};
 use serde::{Deserialize, Serialize};
 
-use crate::Identifier;
+use crate::{eval::Value, Identifier};
 
 pub use serde_json::Number;
 
 /// Mathematical constants that cannot be expressed using numeric values and
 /// basic jani-model expressions.
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MathConstant {
     /// Euler's number (the base of the natural logarithm); type real.
     #[serde(rename = "e")]
@@ -32,7 +34,7 @@ impl Display for MathConstant {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 #[serde(untagged)]
 pub enum ConstantValue {
     /// Numeric value; has type int if it is an integer and type real otherwise.
@@ -44,6 +46,25 @@ pub enum ConstantValue {
     MathConstant(MathConstant),
 }
 
+impl ConstantValue {
+    /// Create an exact constant for an integer. Integers beyond the range of
+    /// `i64` and `u64` can only be represented with the `arbitrary-precision`
+    /// feature. Otherwise, `None` is returned for them.
+    pub fn from_big_int(value: &BigInt) -> Option<Self> {
+        if let Some(int) = value.to_i64() {
+            return Some(ConstantValue::Number(int.into()));
+        }
+        if let Some(int) = value.to_u64() {
+            return Some(ConstantValue::Number(int.into()));
+        }
+        if cfg!(feature = "arbitrary-precision") {
+            value.to_string().parse().ok().map(ConstantValue::Number)
+        } else {
+            None
+        }
+    }
+}
+
 impl From<u64> for ConstantValue {
     fn from(value: u64) -> Self {
         ConstantValue::Number(value.into())
@@ -87,7 +108,7 @@ impl Display for ConstantValue {
 /// `right`, or the type of `right` if that is assignable from the type of `left`
 /// (previously: the result type is the most specific type assignable from the
 /// types of then and else).
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 #[serde(tag = "op", rename = "ite")]
 pub struct IteExpression {
     #[serde(rename = "if")]
@@ -99,7 +120,7 @@ pub struct IteExpression {
 }
 
 /// JANI operators with one operand.
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum UnaryOp {
     /// Negation: computes `¬exp`.
     #[serde(rename = "¬")]
@@ -115,17 +136,125 @@ pub enum UnaryOp {
     /// global variable.
     #[serde(rename = "der")]
     Derivative,
+    /// Sine (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "sin")]
+    Sin,
+    /// Cosine (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "cos")]
+    Cos,
+    /// Tangent (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "tan")]
+    Tan,
+    /// Cotangent (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "cot")]
+    Cot,
+    /// Secant (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "sec")]
+    Sec,
+    /// Cosecant (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "csc")]
+    Csc,
+    /// Arc sine (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "asin")]
+    Asin,
+    /// Arc cosine (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "acos")]
+    Acos,
+    /// Arc tangent (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "atan")]
+    Atan,
+    /// Arc cotangent (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "acot")]
+    Acot,
+    /// Arc secant (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "asec")]
+    Asec,
+    /// Arc cosecant (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "acsc")]
+    Acsc,
+    /// Hyperbolic sine (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "sinh")]
+    Sinh,
+    /// Hyperbolic cosine (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "cosh")]
+    Cosh,
+    /// Hyperbolic tangent (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "tanh")]
+    Tanh,
+    /// Hyperbolic cotangent (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "coth")]
+    Coth,
+    /// Hyperbolic secant (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "sech")]
+    Sech,
+    /// Hyperbolic cosecant (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "csch")]
+    Csch,
+    /// Inverse hyperbolic sine (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "asinh")]
+    Asinh,
+    /// Inverse hyperbolic cosine (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "acosh")]
+    Acosh,
+    /// Inverse hyperbolic tangent (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "atanh")]
+    Atanh,
+    /// Inverse hyperbolic cotangent (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "acoth")]
+    Acoth,
+    /// Inverse hyperbolic secant (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "asech")]
+    Asech,
+    /// Inverse hyperbolic cosecant (trigonometric-functions extension).
+    #[cfg(feature = "transcendental")]
+    #[serde(rename = "acsch")]
+    Acsch,
+}
+
+impl UnaryOp {
+    /// Whether this operator is a transcendental function. Its values can in
+    /// general only be approximated.
+    pub fn is_transcendental(&self) -> bool {
+        match self {
+            UnaryOp::Not | UnaryOp::Floor | UnaryOp::Ceil | UnaryOp::Derivative => false,
+            #[cfg(feature = "transcendental")]
+            _ => true,
+        }
+    }
 }
 
 /// JANI expressions with one operand.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct UnaryExpression {
     pub op: UnaryOp,
     pub exp: Expression,
 }
 
 /// JANI operators with two operands.
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BinaryOp {
     #[serde(rename = "∨")]
     Or,
@@ -149,8 +278,10 @@ pub enum BinaryOp {
     Modulo,
     #[serde(rename = "/")]
     Divide,
+    /// Power: computes `left^right`.
     #[serde(rename = "pow")]
     Pow,
+    /// Logarithm: computes the logarithm of `left` to the base `right`.
     #[serde(rename = "log")]
     Log,
 
@@ -167,8 +298,73 @@ pub enum BinaryOp {
     // TODO: add other derived operators!
 }
 
+/// The kind of the result type of a [`BinaryOp`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResultTypeKind {
+    Bool,
+    /// An integer if both operands are integers, and a real otherwise.
+    Numeric,
+    Real,
+}
+
+impl BinaryOp {
+    /// Whether `a op b` equals `b op a` for all operands.
+    pub fn is_commutative(&self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Or
+                | BinaryOp::And
+                | BinaryOp::Equals
+                | BinaryOp::NotEquals
+                | BinaryOp::Plus
+                | BinaryOp::Times
+                | BinaryOp::Min
+                | BinaryOp::Max
+        )
+    }
+
+    /// Whether `(a op b) op c` equals `a op (b op c)` for all operands.
+    /// Equality is only associative for booleans and thus excluded.
+    pub fn is_associative(&self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Or
+                | BinaryOp::And
+                | BinaryOp::Plus
+                | BinaryOp::Times
+                | BinaryOp::Min
+                | BinaryOp::Max
+        )
+    }
+
+    /// The kind of the result type, like in [`Expression::eval`]. Division
+    /// and logarithms always yield reals, while powers of integers with
+    /// non-negative exponents are integers.
+    pub fn result_type_kind(&self) -> ResultTypeKind {
+        match self {
+            BinaryOp::Or
+            | BinaryOp::And
+            | BinaryOp::Equals
+            | BinaryOp::NotEquals
+            | BinaryOp::Less
+            | BinaryOp::LessOrEqual
+            | BinaryOp::Implication
+            | BinaryOp::Greater
+            | BinaryOp::GreaterOrEqual => ResultTypeKind::Bool,
+            BinaryOp::Plus
+            | BinaryOp::Minus
+            | BinaryOp::Times
+            | BinaryOp::Modulo
+            | BinaryOp::Pow
+            | BinaryOp::Min
+            | BinaryOp::Max => ResultTypeKind::Numeric,
+            BinaryOp::Divide | BinaryOp::Log => ResultTypeKind::Real,
+        }
+    }
+}
+
 /// JANI expressions with two operands.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct BinaryExpression {
     pub op: BinaryOp,
     pub left: Expression,
@@ -176,16 +372,19 @@ pub struct BinaryExpression {
 }
 
 /// Nondeterministic selection (needs
-/// [`super::models::ModelFeature::NondetSelection`]).
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+/// [`super::models::ModelFeature::NondetSelection`]). Its value is any value
+/// of `var` that satisfies the constraint `exp`. The domain of `var` can be
+/// bounded by adding the bounds to `exp`, see also
+/// [`Expression::to_z3_with_typed_witnesses`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 #[serde(tag = "op", rename = "nondet")]
 pub struct NondetSelectionExpression {
-    var: Identifier,
-    exp: Expression,
+    pub var: Identifier,
+    pub exp: Expression,
 }
 
 /// Function call (needs [`super::models::ModelFeature::Functions`]).
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 #[serde(tag = "op", rename = "call")]
 pub struct CallExpression {
     pub function: Identifier,
@@ -193,7 +392,7 @@ pub struct CallExpression {
 }
 
 /// JANI expressions.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 #[serde(untagged)]
 pub enum Expression {
     Constant(ConstantValue),
@@ -207,6 +406,116 @@ pub enum Expression {
     Call(Box<CallExpression>),
 }
 
+impl Expression {
+    /// Create a new expression of the same kind with each direct
+    /// subexpression replaced by the result of `f`.
+    pub(crate) fn map_children(&self, mut f: impl FnMut(&Expression) -> Expression) -> Expression {
+        match self {
+            Expression::Constant(_) | Expression::Identifier(_) => self.clone(),
+            Expression::IfThenElse(ite) => IteExpression {
+                cond: f(&ite.cond),
+                left: f(&ite.left),
+                right: f(&ite.right),
+            }
+            .into(),
+            Expression::Unary(unary) => UnaryExpression {
+                op: unary.op,
+                exp: f(&unary.exp),
+            }
+            .into(),
+            Expression::Binary(binary) => BinaryExpression {
+                op: binary.op,
+                left: f(&binary.left),
+                right: f(&binary.right),
+            }
+            .into(),
+            Expression::NondetSelection(nondet) => {
+                Expression::NondetSelection(Box::new(NondetSelectionExpression {
+                    var: nondet.var.clone(),
+                    exp: f(&nondet.exp),
+                }))
+            }
+            Expression::Call(call) => CallExpression {
+                function: call.function.clone(),
+                args: call.args.iter().map(f).collect(),
+            }
+            .into(),
+        }
+    }
+
+    /// The direct subexpressions from left to right.
+    pub(crate) fn children(&self) -> Vec<&Expression> {
+        match self {
+            Expression::Constant(_) | Expression::Identifier(_) => vec![],
+            Expression::IfThenElse(ite) => vec![&ite.cond, &ite.left, &ite.right],
+            Expression::Unary(unary) => vec![&unary.exp],
+            Expression::Binary(binary) => vec![&binary.left, &binary.right],
+            Expression::NondetSelection(nondet) => vec![&nondet.exp],
+            Expression::Call(call) => call.args.iter().collect(),
+        }
+    }
+
+    /// The compound subexpressions (i.e. neither constants nor identifiers)
+    /// that occur more than once in this expression, together with their
+    /// number of occurrences. They are ordered by their first occurrence in a
+    /// pre-order traversal, so enclosing expressions come before their parts.
+    ///
+    /// Subexpressions are compared structurally. Identifiers bound by
+    /// different nondeterministic selections are not distinguished.
+    pub fn common_subexpressions(&self) -> Vec<(Expression, usize)> {
+        let mut counts: HashMap<&Expression, usize> = HashMap::new();
+        let mut order = Vec::new();
+        let mut worklist = vec![self];
+        while let Some(expr) = worklist.pop() {
+            if let Expression::Constant(_) | Expression::Identifier(_) = expr {
+                continue;
+            }
+            let count = counts.entry(expr).or_insert(0);
+            if *count == 0 {
+                order.push(expr);
+            }
+            *count += 1;
+            worklist.extend(expr.children().into_iter().rev());
+        }
+        order
+            .into_iter()
+            .filter(|expr| counts[expr] > 1)
+            .map(|expr| (expr.clone(), counts[expr]))
+            .collect()
+    }
+
+    /// Whether this expression or one of its subexpressions satisfies `pred`.
+    /// The search stops at the first match.
+    pub fn contains(&self, pred: impl Fn(&Expression) -> bool) -> bool {
+        let mut worklist = vec![self];
+        while let Some(expr) = worklist.pop() {
+            if pred(expr) {
+                return true;
+            }
+            worklist.extend(expr.children());
+        }
+        false
+    }
+
+    /// The largest absolute value of the integer constants in this
+    /// expression, or `None` if there are none. Real numbers and
+    /// mathematical constants are ignored.
+    pub fn max_abs_constant(&self) -> Option<BigInt> {
+        let mut max: Option<BigInt> = None;
+        let mut worklist = vec![self];
+        while let Some(expr) = worklist.pop() {
+            if let Expression::Constant(value) = expr {
+                if let Ok(Value::Int(int)) = Value::from_constant(value) {
+                    // `None` is smaller than any `Some`
+                    max = max.max(Some(int.abs()));
+                }
+            }
+            worklist.extend(expr.children());
+        }
+        max
+    }
+}
+
 impl<T> From<T> for Expression
 where
     T: Into<ConstantValue>,
@@ -329,4 +638,194 @@ impl Mul for Expression {
     }
 }
 
+impl Expression {
+    fn binary(self, op: BinaryOp, rhs: Expression) -> Expression {
+        BinaryExpression {
+            op,
+            left: self,
+            right: rhs,
+        }
+        .into()
+    }
+
+    /// The comparison `self < rhs`.
+    pub fn lt(self, rhs: Expression) -> Expression {
+        self.binary(BinaryOp::Less, rhs)
+    }
+
+    /// The comparison `self ≤ rhs`.
+    pub fn le(self, rhs: Expression) -> Expression {
+        self.binary(BinaryOp::LessOrEqual, rhs)
+    }
+
+    /// The comparison `self > rhs`.
+    pub fn gt(self, rhs: Expression) -> Expression {
+        self.binary(BinaryOp::Greater, rhs)
+    }
+
+    /// The comparison `self ≥ rhs`.
+    pub fn ge(self, rhs: Expression) -> Expression {
+        self.binary(BinaryOp::GreaterOrEqual, rhs)
+    }
+
+    /// The equality `self = rhs`. Unlike `==`, this builds an expression.
+    pub fn eq_expr(self, rhs: Expression) -> Expression {
+        self.binary(BinaryOp::Equals, rhs)
+    }
+
+    /// The disequality `self ≠ rhs`. Unlike `!=`, this builds an expression.
+    pub fn ne_expr(self, rhs: Expression) -> Expression {
+        self.binary(BinaryOp::NotEquals, rhs)
+    }
+
+    /// Serialize this expression as pretty-printed JANI JSON, e.g. for golden
+    /// files. The keys of every object are sorted so that the output does not
+    /// depend on the field order of the serializer, and operators like `∧`
+    /// are printed as they are instead of being escaped.
+    pub fn to_pretty_json(&self) -> String {
+        let value = serde_json::to_value(self).unwrap();
+        serde_json::to_string_pretty(&sort_keys(value)).unwrap()
+    }
+}
+
+/// Rebuild every object of the value with its keys in sorted order.
+fn sort_keys(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Array(values) => values.into_iter().map(sort_keys).collect(),
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .map(|(key, value)| (key, sort_keys(value)))
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .collect(),
+        value => value,
+    }
+}
+
 pub type LValue = Identifier;
+
+#[cfg(test)]
+mod test {
+    use crate::test_util::{binary, ident};
+
+    use super::{
+        BinaryOp, ConstantValue, Expression, IteExpression, MathConstant, ResultTypeKind,
+        UnaryExpression, UnaryOp,
+    };
+
+    #[test]
+    fn test_operators() {
+        let guard = !(ident("x") + 1.into()).lt(ident("y") * 2.into())
+            | ident("b") & (ident("x") - ident("y")).eq_expr(0.into());
+        let explicit = binary(
+            BinaryOp::Or,
+            UnaryExpression {
+                op: UnaryOp::Not,
+                exp: binary(
+                    BinaryOp::Less,
+                    binary(BinaryOp::Plus, ident("x"), 1.into()),
+                    binary(BinaryOp::Times, ident("y"), 2.into()),
+                ),
+            }
+            .into(),
+            binary(
+                BinaryOp::And,
+                ident("b"),
+                binary(
+                    BinaryOp::Equals,
+                    binary(BinaryOp::Minus, ident("x"), ident("y")),
+                    0.into(),
+                ),
+            ),
+        );
+        assert_eq!(guard, explicit);
+
+        for (built, op) in [
+            (ident("x").le(ident("y")), BinaryOp::LessOrEqual),
+            (ident("x").gt(ident("y")), BinaryOp::Greater),
+            (ident("x").ge(ident("y")), BinaryOp::GreaterOrEqual),
+            (ident("x").ne_expr(ident("y")), BinaryOp::NotEquals),
+        ] {
+            assert_eq!(built, binary(op, ident("x"), ident("y")));
+        }
+    }
+
+    #[test]
+    fn test_max_abs_constant() {
+        let real = |value: f64| Expression::Constant(ConstantValue::try_from(value).unwrap());
+        let pi = Expression::Constant(ConstantValue::MathConstant(MathConstant::Pi));
+        let expr = (ident("x") + Expression::Constant(ConstantValue::Number((-7).into())))
+            .lt(real(100.5) * 3.into())
+            | pi.clone().gt(real(1e9));
+        assert_eq!(expr.max_abs_constant(), Some(7.into()));
+
+        let expr = ident("x") * real(0.5) + pi;
+        assert_eq!(expr.max_abs_constant(), None);
+    }
+
+    #[test]
+    fn test_binary_op_metadata() {
+        assert!(BinaryOp::Plus.is_commutative());
+        assert!(BinaryOp::Plus.is_associative());
+        assert!(BinaryOp::Equals.is_commutative());
+        assert!(!BinaryOp::Equals.is_associative());
+        assert!(!BinaryOp::Minus.is_commutative());
+        assert!(!BinaryOp::Implication.is_associative());
+        assert_eq!(BinaryOp::Less.result_type_kind(), ResultTypeKind::Bool);
+        assert_eq!(BinaryOp::Plus.result_type_kind(), ResultTypeKind::Numeric);
+        assert_eq!(BinaryOp::Divide.result_type_kind(), ResultTypeKind::Real);
+    }
+
+    #[test]
+    fn test_contains() {
+        let is_derivative =
+            |expr: &Expression| matches!(expr, Expression::Unary(u) if u.op == UnaryOp::Derivative);
+        let is_division =
+            |expr: &Expression| matches!(expr, Expression::Binary(b) if b.op == BinaryOp::Divide);
+        let derivative: Expression = UnaryExpression {
+            op: UnaryOp::Derivative,
+            exp: ident("x"),
+        }
+        .into();
+        let guard = ident("b") & (ident("y") * derivative).lt(1.into());
+        assert!(guard.contains(is_derivative));
+        assert!(!guard.contains(is_division));
+
+        let guard = ident("x").le(binary(BinaryOp::Divide, ident("y"), 2.into()));
+        assert!(guard.contains(is_division));
+        assert!(!guard.contains(is_derivative));
+        assert!(guard.contains(|expr| *expr == ident("y")));
+    }
+
+    #[test]
+    fn test_to_pretty_json() {
+        let expr: Expression = IteExpression {
+            cond: !ident("b") & ident("x").le(0.into()),
+            left: Expression::Constant(ConstantValue::try_from(0.5).unwrap()),
+            right: ident("x"),
+        }
+        .into();
+        let expected = r#"{
+  "else": "x",
+  "if": {
+    "left": {
+      "exp": "b",
+      "op": "¬"
+    },
+    "op": "∧",
+    "right": {
+      "left": "x",
+      "op": "≤",
+      "right": 0
+    }
+  },
+  "op": "ite",
+  "then": 0.5
+}"#;
+        assert_eq!(expr.to_pretty_json(), expected);
+        assert_eq!(
+            serde_json::from_str::<Expression>(&expr.to_pretty_json()).unwrap(),
+            expr
+        );
+    }
+}
@@ -5,9 +5,19 @@
 //! The main type is [`models::Model`], which can be read using [`from_str`] and
 //! [`from_reader`] and serialized with [`to_string`].
 
+pub mod error;
+pub mod eval;
 pub mod exprs;
+pub mod linear;
+#[cfg(feature = "z3")]
+pub mod lower;
 pub mod models;
+mod normalize;
 pub mod properties;
+mod simplify;
+pub mod smtlib;
+#[cfg(test)]
+mod test_util;
 pub mod types;
 
 use std::{fmt::Display, io::Read};
@@ -18,7 +28,7 @@ use serde::{Deserialize, Serialize};
 /// An identifier.
 ///
 /// Must not contain line breaks.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Identifier(pub String);
 
 impl Display for Identifier {
@@ -0,0 +1,170 @@
+//! Linear forms of JANI expressions.
+
+use std::{collections::BTreeMap, ops::Mul};
+
+use num::{BigRational, Zero};
+
+use crate::{
+    eval::Value,
+    exprs::{BinaryOp, ConstantValue, Expression},
+    Identifier,
+};
+
+/// A linear combination `c1 * x1 + ... + cn * xn + constant`. Variables with a
+/// zero coefficient are never stored, so two linear forms are equal iff they
+/// denote the same function.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LinearForm {
+    pub terms: BTreeMap<Identifier, BigRational>,
+    pub constant: BigRational,
+}
+
+impl LinearForm {
+    fn constant(value: BigRational) -> Self {
+        LinearForm {
+            terms: BTreeMap::new(),
+            constant: value,
+        }
+    }
+
+    fn variable(ident: Identifier) -> Self {
+        LinearForm {
+            terms: BTreeMap::from([(ident, BigRational::from_integer(1.into()))]),
+            constant: BigRational::zero(),
+        }
+    }
+
+    /// The constant value if there are no variables.
+    fn as_constant(&self) -> Option<&BigRational> {
+        self.terms.is_empty().then_some(&self.constant)
+    }
+
+    /// Add `factor * other` to this linear form.
+    fn add_scaled(mut self, other: LinearForm, factor: &BigRational) -> Self {
+        for (ident, coeff) in other.terms {
+            *self.terms.entry(ident).or_insert_with(BigRational::zero) += coeff * factor;
+        }
+        self.terms.retain(|_, coeff| !coeff.is_zero());
+        self.constant += other.constant * factor;
+        self
+    }
+}
+
+impl Mul<&BigRational> for LinearForm {
+    type Output = LinearForm;
+
+    fn mul(self, rhs: &BigRational) -> Self::Output {
+        LinearForm::default().add_scaled(self, rhs)
+    }
+}
+
+impl Expression {
+    /// Compute the linear form of this expression if it is a linear
+    /// combination of identifiers. Supported are numeric literals,
+    /// identifiers, `+`, `-`, and `*` or `/` where one operand (the divisor for
+    /// `/`) is constant. Returns `None` for all other expressions, e.g. for
+    /// products of two variables.
+    ///
+    /// Identifiers are assumed to be numeric.
+    pub fn to_linear_form(&self) -> Option<LinearForm> {
+        match self {
+            Expression::Constant(value @ ConstantValue::Number(_)) => {
+                match Value::from_constant(value).ok()? {
+                    Value::Int(int) => Some(LinearForm::constant(BigRational::from_integer(int))),
+                    Value::Real(real) => Some(LinearForm::constant(real)),
+                    Value::Bool(_) => None,
+                }
+            }
+            Expression::Identifier(ident) => Some(LinearForm::variable(ident.clone())),
+            Expression::Binary(binary) => {
+                let left = binary.left.to_linear_form()?;
+                let right = binary.right.to_linear_form()?;
+                let one = BigRational::from_integer(1.into());
+                match binary.op {
+                    BinaryOp::Plus => Some(left.add_scaled(right, &one)),
+                    BinaryOp::Minus => Some(left.add_scaled(right, &-one)),
+                    BinaryOp::Times => match (left.as_constant(), right.as_constant()) {
+                        (Some(factor), _) => {
+                            let factor = factor.clone();
+                            Some(right * &factor)
+                        }
+                        (None, Some(factor)) => {
+                            let factor = factor.clone();
+                            Some(left * &factor)
+                        }
+                        (None, None) => None,
+                    },
+                    BinaryOp::Divide => {
+                        let divisor = right.as_constant()?;
+                        if divisor.is_zero() {
+                            return None;
+                        }
+                        let factor = divisor.recip();
+                        Some(left * &factor)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use num::BigRational;
+
+    use crate::{
+        exprs::{BinaryExpression, BinaryOp, Expression},
+        test_util::ident,
+        Identifier,
+    };
+
+    use super::LinearForm;
+
+    fn rational(numer: i64, denom: i64) -> BigRational {
+        BigRational::new(numer.into(), denom.into())
+    }
+
+    #[test]
+    fn test_to_linear_form() {
+        let expr = Expression::from(2) * ident("x") + 3.into() - ident("x");
+        assert_eq!(
+            expr.to_linear_form(),
+            Some(LinearForm {
+                terms: BTreeMap::from([(Identifier("x".to_owned()), rational(1, 1))]),
+                constant: rational(3, 1),
+            })
+        );
+
+        let expr: Expression = BinaryExpression {
+            op: BinaryOp::Divide,
+            left: ident("x") + ident("y") - ident("y"),
+            right: 4.into(),
+        }
+        .into();
+        assert_eq!(
+            expr.to_linear_form(),
+            Some(LinearForm {
+                terms: BTreeMap::from([(Identifier("x".to_owned()), rational(1, 4))]),
+                constant: rational(0, 1),
+            })
+        );
+    }
+
+    #[test]
+    fn test_to_linear_form_nonlinear() {
+        let expr = ident("x") * ident("y") + 1.into();
+        assert_eq!(expr.to_linear_form(), None);
+
+        let expr: Expression = BinaryExpression {
+            op: BinaryOp::Pow,
+            left: ident("x"),
+            right: 2.into(),
+        }
+        .into();
+        assert_eq!(expr.to_linear_form(), None);
+    }
+}
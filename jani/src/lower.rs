@@ -0,0 +1,1072 @@
+//! Lowering of JANI expressions to Z3 terms.
+
+use std::{collections::HashMap, fmt::Display};
+
+use num::{Signed, ToPrimitive};
+
+use z3::{
+    ast::{Ast, Bool, Dynamic, Int, Real},
+    Context, SortKind,
+};
+use z3rro::model::{ConcreteValue, InstrumentedModel, SmtEval, SmtEvalError};
+
+use crate::{
+    eval::Value,
+    exprs::{BinaryOp, ConstantValue, Expression, UnaryOp},
+    types::JaniType,
+    Identifier,
+};
+
+/// Errors that can occur when lowering a JANI expression to Z3.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LowerError {
+    /// The identifier has no Z3 term in the environment.
+    UnboundIdentifier(Identifier),
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// The expression has no exact counterpart in Z3.
+    Unsupported(&'static str),
+}
+
+impl Display for LowerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LowerError::UnboundIdentifier(ident) => write!(f, "unbound identifier {}", ident),
+            LowerError::TypeMismatch { expected, found } => {
+                write!(f, "expected term of type {}, found {}", expected, found)
+            }
+            LowerError::Unsupported(what) => write!(f, "cannot lower {} to Z3", what),
+        }
+    }
+}
+
+impl std::error::Error for LowerError {}
+
+/// Errors that can occur when evaluating a JANI expression in a model, see
+/// [`eval_jani_expr_in_model`].
+#[derive(Debug, Clone)]
+pub enum ModelEvalError {
+    Lower(LowerError),
+    Model(SmtEvalError),
+}
+
+impl Display for ModelEvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelEvalError::Lower(err) => err.fmt(f),
+            ModelEvalError::Model(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ModelEvalError {}
+
+impl From<LowerError> for ModelEvalError {
+    fn from(err: LowerError) -> Self {
+        ModelEvalError::Lower(err)
+    }
+}
+
+impl From<SmtEvalError> for ModelEvalError {
+    fn from(err: SmtEvalError) -> Self {
+        ModelEvalError::Model(err)
+    }
+}
+
+/// A numeric Z3 term.
+enum Num<'ctx> {
+    Int(Int<'ctx>),
+    Real(Real<'ctx>),
+}
+
+impl<'ctx> Num<'ctx> {
+    fn to_real(&self) -> Real<'ctx> {
+        match self {
+            Num::Int(int) => int.to_real(),
+            Num::Real(real) => real.clone(),
+        }
+    }
+
+    fn into_dynamic(self) -> Dynamic<'ctx> {
+        match self {
+            Num::Int(int) => int.into(),
+            Num::Real(real) => real.into(),
+        }
+    }
+}
+
+/// Terms of lowered expressions that are reused across calls of
+/// [`Expression::to_z3_cached`], e.g. for guards shared by many edges. The
+/// terms of all compound subexpressions are stored, keyed by their structure.
+///
+/// The terms depend on the environment of the lowering, so the cache is
+/// cleared whenever it is used with a different environment.
+#[derive(Debug, Default)]
+pub struct LoweringCache<'ctx> {
+    env: HashMap<Identifier, Dynamic<'ctx>>,
+    terms: HashMap<Expression, Dynamic<'ctx>>,
+    hits: usize,
+}
+
+impl<'ctx> LoweringCache<'ctx> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of cached terms.
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// The number of subexpressions whose terms were taken from the cache
+    /// instead of being lowered again.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn clear(&mut self) {
+        self.terms.clear();
+    }
+}
+
+/// The state of a lowering.
+#[derive(Default)]
+struct Lowering<'ctx, 'e> {
+    /// The terms of compound subexpressions if they are shared, see
+    /// [`Expression::to_z3_shared`].
+    shared: Option<HashMap<&'e Expression, Dynamic<'ctx>>>,
+    /// The constraints on the witnesses of nondeterministic selections if
+    /// they are supported, see [`Expression::to_z3_with_witnesses`].
+    witness_constraints: Option<Vec<Bool<'ctx>>>,
+    /// The types of selected variables, see
+    /// [`Expression::to_z3_with_typed_witnesses`].
+    witness_types: Option<&'e HashMap<Identifier, JaniType>>,
+    /// The terms reused across lowerings, see [`Expression::to_z3_cached`].
+    cache: Option<&'e mut LoweringCache<'ctx>>,
+    /// The number of compound subexpressions lowered so far, i.e. whose
+    /// terms were not shared or cached.
+    lowered: usize,
+}
+
+impl Expression {
+    /// Lower this expression to a Z3 term. The terms for identifiers are
+    /// taken from `env`. Integers and reals are mixed like in JANI, i.e.
+    /// integer operands are converted to reals if the other operand is a
+    /// real.
+    ///
+    /// Division is always real division, so `7 / 2` is `3.5`. Modulo is only
+    /// supported for integers and rounds the quotient down like
+    /// [`Expression::eval`], so the result has the sign of the divisor, e.g.
+    /// `7 % -3` is `-2`. Like in Z3, division and modulo by zero are
+    /// unspecified.
+    ///
+    /// Powers are only supported if the exponent is a natural number literal
+    /// of at most 64, and are expanded into products. Math constants, other
+    /// powers, logarithms, derivatives, transcendental functions,
+    /// nondeterministic selections and function calls are not supported.
+    pub fn to_z3<'ctx>(
+        &self,
+        ctx: &'ctx Context,
+        env: &HashMap<Identifier, Dynamic<'ctx>>,
+    ) -> Result<Dynamic<'ctx>, LowerError> {
+        self.lower(ctx, env, &mut Lowering::default())
+    }
+
+    /// Like [`Self::to_z3`], but structurally equal subexpressions (see
+    /// [`Self::common_subexpressions`]) are lowered only once. Z3 already
+    /// shares structurally equal terms, so the result is the same term as
+    /// with [`Self::to_z3`], but large repeated subexpressions are not
+    /// traversed again.
+    pub fn to_z3_shared<'ctx>(
+        &self,
+        ctx: &'ctx Context,
+        env: &HashMap<Identifier, Dynamic<'ctx>>,
+    ) -> Result<Dynamic<'ctx>, LowerError> {
+        let mut lowering = Lowering {
+            shared: Some(HashMap::new()),
+            ..Default::default()
+        };
+        self.lower(ctx, env, &mut lowering)
+    }
+
+    /// Like [`Self::to_z3`], but the terms of compound subexpressions are
+    /// looked up in and added to `cache`, so expressions lowered repeatedly
+    /// are only built once. Subexpressions are shared like in
+    /// [`Self::to_z3_shared`].
+    pub fn to_z3_cached<'ctx>(
+        &self,
+        ctx: &'ctx Context,
+        env: &HashMap<Identifier, Dynamic<'ctx>>,
+        cache: &mut LoweringCache<'ctx>,
+    ) -> Result<Dynamic<'ctx>, LowerError> {
+        if cache.env != *env {
+            cache.terms.clear();
+            cache.env = env.clone();
+        }
+        let mut lowering = Lowering {
+            cache: Some(cache),
+            ..Default::default()
+        };
+        self.lower(ctx, env, &mut lowering)
+    }
+
+    /// Like [`Self::to_z3`], but nondeterministic selections are supported.
+    /// Each selection is lowered to a fresh constant, its witness, and the
+    /// returned constraints state that every witness satisfies the condition
+    /// of its selection. The constraints must be added as assumptions so that
+    /// a model of the solver resolves the nondeterminism.
+    ///
+    /// JANI does not declare the type of the selected variable, so witnesses
+    /// are reals. See [`Self::to_z3_with_typed_witnesses`] to restrict them.
+    pub fn to_z3_with_witnesses<'ctx>(
+        &self,
+        ctx: &'ctx Context,
+        env: &HashMap<Identifier, Dynamic<'ctx>>,
+    ) -> Result<(Dynamic<'ctx>, Vec<Bool<'ctx>>), LowerError> {
+        let mut lowering = Lowering {
+            witness_constraints: Some(Vec::new()),
+            ..Default::default()
+        };
+        let term = self.lower(ctx, env, &mut lowering)?;
+        Ok((term, lowering.witness_constraints.unwrap()))
+    }
+
+    /// Like [`Self::to_z3_with_witnesses`], but the witnesses of selected
+    /// variables with a type in `var_types` have the sort of that type. For
+    /// bounded integer types, the returned constraints also include the range
+    /// constraints of the witnesses (see [`bounded_int_constraint`]), so
+    /// witnesses stay within the legal domain of the variable.
+    pub fn to_z3_with_typed_witnesses<'ctx>(
+        &self,
+        ctx: &'ctx Context,
+        env: &HashMap<Identifier, Dynamic<'ctx>>,
+        var_types: &HashMap<Identifier, JaniType>,
+    ) -> Result<(Dynamic<'ctx>, Vec<Bool<'ctx>>), LowerError> {
+        let mut lowering = Lowering {
+            witness_constraints: Some(Vec::new()),
+            witness_types: Some(var_types),
+            ..Default::default()
+        };
+        let term = self.lower(ctx, env, &mut lowering)?;
+        Ok((term, lowering.witness_constraints.unwrap()))
+    }
+
+    /// Lower this expression, looking up and storing the terms of compound
+    /// subexpressions if they are shared.
+    fn lower<'ctx, 'e>(
+        &'e self,
+        ctx: &'ctx Context,
+        env: &HashMap<Identifier, Dynamic<'ctx>>,
+        lowering: &mut Lowering<'ctx, 'e>,
+    ) -> Result<Dynamic<'ctx>, LowerError> {
+        if let Some(term) = lowering.shared.as_ref().and_then(|shared| shared.get(self)) {
+            return Ok(term.clone());
+        }
+        if let Some(cache) = &mut lowering.cache {
+            if let Some(term) = cache.terms.get(self) {
+                cache.hits += 1;
+                return Ok(term.clone());
+            }
+        }
+        let term = self.lower_uncached(ctx, env, lowering)?;
+        if !matches!(self, Expression::Constant(_) | Expression::Identifier(_)) {
+            lowering.lowered += 1;
+            if let Some(shared) = &mut lowering.shared {
+                shared.insert(self, term.clone());
+            }
+            if let Some(cache) = &mut lowering.cache {
+                cache.terms.insert(self.clone(), term.clone());
+            }
+        }
+        Ok(term)
+    }
+
+    fn lower_uncached<'ctx, 'e>(
+        &'e self,
+        ctx: &'ctx Context,
+        env: &HashMap<Identifier, Dynamic<'ctx>>,
+        lowering: &mut Lowering<'ctx, 'e>,
+    ) -> Result<Dynamic<'ctx>, LowerError> {
+        match self {
+            Expression::Constant(value) => lower_constant_value(ctx, value),
+            Expression::Identifier(ident) => env
+                .get(ident)
+                .cloned()
+                .ok_or_else(|| LowerError::UnboundIdentifier(ident.clone())),
+            Expression::IfThenElse(ite) => {
+                let cond = as_bool(&ite.cond.lower(ctx, env, lowering)?)?;
+                let left = ite.left.lower(ctx, env, lowering)?;
+                let right = ite.right.lower(ctx, env, lowering)?;
+                if left.sort_kind() == SortKind::Bool {
+                    return Ok(cond.ite(&left, &as_bool(&right)?.into()));
+                }
+                Ok(match (as_num(&left)?, as_num(&right)?) {
+                    (Num::Int(left), Num::Int(right)) => cond.ite(&left, &right).into(),
+                    (left, right) => cond.ite(&left.to_real(), &right.to_real()).into(),
+                })
+            }
+            Expression::Unary(unary) => {
+                let operand = unary.exp.lower(ctx, env, lowering)?;
+                match unary.op {
+                    UnaryOp::Not => Ok(as_bool(&operand)?.not().into()),
+                    UnaryOp::Floor => Ok(match as_num(&operand)? {
+                        Num::Int(int) => int.into(),
+                        Num::Real(real) => real.to_int().into(),
+                    }),
+                    UnaryOp::Ceil => Ok(match as_num(&operand)? {
+                        Num::Int(int) => int.into(),
+                        Num::Real(real) => real.unary_minus().to_int().unary_minus().into(),
+                    }),
+                    UnaryOp::Derivative => Err(LowerError::Unsupported("derivative")),
+                    #[cfg(feature = "transcendental")]
+                    _ => Err(LowerError::Unsupported("transcendental function")),
+                }
+            }
+            Expression::Binary(binary) => {
+                let left = binary.left.lower(ctx, env, lowering)?;
+                if binary.op == BinaryOp::Pow {
+                    return lower_pow(ctx, left, &binary.right);
+                }
+                let right = binary.right.lower(ctx, env, lowering)?;
+                lower_binary(ctx, binary.op, left, right)
+            }
+            Expression::NondetSelection(nondet) => {
+                if lowering.witness_constraints.is_none() {
+                    return Err(LowerError::Unsupported("nondeterministic selection"));
+                }
+                let name = nondet.var.0.as_str();
+                let ty = lowering
+                    .witness_types
+                    .and_then(|types| types.get(&nondet.var));
+                let witness: Dynamic<'ctx> = match ty {
+                    Some(JaniType::Bool) => Bool::fresh_const(ctx, name).into(),
+                    Some(JaniType::Int | JaniType::BoundedInt { .. }) => {
+                        Int::fresh_const(ctx, name).into()
+                    }
+                    Some(JaniType::Real) | None => Real::fresh_const(ctx, name).into(),
+                };
+                if let Some(JaniType::BoundedInt { lower, upper }) = ty {
+                    let bound =
+                        bounded_int_constraint(ctx, &witness.as_int().unwrap(), *lower, *upper);
+                    lowering.witness_constraints.as_mut().unwrap().push(bound);
+                }
+                let mut inner_env = env.clone();
+                inner_env.insert(nondet.var.clone(), witness.clone());
+                // the selected variable shadows outer bindings, so terms of
+                // structurally equal subexpressions must not be shared
+                let shared = lowering.shared.take();
+                let condition = nondet.exp.lower(ctx, &inner_env, lowering);
+                lowering.shared = shared;
+                let condition = as_bool(&condition?)?;
+                lowering
+                    .witness_constraints
+                    .as_mut()
+                    .unwrap()
+                    .push(condition);
+                Ok(witness)
+            }
+            Expression::Call(_) => Err(LowerError::Unsupported("function call")),
+        }
+    }
+}
+
+fn lower_constant_value<'ctx>(
+    ctx: &'ctx Context,
+    value: &ConstantValue,
+) -> Result<Dynamic<'ctx>, LowerError> {
+    if let ConstantValue::MathConstant(_) = value {
+        return Err(LowerError::Unsupported("math constant"));
+    }
+    match Value::from_constant(value) {
+        Ok(Value::Bool(b)) => Ok(Bool::from_bool(ctx, b).into()),
+        Ok(Value::Int(int)) => Ok(Int::from_big_int(ctx, &int).into()),
+        Ok(Value::Real(real)) => {
+            Ok(
+                Real::from_real_str(ctx, &real.numer().to_string(), &real.denom().to_string())
+                    .unwrap()
+                    .into(),
+            )
+        }
+        Err(_) => Err(LowerError::Unsupported("number literal")),
+    }
+}
+
+/// The largest exponent of a power that is expanded into a product.
+const MAX_POW_EXPONENT: usize = 64;
+
+/// Expand a power with a natural number literal as the exponent into a
+/// product of the base. The exponent must not exceed [`MAX_POW_EXPONENT`].
+fn lower_pow<'ctx>(
+    ctx: &'ctx Context,
+    base: Dynamic<'ctx>,
+    exponent: &Expression,
+) -> Result<Dynamic<'ctx>, LowerError> {
+    let exponent = match exponent {
+        Expression::Constant(value) => Value::from_constant(value).ok(),
+        _ => None,
+    };
+    let exponent = match exponent {
+        Some(Value::Int(exponent)) => exponent,
+        _ => {
+            return Err(LowerError::Unsupported(
+                "power with a non-constant exponent",
+            ))
+        }
+    };
+    if exponent.is_negative() {
+        return Err(LowerError::Unsupported("power with a negative exponent"));
+    }
+    let exponent = exponent
+        .to_usize()
+        .filter(|&exponent| exponent <= MAX_POW_EXPONENT)
+        .ok_or(LowerError::Unsupported("power with a huge exponent"))?;
+    Ok(match as_num(&base)? {
+        Num::Int(_) if exponent == 0 => Int::from_i64(ctx, 1).into(),
+        Num::Real(_) if exponent == 0 => Real::from_real(ctx, 1, 1).into(),
+        Num::Int(base) => Int::mul(ctx, &vec![&base; exponent]).into(),
+        Num::Real(base) => Real::mul(ctx, &vec![&base; exponent]).into(),
+    })
+}
+
+fn lower_binary<'ctx>(
+    ctx: &'ctx Context,
+    op: BinaryOp,
+    left: Dynamic<'ctx>,
+    right: Dynamic<'ctx>,
+) -> Result<Dynamic<'ctx>, LowerError> {
+    match op {
+        BinaryOp::Or => Ok(Bool::or(ctx, &[as_bool(&left)?, as_bool(&right)?]).into()),
+        BinaryOp::And => Ok(Bool::and(ctx, &[as_bool(&left)?, as_bool(&right)?]).into()),
+        BinaryOp::Implication => Ok(as_bool(&left)?.implies(&as_bool(&right)?).into()),
+        BinaryOp::Equals | BinaryOp::NotEquals => {
+            // JANI has no separate operator for the equivalence of booleans
+            let eq = if left.sort_kind() == SortKind::Bool {
+                as_bool(&left)?.iff(&as_bool(&right)?)
+            } else {
+                match (as_num(&left)?, as_num(&right)?) {
+                    (Num::Int(left), Num::Int(right)) => left._eq(&right),
+                    (left, right) => left.to_real()._eq(&right.to_real()),
+                }
+            };
+            Ok(if op == BinaryOp::Equals { eq } else { eq.not() }.into())
+        }
+        BinaryOp::Less | BinaryOp::LessOrEqual | BinaryOp::Greater | BinaryOp::GreaterOrEqual => {
+            let res = match (as_num(&left)?, as_num(&right)?) {
+                (Num::Int(left), Num::Int(right)) => match op {
+                    BinaryOp::Less => left.lt(&right),
+                    BinaryOp::LessOrEqual => left.le(&right),
+                    BinaryOp::Greater => left.gt(&right),
+                    _ => left.ge(&right),
+                },
+                (left, right) => {
+                    let (left, right) = (left.to_real(), right.to_real());
+                    match op {
+                        BinaryOp::Less => left.lt(&right),
+                        BinaryOp::LessOrEqual => left.le(&right),
+                        BinaryOp::Greater => left.gt(&right),
+                        _ => left.ge(&right),
+                    }
+                }
+            };
+            Ok(res.into())
+        }
+        BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Times => {
+            let res = match (as_num(&left)?, as_num(&right)?) {
+                (Num::Int(left), Num::Int(right)) => Num::Int(match op {
+                    BinaryOp::Plus => left + right,
+                    BinaryOp::Minus => left - right,
+                    _ => left * right,
+                }),
+                (left, right) => {
+                    let (left, right) = (left.to_real(), right.to_real());
+                    Num::Real(match op {
+                        BinaryOp::Plus => left + right,
+                        BinaryOp::Minus => left - right,
+                        _ => left * right,
+                    })
+                }
+            };
+            Ok(res.into_dynamic())
+        }
+        // floored modulo from Z3's Euclidean one, which is never negative
+        BinaryOp::Modulo => match (as_num(&left)?, as_num(&right)?) {
+            (Num::Int(left), Num::Int(right)) => {
+                let zero = Int::from_i64(ctx, 0);
+                let rem = left.modulo(&right);
+                let shift = Bool::and(ctx, &[right.lt(&zero), rem._eq(&zero).not()]);
+                Ok(shift.ite(&(&rem + &right), &rem).into())
+            }
+            _ => Err(LowerError::Unsupported("modulo of reals")),
+        },
+        // real division, also for integer operands
+        BinaryOp::Divide => {
+            let (left, right) = (as_num(&left)?, as_num(&right)?);
+            Ok(left.to_real().div(&right.to_real()).into())
+        }
+        BinaryOp::Min | BinaryOp::Max => {
+            let (left, right) = (as_num(&left)?, as_num(&right)?);
+            Ok(match (left, right) {
+                (Num::Int(left), Num::Int(right)) => {
+                    let cond = if op == BinaryOp::Min {
+                        left.le(&right)
+                    } else {
+                        left.ge(&right)
+                    };
+                    cond.ite(&left, &right).into()
+                }
+                (left, right) => {
+                    let (left, right) = (left.to_real(), right.to_real());
+                    let cond = if op == BinaryOp::Min {
+                        left.le(&right)
+                    } else {
+                        left.ge(&right)
+                    };
+                    cond.ite(&left, &right).into()
+                }
+            })
+        }
+        // only powers with constant exponents are supported, see lower_pow
+        BinaryOp::Pow => Err(LowerError::Unsupported("pow")),
+        BinaryOp::Log => Err(LowerError::Unsupported("log")),
+    }
+}
+
+fn sort_name(term: &Dynamic<'_>) -> &'static str {
+    match term.sort_kind() {
+        SortKind::Bool => "bool",
+        SortKind::Int => "int",
+        SortKind::Real => "real",
+        _ => "other",
+    }
+}
+
+fn as_bool<'ctx>(term: &Dynamic<'ctx>) -> Result<Bool<'ctx>, LowerError> {
+    term.as_bool().ok_or(LowerError::TypeMismatch {
+        expected: "bool",
+        found: sort_name(term),
+    })
+}
+
+fn as_num<'ctx>(term: &Dynamic<'ctx>) -> Result<Num<'ctx>, LowerError> {
+    match term.sort_kind() {
+        SortKind::Int => Ok(Num::Int(term.as_int().unwrap())),
+        SortKind::Real => Ok(Num::Real(term.as_real().unwrap())),
+        _ => Err(LowerError::TypeMismatch {
+            expected: "numeric",
+            found: sort_name(term),
+        }),
+    }
+}
+
+/// Create a Z3 constant with the given name for a variable of type `ty`.
+pub fn declare_var<'ctx>(ctx: &'ctx Context, ident: &Identifier, ty: &JaniType) -> Dynamic<'ctx> {
+    let name = ident.0.as_str();
+    match ty {
+        JaniType::Bool => Bool::new_const(ctx, name).into(),
+        JaniType::Int | JaniType::BoundedInt { .. } => Int::new_const(ctx, name).into(),
+        JaniType::Real => Real::new_const(ctx, name).into(),
+    }
+}
+
+/// Create a Z3 constant for a JANI constant of type `ty`, see
+/// [`crate::models::ConstantDeclaration`]. If the constant has a defining
+/// `value`, it is lowered in `env` and the assumption `name = value` is
+/// returned as well, which must be added to the prover. Without a value, the
+/// constant is a model parameter and left unconstrained. Range constraints
+/// of bounded types are not included, see [`bounded_int_constraint`].
+pub fn lower_constant<'ctx>(
+    ctx: &'ctx Context,
+    env: &HashMap<Identifier, Dynamic<'ctx>>,
+    name: &Identifier,
+    ty: &JaniType,
+    value: Option<&Expression>,
+) -> Result<(Dynamic<'ctx>, Option<Bool<'ctx>>), LowerError> {
+    let constant = declare_var(ctx, name, ty);
+    let definition = match value {
+        Some(value) => {
+            let value = value.to_z3(ctx, env)?;
+            let definition = lower_binary(ctx, BinaryOp::Equals, constant.clone(), value)?;
+            Some(definition.as_bool().unwrap())
+        }
+        None => None,
+    };
+    Ok((constant, definition))
+}
+
+/// Lower `expr` in `env` (see [`Expression::to_z3`]) and evaluate the term in
+/// `model`, e.g. to read the value of a guard in a counterexample. `env` must
+/// contain the terms the model was obtained for.
+pub fn eval_jani_expr_in_model<'ctx>(
+    ctx: &'ctx Context,
+    expr: &Expression,
+    env: &HashMap<Identifier, Dynamic<'ctx>>,
+    model: &InstrumentedModel<'ctx>,
+) -> Result<ConcreteValue, ModelEvalError> {
+    let term = expr.to_z3(ctx, env)?;
+    let value = match term.sort_kind() {
+        SortKind::Bool => term.as_bool().unwrap().eval_concrete(model)?,
+        SortKind::Int => term.as_int().unwrap().eval_concrete(model)?,
+        _ => term.as_real().unwrap().eval_concrete(model)?,
+    };
+    Ok(value)
+}
+
+/// The constraint `lower <= var <= upper`, where missing bounds are omitted.
+pub fn bounded_int_constraint<'ctx>(
+    ctx: &'ctx Context,
+    var: &Int<'ctx>,
+    lower: Option<i64>,
+    upper: Option<i64>,
+) -> Bool<'ctx> {
+    let mut constraints = Vec::new();
+    if let Some(lower) = lower {
+        constraints.push(Int::from_i64(ctx, lower).le(var));
+    }
+    if let Some(upper) = upper {
+        constraints.push(var.le(&Int::from_i64(ctx, upper)));
+    }
+    Bool::and(ctx, &constraints)
+}
+
+/// Declare Z3 constants for all variables in `types`. Returns the environment
+/// for [`Expression::to_z3`] and the range constraints of all bounded
+/// variables, sorted by variable name. The constraints must be added as
+/// assumptions (e.g. via `Prover::add_assumption`), otherwise proofs might
+/// consider values outside of the declared ranges.
+pub fn declare_env_with_bounds<'ctx>(
+    ctx: &'ctx Context,
+    types: &HashMap<Identifier, JaniType>,
+) -> (HashMap<Identifier, Dynamic<'ctx>>, Vec<Bool<'ctx>>) {
+    let mut idents: Vec<&Identifier> = types.keys().collect();
+    idents.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut env = HashMap::new();
+    let mut constraints = Vec::new();
+    for ident in idents {
+        let ty = &types[ident];
+        let var = declare_var(ctx, ident, ty);
+        if let JaniType::BoundedInt { lower, upper } = ty {
+            constraints.push(bounded_int_constraint(
+                ctx,
+                &var.as_int().unwrap(),
+                *lower,
+                *upper,
+            ));
+        }
+        env.insert(ident.clone(), var);
+    }
+    (env, constraints)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use num::BigRational;
+    use z3::{
+        ast::{Ast, Bool, Int, Real},
+        Config, Context, SatResult, SortKind,
+    };
+    use z3rro::{
+        model::{ConcreteValue, SmtEval},
+        prover::{IncrementalMode, ProveResult, Prover, SolverType},
+    };
+
+    use crate::{
+        eval::Value,
+        exprs::{BinaryOp, ConstantValue, Expression, NondetSelectionExpression},
+        test_util::{binary, ident},
+        types::JaniType,
+        Identifier,
+    };
+
+    use super::{
+        declare_env_with_bounds, declare_var, eval_jani_expr_in_model, lower_constant, LowerError,
+        Lowering, LoweringCache, ModelEvalError,
+    };
+
+    #[test]
+    fn test_declare_env_with_bounds() {
+        let ctx = Context::new(&Config::default());
+        let types = HashMap::from([
+            (
+                Identifier("x".to_owned()),
+                JaniType::BoundedInt {
+                    lower: Some(0),
+                    upper: Some(10),
+                },
+            ),
+            (Identifier("y".to_owned()), JaniType::Int),
+        ]);
+        let (env, bounds) = declare_env_with_bounds(&ctx, &types);
+        assert_eq!(bounds.len(), 1);
+
+        let goal = binary(BinaryOp::Less, ident("x"), 10.into());
+        let goal: Bool = goal.to_z3(&ctx, &env).unwrap().as_bool().unwrap();
+
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        for bound in &bounds {
+            prover.add_assumption(bound);
+        }
+        prover.add_provable(&goal);
+        assert!(matches!(
+            prover.check_proof(),
+            Ok(ProveResult::Counterexample)
+        ));
+        // the only counterexample in range
+        let model = prover.get_model().unwrap();
+        let x: Int = env[&Identifier("x".to_owned())].as_int().unwrap();
+        assert_eq!(x.eval(&model).unwrap(), 10.into());
+
+        let goal = binary(BinaryOp::LessOrEqual, ident("x"), 10.into());
+        prover.push();
+        prover.add_provable(&goal.to_z3(&ctx, &env).unwrap().as_bool().unwrap());
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        prover.pop();
+    }
+
+    #[test]
+    fn test_mixed_comparison() {
+        let ctx = Context::new(&Config::default());
+        let x = Identifier("x".to_owned());
+        let env = HashMap::from([(x.clone(), declare_var(&ctx, &x, &JaniType::Int))]);
+        let expr = ident("x").lt(Expression::Constant(ConstantValue::try_from(2.5).unwrap()));
+        let term = expr.to_z3(&ctx, &env).unwrap();
+
+        // the integer operand is converted to a real
+        assert!(term
+            .children()
+            .iter()
+            .all(|operand| operand.sort_kind() == SortKind::Real));
+        let term = term.as_bool().unwrap();
+
+        let x = env[&x].as_int().unwrap();
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        prover.add_provable(&term._eq(&x.le(&Int::from_i64(&ctx, 2))));
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+    }
+
+    #[test]
+    fn test_lower_pow() {
+        let ctx = Context::new(&Config::default());
+        let x = Identifier("x".to_owned());
+        let env = HashMap::from([(x.clone(), declare_var(&ctx, &x, &JaniType::Int))]);
+        let cube = binary(BinaryOp::Pow, ident("x"), 3.into());
+        let term = cube.to_z3(&ctx, &env).unwrap();
+        assert_eq!(term.sort_kind(), SortKind::Int);
+        assert_eq!(term.children().len(), 3);
+
+        // x >= 2 implies x^3 >= 8
+        let goal = binary(
+            BinaryOp::Implication,
+            ident("x").ge(2.into()),
+            cube.ge(8.into()),
+        );
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        prover.add_provable(&goal.to_z3(&ctx, &env).unwrap().as_bool().unwrap());
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+
+        let one = binary(BinaryOp::Pow, ident("x"), 0.into()).to_z3(&ctx, &env);
+        assert_eq!(one.unwrap().as_int().unwrap().as_i64(), Some(1));
+        assert_eq!(
+            binary(BinaryOp::Pow, 2.into(), ident("x")).to_z3(&ctx, &env),
+            Err(LowerError::Unsupported(
+                "power with a non-constant exponent"
+            ))
+        );
+        let negative = Expression::Constant(ConstantValue::Number((-1).into()));
+        assert_eq!(
+            binary(BinaryOp::Pow, ident("x"), negative).to_z3(&ctx, &env),
+            Err(LowerError::Unsupported("power with a negative exponent"))
+        );
+        let huge = binary(BinaryOp::Pow, ident("x"), 65.into()).to_z3(&ctx, &env);
+        assert_eq!(
+            huge,
+            Err(LowerError::Unsupported("power with a huge exponent"))
+        );
+        let largest = binary(BinaryOp::Pow, ident("x"), 64.into()).to_z3(&ctx, &env);
+        assert_eq!(largest.unwrap().children().len(), 64);
+    }
+
+    #[test]
+    fn test_modulo_divide() {
+        let ctx = Context::new(&Config::default());
+        let env = HashMap::new();
+        let real = |value: f64| Expression::Constant(ConstantValue::try_from(value).unwrap());
+        let facts = [
+            binary(BinaryOp::Modulo, 7.into(), 3.into()).eq_expr(1.into()),
+            binary(BinaryOp::Divide, 7.into(), 2.into()).eq_expr(real(3.5)),
+            // the result of the modulo has the sign of the divisor
+            binary(BinaryOp::Modulo, Expression::from(0) - 7.into(), 3.into()).eq_expr(2.into()),
+            binary(BinaryOp::Modulo, 7.into(), Expression::from(0) - 3.into())
+                .eq_expr(Expression::from(0) - 2.into()),
+            binary(BinaryOp::Modulo, 6.into(), Expression::from(0) - 3.into()).eq_expr(0.into()),
+        ];
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        for fact in &facts {
+            let term = fact.to_z3(&ctx, &env).unwrap();
+            prover.add_provable(&term.as_bool().unwrap());
+        }
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        // evaluation and simplification agree with the lowering
+        for fact in &facts {
+            assert_eq!(fact.eval(&HashMap::new()), Ok(Value::Bool(true)));
+            assert_eq!(fact.simplify(), Expression::from(true));
+        }
+
+        let quotient = binary(BinaryOp::Divide, 7.into(), 2.into());
+        assert_eq!(
+            quotient.to_z3(&ctx, &env).unwrap().sort_kind(),
+            SortKind::Real
+        );
+        let expr = binary(BinaryOp::Modulo, real(7.5), 2.into());
+        assert_eq!(
+            expr.to_z3(&ctx, &env),
+            Err(LowerError::Unsupported("modulo of reals"))
+        );
+    }
+
+    #[test]
+    fn test_implication_iff() {
+        let ctx = Context::new(&Config::default());
+        let types = HashMap::from([
+            (Identifier("a".to_owned()), JaniType::Bool),
+            (Identifier("b".to_owned()), JaniType::Bool),
+        ]);
+        let (env, _) = declare_env_with_bounds(&ctx, &types);
+        let guard = binary(BinaryOp::Implication, ident("a"), ident("b"));
+
+        // modus ponens, and the implication is equivalent to ¬a ∨ b
+        let modus_ponens = binary(
+            BinaryOp::Implication,
+            guard.clone() & ident("a"),
+            ident("b"),
+        );
+        let equivalence = guard.clone().eq_expr(!ident("a") | ident("b"));
+        let converse = binary(BinaryOp::Implication, ident("b"), ident("a"));
+        let iff = ident("a").eq_expr(ident("b")).eq_expr(guard & converse);
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        for tautology in [modus_ponens, equivalence, iff] {
+            let term = tautology.to_z3(&ctx, &env).unwrap().as_bool().unwrap();
+            prover.add_provable(&term);
+        }
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+    }
+
+    #[test]
+    fn test_lower_constant() {
+        let ctx = Context::new(&Config::default());
+        let n = Identifier("N".to_owned());
+        let k = Identifier("K".to_owned());
+        let x = Identifier("x".to_owned());
+        let mut env = HashMap::from([(x.clone(), declare_var(&ctx, &x, &JaniType::Int))]);
+
+        // the parameter K is unconstrained, N = 2 * 5 is defined
+        let (constant, definition) = lower_constant(&ctx, &env, &k, &JaniType::Int, None).unwrap();
+        assert!(definition.is_none());
+        env.insert(k, constant);
+        let value = Expression::from(2) * 5.into();
+        let (constant, definition) =
+            lower_constant(&ctx, &env, &n, &JaniType::Real, Some(&value)).unwrap();
+        env.insert(n, constant);
+
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        prover.add_assumption(&definition.unwrap());
+        let goal = binary(
+            BinaryOp::Implication,
+            ident("x").lt(ident("N")),
+            ident("x").le(9.into()),
+        );
+        prover.add_provable(&goal.to_z3(&ctx, &env).unwrap().as_bool().unwrap());
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+
+        prover.push();
+        let goal = binary(
+            BinaryOp::Implication,
+            ident("x").lt(ident("K")),
+            ident("x").le(9.into()),
+        );
+        prover.add_provable(&goal.to_z3(&ctx, &env).unwrap().as_bool().unwrap());
+        assert!(matches!(
+            prover.check_proof(),
+            Ok(ProveResult::Counterexample)
+        ));
+        prover.pop();
+    }
+
+    #[test]
+    fn test_to_z3_shared() {
+        let ctx = Context::new(&Config::default());
+        let x = Identifier("x".to_owned());
+        let env = HashMap::from([(x.clone(), declare_var(&ctx, &x, &JaniType::Int))]);
+        let sum = ident("x") + 1.into();
+        let expr = binary(
+            BinaryOp::Less,
+            sum.clone() * sum.clone(),
+            sum.clone() + 2.into(),
+        );
+        assert_eq!(expr.common_subexpressions(), vec![(sum, 3)]);
+
+        // Z3 shares the terms in any case, but the sum is only lowered once
+        let term = expr.to_z3_shared(&ctx, &env).unwrap();
+        assert_eq!(term, expr.to_z3(&ctx, &env).unwrap());
+        let mut lowering = Lowering::default();
+        expr.lower(&ctx, &env, &mut lowering).unwrap();
+        assert_eq!(lowering.lowered, 6);
+        let mut lowering = Lowering {
+            shared: Some(HashMap::new()),
+            ..Default::default()
+        };
+        expr.lower(&ctx, &env, &mut lowering).unwrap();
+        assert_eq!(lowering.lowered, 4);
+    }
+
+    #[test]
+    fn test_to_z3_cached() {
+        let ctx = Context::new(&Config::default());
+        let x = Identifier("x".to_owned());
+        let env = HashMap::from([(x.clone(), declare_var(&ctx, &x, &JaniType::Int))]);
+        let guard = (ident("x") + 1.into()).lt(10.into()) & ident("x").ge(0.into());
+        let mut cache = LoweringCache::new();
+
+        let term = guard.to_z3_cached(&ctx, &env, &mut cache).unwrap();
+        // the guard, its conjuncts and the sum
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.hits(), 0);
+        let again = guard.to_z3_cached(&ctx, &env, &mut cache).unwrap();
+        assert_eq!(again, term);
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(term, guard.to_z3(&ctx, &env).unwrap());
+        // the guard is reused within a larger expression
+        let larger = guard.clone() | ident("x").eq_expr(20.into());
+        larger.to_z3_cached(&ctx, &env, &mut cache).unwrap();
+        assert_eq!(cache.len(), 6);
+        assert_eq!(cache.hits(), 2);
+
+        // another environment invalidates the cache
+        let env = HashMap::from([(x.clone(), declare_var(&ctx, &x, &JaniType::Real))]);
+        let term = guard.to_z3_cached(&ctx, &env, &mut cache).unwrap();
+        assert_eq!(term, guard.to_z3(&ctx, &env).unwrap());
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.hits(), 2);
+    }
+
+    #[test]
+    fn test_eval_jani_expr_in_model() {
+        let ctx = Context::new(&Config::default());
+        let x = Identifier("x".to_owned());
+        let env = HashMap::from([(x.clone(), declare_var(&ctx, &x, &JaniType::Int))]);
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let guard = ident("x").eq_expr(4.into());
+        prover.add_assumption(&guard.to_z3(&ctx, &env).unwrap().as_bool().unwrap());
+        let (res, model) = prover.check_sat_model().unwrap();
+        assert_eq!(res, SatResult::Sat);
+        let model = model.unwrap();
+
+        let sum = ident("x") + 1.into();
+        assert_eq!(
+            eval_jani_expr_in_model(&ctx, &sum, &env, &model).unwrap(),
+            ConcreteValue::Int(5.into())
+        );
+        let half = binary(BinaryOp::Divide, ident("x"), 8.into());
+        assert_eq!(
+            eval_jani_expr_in_model(&ctx, &half, &env, &model).unwrap(),
+            ConcreteValue::Real(BigRational::new(1.into(), 2.into()))
+        );
+        assert_eq!(
+            eval_jani_expr_in_model(&ctx, &guard, &env, &model).unwrap(),
+            ConcreteValue::Bool(true)
+        );
+        assert!(matches!(
+            eval_jani_expr_in_model(&ctx, &ident("y"), &env, &model),
+            Err(ModelEvalError::Lower(LowerError::UnboundIdentifier(_)))
+        ));
+    }
+
+    #[test]
+    fn test_to_z3_with_typed_witnesses() {
+        let ctx = Context::new(&Config::default());
+        let v = Identifier("v".to_owned());
+        let x = Identifier("x".to_owned());
+        let env = HashMap::from([(x.clone(), declare_var(&ctx, &x, &JaniType::Int))]);
+        // nondet v. x < v, where v ranges over 0..=3
+        let selection = Expression::NondetSelection(Box::new(NondetSelectionExpression {
+            var: v.clone(),
+            exp: ident("x").lt(Expression::Identifier(v.clone())),
+        }));
+        let var_types = HashMap::from([(
+            v,
+            JaniType::BoundedInt {
+                lower: Some(0),
+                upper: Some(3),
+            },
+        )]);
+        let (term, constraints) = selection
+            .to_z3_with_typed_witnesses(&ctx, &env, &var_types)
+            .unwrap();
+        assert_eq!(term.sort_kind(), SortKind::Int);
+        assert_eq!(constraints.len(), 2);
+
+        let witness = term.as_int().unwrap();
+        let x = env[&x].as_int().unwrap();
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        for constraint in &constraints {
+            prover.add_assumption(constraint);
+        }
+        prover.add_assumption(&x._eq(&Int::from_i64(&ctx, 2)));
+        // the only witness within the bounds is 3
+        prover.add_provable(&witness._eq(&Int::from_i64(&ctx, 3)));
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+
+        // no witness exists within the bounds
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        for constraint in &constraints {
+            prover.add_assumption(constraint);
+        }
+        prover.add_assumption(&x._eq(&Int::from_i64(&ctx, 3)));
+        assert_eq!(prover.check_sat().unwrap(), SatResult::Unsat);
+    }
+
+    #[test]
+    fn test_to_z3_with_witnesses() {
+        let ctx = Context::new(&Config::default());
+        let v = Identifier("v".to_owned());
+        let x = Identifier("x".to_owned());
+        let env = HashMap::from([(x.clone(), declare_var(&ctx, &x, &JaniType::Real))]);
+        // nondet v. v * 2 = x, plus 1
+        let selection = Expression::NondetSelection(Box::new(NondetSelectionExpression {
+            var: v.clone(),
+            exp: binary(
+                BinaryOp::Equals,
+                Expression::Identifier(v) * 2.into(),
+                ident("x"),
+            ),
+        }));
+        let expr = selection + 1.into();
+        assert_eq!(
+            expr.to_z3(&ctx, &env),
+            Err(LowerError::Unsupported("nondeterministic selection"))
+        );
+
+        let (term, constraints) = expr.to_z3_with_witnesses(&ctx, &env).unwrap();
+        assert_eq!(constraints.len(), 1);
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        for constraint in &constraints {
+            prover.add_assumption(constraint);
+        }
+        let x = env[&x].as_real().unwrap();
+        prover.add_assumption(&x._eq(&Real::from_real(&ctx, 5, 1)));
+        let (res, model) = prover.check_sat_model().unwrap();
+        assert_eq!(res, SatResult::Sat);
+        let model = model.unwrap();
+        let value = term.as_real().unwrap().eval(&model).unwrap();
+        assert_eq!(value, BigRational::new(7.into(), 2.into()));
+        assert!(constraints[0].eval(&model).unwrap());
+    }
+}
@@ -0,0 +1,105 @@
+//! Normal forms for JANI expressions.
+
+use crate::exprs::{BinaryExpression, BinaryOp, Expression};
+
+impl Expression {
+    /// Rewrite chained comparisons such as `(a < b) ≤ c` into conjunctions of
+    /// pairwise comparisons (`a < b ∧ b ≤ c`) and replace `>` and `≥` by `<`
+    /// and `≤` with swapped operands. Afterwards, the only comparison
+    /// operators are `<`, `≤`, `=` and `≠`.
+    pub fn normalize_comparisons(&self) -> Expression {
+        match self {
+            Expression::Binary(binary) if is_ordering(binary.op) => {
+                let mut operands = Vec::new();
+                let mut ops = Vec::new();
+                flatten_chain(self, &mut operands, &mut ops);
+                ops.iter()
+                    .zip(operands.windows(2))
+                    .map(|(op, pair)| comparison(*op, pair[0].clone(), pair[1].clone()))
+                    .reduce(|acc, cmp| acc & cmp)
+                    .unwrap()
+            }
+            _ => self.map_children(Expression::normalize_comparisons),
+        }
+    }
+}
+
+fn is_ordering(op: BinaryOp) -> bool {
+    matches!(
+        op,
+        BinaryOp::Less | BinaryOp::LessOrEqual | BinaryOp::Greater | BinaryOp::GreaterOrEqual
+    )
+}
+
+/// Collect the operands and operators of a chain of ordering comparisons. The
+/// operands are normalized already.
+fn flatten_chain(expr: &Expression, operands: &mut Vec<Expression>, ops: &mut Vec<BinaryOp>) {
+    match expr {
+        Expression::Binary(binary) if is_ordering(binary.op) => {
+            flatten_chain(&binary.left, operands, ops);
+            ops.push(binary.op);
+            flatten_chain(&binary.right, operands, ops);
+        }
+        _ => operands.push(expr.normalize_comparisons()),
+    }
+}
+
+fn comparison(op: BinaryOp, left: Expression, right: Expression) -> Expression {
+    let (op, left, right) = match op {
+        BinaryOp::Greater => (BinaryOp::Less, right, left),
+        BinaryOp::GreaterOrEqual => (BinaryOp::LessOrEqual, right, left),
+        _ => (op, left, right),
+    };
+    BinaryExpression { op, left, right }.into()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        exprs::{BinaryOp, Expression, IteExpression},
+        test_util::{binary, ident},
+    };
+
+    #[test]
+    fn test_chained_comparison() {
+        let expr = binary(
+            BinaryOp::LessOrEqual,
+            binary(BinaryOp::Less, ident("a"), ident("b")),
+            ident("c"),
+        );
+        assert_eq!(
+            expr.normalize_comparisons(),
+            binary(BinaryOp::Less, ident("a"), ident("b"))
+                & binary(BinaryOp::LessOrEqual, ident("b"), ident("c"))
+        );
+
+        let expr = binary(
+            BinaryOp::Greater,
+            ident("a"),
+            binary(BinaryOp::GreaterOrEqual, ident("b"), ident("c")),
+        );
+        assert_eq!(
+            expr.normalize_comparisons(),
+            binary(BinaryOp::Less, ident("b"), ident("a"))
+                & binary(BinaryOp::LessOrEqual, ident("c"), ident("b"))
+        );
+    }
+
+    #[test]
+    fn test_flip_greater() {
+        let cond = binary(BinaryOp::Greater, ident("x") + 1.into(), ident("y"));
+        let expr: Expression = IteExpression {
+            cond,
+            left: ident("x") * 2.into(),
+            right: binary(BinaryOp::Equals, ident("x"), ident("y")),
+        }
+        .into();
+        let expected: Expression = IteExpression {
+            cond: binary(BinaryOp::Less, ident("y"), ident("x") + 1.into()),
+            left: ident("x") * 2.into(),
+            right: binary(BinaryOp::Equals, ident("x"), ident("y")),
+        }
+        .into();
+        assert_eq!(expr.normalize_comparisons(), expected);
+    }
+}
@@ -0,0 +1,447 @@
+//! Substitution and constant folding for JANI expressions.
+
+use std::collections::HashMap;
+
+use num::ToPrimitive;
+
+use crate::{
+    eval::{pow_bits, Value},
+    exprs::{
+        BinaryExpression, BinaryOp, CallExpression, ConstantValue, Expression, IteExpression,
+        NondetSelectionExpression, UnaryExpression, UnaryOp,
+    },
+    Identifier,
+};
+
+impl Expression {
+    /// Replace all free occurrences of the identifiers in `subst` by their
+    /// respective expressions.
+    pub fn substitute(&self, subst: &HashMap<Identifier, Expression>) -> Expression {
+        match self {
+            Expression::Constant(_) => self.clone(),
+            Expression::Identifier(ident) => subst.get(ident).unwrap_or(self).clone(),
+            Expression::IfThenElse(ite) => IteExpression {
+                cond: ite.cond.substitute(subst),
+                left: ite.left.substitute(subst),
+                right: ite.right.substitute(subst),
+            }
+            .into(),
+            Expression::Unary(unary) => UnaryExpression {
+                op: unary.op,
+                exp: unary.exp.substitute(subst),
+            }
+            .into(),
+            Expression::Binary(binary) => BinaryExpression {
+                op: binary.op,
+                left: binary.left.substitute(subst),
+                right: binary.right.substitute(subst),
+            }
+            .into(),
+            Expression::NondetSelection(nondet) => {
+                // the selected variable shadows outer bindings
+                let exp = if subst.contains_key(&nondet.var) {
+                    let mut subst = subst.clone();
+                    subst.remove(&nondet.var);
+                    nondet.exp.substitute(&subst)
+                } else {
+                    nondet.exp.substitute(subst)
+                };
+                Expression::NondetSelection(Box::new(NondetSelectionExpression {
+                    var: nondet.var.clone(),
+                    exp,
+                }))
+            }
+            Expression::Call(call) => CallExpression {
+                function: call.function.clone(),
+                args: call.args.iter().map(|arg| arg.substitute(subst)).collect(),
+            }
+            .into(),
+        }
+    }
+
+    /// Fold constant subexpressions and apply basic boolean and arithmetic
+    /// identities such as `true ∧ e = e`, `¬¬e = e` or `0 + e = e`. The
+    /// comparisons `e = e` and `e ≠ e` are only folded if `e` contains no
+    /// nondeterministic selections and derivatives. Subexpressions are
+    /// only folded into constants if the result can be represented exactly by
+    /// a [`ConstantValue`].
+    ///
+    /// If-then-else expressions with identical branches are replaced by the
+    /// branch. An if-then-else directly in a branch of another one is
+    /// replaced by its respective branch if its condition is identical to the
+    /// outer condition or its negation. Other conditions are not compared
+    /// semantically.
+    pub fn simplify(&self) -> Expression {
+        match self {
+            Expression::Constant(_) | Expression::Identifier(_) => self.clone(),
+            Expression::IfThenElse(ite) => {
+                let cond = ite.cond.simplify();
+                match as_bool(&cond) {
+                    Some(true) => ite.left.simplify(),
+                    Some(false) => ite.right.simplify(),
+                    None => {
+                        let (mut left, mut right) = (ite.left.simplify(), ite.right.simplify());
+                        if is_pure(&cond) {
+                            left = prune_branch(left, &cond, true);
+                            right = prune_branch(right, &cond, false);
+                        }
+                        if left == right {
+                            return left;
+                        }
+                        IteExpression { cond, left, right }.into()
+                    }
+                }
+            }
+            Expression::Unary(unary) => {
+                let exp = unary.exp.simplify();
+                if let (UnaryOp::Not, Expression::Unary(inner)) = (unary.op, &exp) {
+                    if inner.op == UnaryOp::Not {
+                        return inner.exp.clone();
+                    }
+                }
+                let res = UnaryExpression { op: unary.op, exp }.into();
+                // the values of transcendental functions are approximations
+                if unary.op.is_transcendental() {
+                    res
+                } else {
+                    fold(res)
+                }
+            }
+            Expression::Binary(binary) => {
+                simplify_binary(binary.op, binary.left.simplify(), binary.right.simplify())
+            }
+            Expression::NondetSelection(_) | Expression::Call(_) => {
+                self.map_children(Expression::simplify)
+            }
+        }
+    }
+
+    /// Specialize this expression by replacing the identifiers in `env` by
+    /// their constant values and simplifying the result. The returned
+    /// expression only references the remaining free identifiers.
+    pub fn propagate_constants(&self, env: &HashMap<Identifier, ConstantValue>) -> Expression {
+        let subst = env
+            .iter()
+            .map(|(ident, value)| (ident.clone(), Expression::Constant(value.clone())))
+            .collect();
+        self.substitute(&subst).simplify()
+    }
+}
+
+fn as_bool(expr: &Expression) -> Option<bool> {
+    match expr {
+        Expression::Constant(ConstantValue::Boolean(b)) => Some(*b),
+        _ => None,
+    }
+}
+
+/// Whether the expression is the integer literal `value`. Real literals are
+/// excluded so that identities do not change the type of an expression.
+fn is_int_literal(expr: &Expression, value: i64) -> bool {
+    match expr {
+        Expression::Constant(ConstantValue::Number(number)) => number.as_i64() == Some(value),
+        _ => false,
+    }
+}
+
+/// Whether the expression always denotes the same value, i.e. it contains no
+/// nondeterministic selections and no derivatives.
+fn is_pure(expr: &Expression) -> bool {
+    !expr.contains(|expr| match expr {
+        Expression::NondetSelection(_) => true,
+        Expression::Unary(unary) => unary.op == UnaryOp::Derivative,
+        _ => false,
+    })
+}
+
+/// The value of `cond` given that `known` has the value `value`, if it is
+/// syntactically obvious.
+fn implied_value(cond: &Expression, known: &Expression, value: bool) -> Option<bool> {
+    let is_negation = |expr: &Expression, of: &Expression| matches!(expr, Expression::Unary(unary) if unary.op == UnaryOp::Not && unary.exp == *of);
+    if cond == known {
+        Some(value)
+    } else if is_negation(cond, known) || is_negation(known, cond) {
+        Some(!value)
+    } else {
+        None
+    }
+}
+
+/// Replace if-then-else expressions at the top of the `branch` whose
+/// condition is decided by the outer condition `cond` having the value
+/// `value` by the respective branch.
+fn prune_branch(mut branch: Expression, cond: &Expression, value: bool) -> Expression {
+    while let Expression::IfThenElse(inner) = &branch {
+        branch = match implied_value(&inner.cond, cond, value) {
+            Some(true) => inner.left.clone(),
+            Some(false) => inner.right.clone(),
+            None => break,
+        };
+    }
+    branch
+}
+
+/// Constants are 64-bit integers or doubles, whose numerators and
+/// denominators have at most 1074 bits. Larger powers are not folded.
+const MAX_CONSTANT_BITS: u64 = 1074;
+
+/// Replace the expression by its value if all of its operands are constants.
+fn fold(expr: Expression) -> Expression {
+    if let Expression::Binary(binary) = &expr {
+        if binary.op == BinaryOp::Pow && !pow_may_be_constant(&binary.left, &binary.right) {
+            return expr;
+        }
+    }
+    match expr.eval(&HashMap::new()).map(|value| value.to_constant()) {
+        Ok(Some(value)) => Expression::Constant(value),
+        _ => expr,
+    }
+}
+
+/// Whether `base` to the power of `exponent` is small enough to be a
+/// constant, so that computing it is worthwhile. Non-constant operands are
+/// left to the evaluation.
+fn pow_may_be_constant(base: &Expression, exponent: &Expression) -> bool {
+    let env = HashMap::new();
+    let (Ok(base), Ok(Value::Int(exponent))) = (base.eval(&env), exponent.eval(&env)) else {
+        return true;
+    };
+    match (base.to_rational(), exponent.magnitude().to_u32()) {
+        (Ok(base), Some(exponent)) => pow_bits(&base, exponent) <= MAX_CONSTANT_BITS,
+        (Ok(base), None) => pow_bits(&base, u32::MAX) == 0,
+        (Err(_), _) => true,
+    }
+}
+
+fn simplify_binary(op: BinaryOp, left: Expression, right: Expression) -> Expression {
+    match (op, as_bool(&left), as_bool(&right)) {
+        (BinaryOp::And, Some(true), _) | (BinaryOp::Or, Some(false), _) => return right,
+        (BinaryOp::And, _, Some(true)) | (BinaryOp::Or, _, Some(false)) => return left,
+        (BinaryOp::And, Some(false), _) | (BinaryOp::And, _, Some(false)) => return false.into(),
+        (BinaryOp::Or, Some(true), _) | (BinaryOp::Or, _, Some(true)) => return true.into(),
+        (BinaryOp::Implication, Some(false), _) | (BinaryOp::Implication, _, Some(true)) => {
+            return true.into()
+        }
+        (BinaryOp::Implication, Some(true), _) => return right,
+        _ => {}
+    }
+    if matches!(op, BinaryOp::Equals | BinaryOp::NotEquals) && left == right && is_pure(&left) {
+        return (op == BinaryOp::Equals).into();
+    }
+    match op {
+        BinaryOp::Plus if is_int_literal(&left, 0) => return right,
+        BinaryOp::Plus | BinaryOp::Minus if is_int_literal(&right, 0) => return left,
+        BinaryOp::Times if is_int_literal(&left, 1) => return right,
+        BinaryOp::Times if is_int_literal(&right, 1) => return left,
+        _ => {}
+    }
+    let res = BinaryExpression { op, left, right }.into();
+    // the values of logarithms are approximations
+    if op == BinaryOp::Log {
+        res
+    } else {
+        fold(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::{
+        exprs::{
+            BinaryExpression, BinaryOp, ConstantValue, Expression, IteExpression,
+            NondetSelectionExpression, UnaryExpression, UnaryOp,
+        },
+        test_util::{binary, ident},
+        Identifier,
+    };
+
+    #[test]
+    fn test_simplify() {
+        let expr = (Expression::from(2) * 3.into() + ident("x")) & true.into();
+        assert_eq!(expr.simplify(), Expression::from(6) + ident("x"));
+
+        let expr = (Expression::from(0) + ident("x")) * 1.into();
+        assert_eq!(expr.simplify(), ident("x"));
+
+        let expr: Expression = BinaryExpression {
+            op: BinaryOp::Divide,
+            left: 1.into(),
+            right: 3.into(),
+        }
+        .into();
+        assert_eq!(expr.simplify(), expr);
+    }
+
+    #[test]
+    fn test_simplify_boolean_identities() {
+        let implies = |left: Expression, right: Expression| -> Expression {
+            BinaryExpression {
+                op: BinaryOp::Implication,
+                left,
+                right,
+            }
+            .into()
+        };
+        let sum = ident("x") + ident("y");
+
+        assert_eq!((!!ident("b")).simplify(), ident("b"));
+        assert_eq!((!!!ident("b")).simplify(), !ident("b"));
+        assert_eq!(
+            sum.clone().eq_expr(sum.clone()).simplify(),
+            Expression::from(true)
+        );
+        assert_eq!(
+            sum.clone().ne_expr(sum.clone()).simplify(),
+            Expression::from(false)
+        );
+        assert_eq!(implies(true.into(), ident("b")).simplify(), ident("b"));
+        assert_eq!(
+            implies(ident("a"), true.into()).simplify(),
+            Expression::from(true)
+        );
+
+        // two selections may select different values
+        let nondet = Expression::NondetSelection(Box::new(NondetSelectionExpression {
+            var: Identifier("v".to_owned()),
+            exp: ident("v").lt(ident("x")),
+        }));
+        let expr = nondet.clone().eq_expr(nondet);
+        assert_eq!(expr.simplify(), expr);
+        let derivative: Expression = UnaryExpression {
+            op: UnaryOp::Derivative,
+            exp: ident("x"),
+        }
+        .into();
+        let expr = derivative.clone().ne_expr(derivative);
+        assert_eq!(expr.simplify(), expr);
+
+        // ¬¬(s = s) ⇒ ((true ∧ ¬¬c) ∨ false) combines all identities
+        let expr = implies(
+            !!sum.clone().eq_expr(sum),
+            (Expression::from(true) & !!ident("c")) | false.into(),
+        );
+        assert_eq!(expr.simplify(), ident("c"));
+    }
+
+    #[test]
+    fn test_simplify_nested_ite() {
+        let ite = |cond: Expression, left: Expression, right: Expression| -> Expression {
+            IteExpression { cond, left, right }.into()
+        };
+        let c1 = ident("x").lt(ident("y"));
+        let c2 = ident("b");
+
+        // repeated conditions
+        let expr = ite(
+            c1.clone(),
+            ite(c1.clone(), ident("a"), ident("b")),
+            ident("c"),
+        );
+        assert_eq!(expr.simplify(), ite(c1.clone(), ident("a"), ident("c")));
+        let expr = ite(
+            c1.clone(),
+            ident("a"),
+            ite(
+                c1.clone(),
+                ident("b"),
+                ite(!c1.clone(), ident("c"), ident("d")),
+            ),
+        );
+        assert_eq!(expr.simplify(), ite(c1.clone(), ident("a"), ident("c")));
+        let expr = ite(
+            !c2.clone(),
+            ite(c2.clone(), ident("a"), ident("b")),
+            ident("c"),
+        );
+        assert_eq!(expr.simplify(), ite(!c2.clone(), ident("b"), ident("c")));
+
+        // identical branches, also after pruning
+        assert_eq!(
+            ite(c2.clone(), ident("a"), ident("a")).simplify(),
+            ident("a")
+        );
+        let expr = ite(
+            c1.clone(),
+            ite(c1.clone(), ident("a"), ident("b")),
+            ite(c1.clone(), ident("b"), ident("a")),
+        );
+        assert_eq!(expr.simplify(), ident("a"));
+
+        // different conditions are kept
+        let expr = ite(
+            c1.clone(),
+            ite(c2.clone(), ident("a"), ident("b")),
+            ident("c"),
+        );
+        assert_eq!(expr.simplify(), expr);
+        let expr = ite(
+            c1.clone(),
+            ite(ident("y").gt(ident("x")), ident("a"), ident("b")),
+            ident("c"),
+        );
+        assert_eq!(expr.simplify(), expr);
+
+        // two selections may select different values
+        let nondet = Expression::NondetSelection(Box::new(NondetSelectionExpression {
+            var: Identifier("v".to_owned()),
+            exp: ident("v"),
+        }));
+        let expr = ite(
+            nondet.clone(),
+            ite(nondet, ident("a"), ident("b")),
+            ident("c"),
+        );
+        assert_eq!(expr.simplify(), expr);
+    }
+
+    #[test]
+    fn test_simplify_huge_power() {
+        let pow = |left, right| binary(BinaryOp::Pow, left, right);
+        // the result cannot be a constant, so it is not computed
+        let huge = pow(3.into(), 2_000_000.into());
+        assert_eq!(huge.simplify(), huge);
+        let huge = pow(3.into(), 50_000_000.into());
+        assert_eq!(huge.simplify(), huge);
+        assert_eq!(pow(2.into(), 10.into()).simplify(), Expression::from(1024));
+        assert_eq!(
+            pow(1.into(), 50_000_000.into()).simplify(),
+            Expression::from(1)
+        );
+    }
+
+    #[test]
+    fn test_propagate_constants() {
+        let cond: Expression = BinaryExpression {
+            op: BinaryOp::Less,
+            left: ident("n"),
+            right: 10.into(),
+        }
+        .into();
+        let expr: Expression = IteExpression {
+            cond: cond.clone(),
+            left: ident("n") * 2.into() + ident("x"),
+            right: ident("x"),
+        }
+        .into();
+
+        let env = HashMap::from([(Identifier("n".to_owned()), ConstantValue::from(4))]);
+        assert_eq!(
+            expr.propagate_constants(&env),
+            Expression::from(8) + ident("x")
+        );
+
+        let env = HashMap::from([(Identifier("n".to_owned()), ConstantValue::from(12))]);
+        assert_eq!(expr.propagate_constants(&env), ident("x"));
+
+        let env = HashMap::from([(Identifier("x".to_owned()), ConstantValue::from(1))]);
+        let expected: Expression = IteExpression {
+            cond,
+            left: ident("n") * 2.into() + 1.into(),
+            right: 1.into(),
+        }
+        .into();
+        assert_eq!(expr.propagate_constants(&env), expected);
+    }
+}
@@ -0,0 +1,464 @@
+//! Printing JANI expressions as SMT-LIB terms.
+
+use std::{collections::HashMap, fmt::Display};
+
+use num::{BigInt, BigRational, Signed};
+
+use crate::{
+    eval::Value,
+    exprs::{BinaryOp, ConstantValue, Expression, UnaryOp},
+    types::JaniType,
+    Identifier,
+};
+
+/// Errors that can occur when printing a JANI expression as SMT-LIB.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SmtlibError {
+    /// The identifier is free in the expression, but has no type in the
+    /// environment.
+    UnboundIdentifier(Identifier),
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// The expression has no counterpart in SMT-LIB.
+    Unsupported(&'static str),
+}
+
+impl Display for SmtlibError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SmtlibError::UnboundIdentifier(ident) => write!(f, "unbound identifier {}", ident),
+            SmtlibError::TypeMismatch { expected, found } => {
+                write!(f, "expected term of type {}, found {}", expected, found)
+            }
+            SmtlibError::Unsupported(what) => write!(f, "cannot express {} in SMT-LIB", what),
+        }
+    }
+}
+
+impl std::error::Error for SmtlibError {}
+
+/// The sort of a printed term.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sort {
+    Bool,
+    Int,
+    Real,
+}
+
+impl Sort {
+    fn name(self) -> &'static str {
+        match self {
+            Sort::Bool => "Bool",
+            Sort::Int => "Int",
+            Sort::Real => "Real",
+        }
+    }
+}
+
+/// A printed term together with its sort.
+type Term = (String, Sort);
+
+impl Expression {
+    /// Print this expression as an SMT-LIB term. The types of identifiers
+    /// are taken from `env`. Integers and reals are mixed like in JANI, i.e.
+    /// integer operands are converted with `to_real` if the other operand is
+    /// a real, like in the lowering to Z3.
+    ///
+    /// Division is always real division and modulo is only supported for
+    /// integers, both printed like they are lowered to Z3. Mathematical
+    /// constants, powers, logarithms, derivatives, transcendental functions,
+    /// nondeterministic selections and function calls are not supported.
+    pub fn to_smtlib(&self, env: &HashMap<Identifier, JaniType>) -> Result<String, SmtlibError> {
+        Ok(self.print(env)?.0)
+    }
+
+    fn print(&self, env: &HashMap<Identifier, JaniType>) -> Result<Term, SmtlibError> {
+        match self {
+            Expression::Constant(value) => constant_to_smtlib(value),
+            Expression::Identifier(ident) => {
+                let sort = match env.get(ident) {
+                    Some(JaniType::Bool) => Sort::Bool,
+                    Some(JaniType::Int | JaniType::BoundedInt { .. }) => Sort::Int,
+                    Some(JaniType::Real) => Sort::Real,
+                    None => return Err(SmtlibError::UnboundIdentifier(ident.clone())),
+                };
+                Ok((symbol(ident), sort))
+            }
+            Expression::IfThenElse(ite) => {
+                let cond = as_bool(ite.cond.print(env)?)?;
+                let left = ite.left.print(env)?;
+                let right = ite.right.print(env)?;
+                if left.1 == Sort::Bool {
+                    let ite = format!("(ite {} {} {})", cond, left.0, as_bool(right)?);
+                    return Ok((ite, Sort::Bool));
+                }
+                let (left, right, sort) = unify(left, right)?;
+                Ok((format!("(ite {} {} {})", cond, left, right), sort))
+            }
+            Expression::Unary(unary) => {
+                let exp = unary.exp.print(env)?;
+                match unary.op {
+                    UnaryOp::Not => Ok((format!("(not {})", as_bool(exp)?), Sort::Bool)),
+                    UnaryOp::Floor | UnaryOp::Ceil if as_num(&exp)? == Sort::Int => Ok(exp),
+                    UnaryOp::Floor => Ok((format!("(to_int {})", exp.0), Sort::Int)),
+                    UnaryOp::Ceil => Ok((format!("(- (to_int (- {})))", exp.0), Sort::Int)),
+                    UnaryOp::Derivative => Err(SmtlibError::Unsupported("derivative")),
+                    #[cfg(feature = "transcendental")]
+                    _ => Err(SmtlibError::Unsupported("transcendental function")),
+                }
+            }
+            Expression::Binary(binary) => {
+                let left = binary.left.print(env)?;
+                let right = binary.right.print(env)?;
+                let op = match binary.op {
+                    BinaryOp::Or => "or",
+                    BinaryOp::And => "and",
+                    BinaryOp::Implication => "=>",
+                    BinaryOp::Equals | BinaryOp::NotEquals => {
+                        let op = if binary.op == BinaryOp::Equals {
+                            "="
+                        } else {
+                            "distinct"
+                        };
+                        let (left, right) = if left.1 == Sort::Bool {
+                            (left.0, as_bool(right)?)
+                        } else {
+                            let (left, right, _) = unify(left, right)?;
+                            (left, right)
+                        };
+                        return Ok((format!("({} {} {})", op, left, right), Sort::Bool));
+                    }
+                    BinaryOp::Less
+                    | BinaryOp::LessOrEqual
+                    | BinaryOp::Greater
+                    | BinaryOp::GreaterOrEqual => {
+                        let op = match binary.op {
+                            BinaryOp::Less => "<",
+                            BinaryOp::LessOrEqual => "<=",
+                            BinaryOp::Greater => ">",
+                            _ => ">=",
+                        };
+                        let (left, right, _) = unify(left, right)?;
+                        return Ok((format!("({} {} {})", op, left, right), Sort::Bool));
+                    }
+                    BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Times => {
+                        let op = match binary.op {
+                            BinaryOp::Plus => "+",
+                            BinaryOp::Minus => "-",
+                            _ => "*",
+                        };
+                        let (left, right, sort) = unify(left, right)?;
+                        return Ok((format!("({} {} {})", op, left, right), sort));
+                    }
+                    BinaryOp::Min | BinaryOp::Max => {
+                        let op = if binary.op == BinaryOp::Min {
+                            "<="
+                        } else {
+                            ">="
+                        };
+                        let (left, right, sort) = unify(left, right)?;
+                        let ite = format!("(ite ({1} {0} {2}) {0} {2})", left, op, right);
+                        return Ok((ite, sort));
+                    }
+                    // floored modulo from the Euclidean `mod`
+                    BinaryOp::Modulo => {
+                        if as_num(&left)? != Sort::Int || as_num(&right)? != Sort::Int {
+                            return Err(SmtlibError::Unsupported("modulo of reals"));
+                        }
+                        let rem = format!("(mod {} {})", left.0, right.0);
+                        let res = format!(
+                            "(ite (and (< {1} 0) (distinct {0} 0)) (+ {0} {1}) {0})",
+                            rem, right.0
+                        );
+                        return Ok((res, Sort::Int));
+                    }
+                    // real division, also for integer operands
+                    BinaryOp::Divide => {
+                        let res = format!("(/ {} {})", to_real(left)?, to_real(right)?);
+                        return Ok((res, Sort::Real));
+                    }
+                    BinaryOp::Pow => return Err(SmtlibError::Unsupported("pow")),
+                    BinaryOp::Log => return Err(SmtlibError::Unsupported("log")),
+                };
+                let res = format!("({} {} {})", op, as_bool(left)?, as_bool(right)?);
+                Ok((res, Sort::Bool))
+            }
+            Expression::NondetSelection(_) => {
+                Err(SmtlibError::Unsupported("nondeterministic selection"))
+            }
+            Expression::Call(_) => Err(SmtlibError::Unsupported("function call")),
+        }
+    }
+
+    /// Print a self-contained SMT-LIB program which declares every free
+    /// variable of this expression with the sort of its type in `env` and
+    /// then asserts the expression. Declarations are sorted by name. The
+    /// range of each bounded integer is asserted after its declaration.
+    pub fn to_smtlib_program(
+        &self,
+        env: &HashMap<Identifier, JaniType>,
+    ) -> Result<String, SmtlibError> {
+        let mut free_variables = self.free_variables();
+        free_variables.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut program = String::new();
+        for ident in free_variables {
+            let ty = env
+                .get(&ident)
+                .ok_or_else(|| SmtlibError::UnboundIdentifier(ident.clone()))?;
+            let name = symbol(&ident);
+            program.push_str(&format!(
+                "(declare-const {} {})\n",
+                name,
+                ty.to_smt_sort_name()
+            ));
+            if let JaniType::BoundedInt { lower, upper } = ty {
+                if let Some(lower) = lower {
+                    let lower = int_to_smtlib(&BigInt::from(*lower));
+                    program.push_str(&format!("(assert (<= {} {}))\n", lower, name));
+                }
+                if let Some(upper) = upper {
+                    let upper = int_to_smtlib(&BigInt::from(*upper));
+                    program.push_str(&format!("(assert (<= {} {}))\n", name, upper));
+                }
+            }
+        }
+        program.push_str(&format!("(assert {})\n", self.to_smtlib(env)?));
+        Ok(program)
+    }
+
+    /// The identifiers occurring free in this expression, i.e. not bound by
+    /// a nondeterministic selection, in the order of their first occurrence.
+    /// Names of called functions are not included.
+    pub fn free_variables(&self) -> Vec<Identifier> {
+        let mut res = Vec::new();
+        self.collect_free_variables(&mut Vec::new(), &mut res);
+        res
+    }
+
+    fn collect_free_variables(&self, bound: &mut Vec<Identifier>, res: &mut Vec<Identifier>) {
+        match self {
+            Expression::Constant(_) => {}
+            Expression::Identifier(ident) => {
+                if !bound.contains(ident) && !res.contains(ident) {
+                    res.push(ident.clone());
+                }
+            }
+            Expression::IfThenElse(ite) => {
+                ite.cond.collect_free_variables(bound, res);
+                ite.left.collect_free_variables(bound, res);
+                ite.right.collect_free_variables(bound, res);
+            }
+            Expression::Unary(unary) => unary.exp.collect_free_variables(bound, res),
+            Expression::Binary(binary) => {
+                binary.left.collect_free_variables(bound, res);
+                binary.right.collect_free_variables(bound, res);
+            }
+            Expression::NondetSelection(nondet) => {
+                bound.push(nondet.var.clone());
+                nondet.exp.collect_free_variables(bound, res);
+                bound.pop();
+            }
+            Expression::Call(call) => {
+                for arg in &call.args {
+                    arg.collect_free_variables(bound, res);
+                }
+            }
+        }
+    }
+}
+
+fn constant_to_smtlib(value: &ConstantValue) -> Result<Term, SmtlibError> {
+    if let ConstantValue::MathConstant(_) = value {
+        return Err(SmtlibError::Unsupported("math constant"));
+    }
+    match Value::from_constant(value) {
+        Ok(Value::Bool(b)) => Ok((b.to_string(), Sort::Bool)),
+        Ok(Value::Int(int)) => Ok((int_to_smtlib(&int), Sort::Int)),
+        Ok(Value::Real(real)) => Ok((real_to_smtlib(&real), Sort::Real)),
+        Err(_) => Err(SmtlibError::Unsupported("number literal")),
+    }
+}
+
+fn as_bool(term: Term) -> Result<String, SmtlibError> {
+    match term.1 {
+        Sort::Bool => Ok(term.0),
+        sort => Err(SmtlibError::TypeMismatch {
+            expected: "Bool",
+            found: sort.name(),
+        }),
+    }
+}
+
+fn as_num(term: &Term) -> Result<Sort, SmtlibError> {
+    match term.1 {
+        Sort::Bool => Err(SmtlibError::TypeMismatch {
+            expected: "Int or Real",
+            found: "Bool",
+        }),
+        sort => Ok(sort),
+    }
+}
+
+fn to_real(term: Term) -> Result<String, SmtlibError> {
+    Ok(match as_num(&term)? {
+        Sort::Int => format!("(to_real {})", term.0),
+        _ => term.0,
+    })
+}
+
+/// Convert both numeric terms to reals unless both are integers, and return
+/// them with the common sort.
+fn unify(left: Term, right: Term) -> Result<(String, String, Sort), SmtlibError> {
+    if as_num(&left)? == Sort::Int && as_num(&right)? == Sort::Int {
+        return Ok((left.0, right.0, Sort::Int));
+    }
+    Ok((to_real(left)?, to_real(right)?, Sort::Real))
+}
+
+fn int_to_smtlib(int: &BigInt) -> String {
+    if int.is_negative() {
+        format!("(- {})", int.abs())
+    } else {
+        int.to_string()
+    }
+}
+
+fn real_to_smtlib(real: &BigRational) -> String {
+    let abs = real.abs();
+    let res = if abs.is_integer() {
+        format!("{}.0", abs.numer())
+    } else {
+        format!("(/ {}.0 {}.0)", abs.numer(), abs.denom())
+    };
+    if real.is_negative() {
+        format!("(- {})", res)
+    } else {
+        res
+    }
+}
+
+/// Print the identifier as an SMT-LIB symbol, quoting it if necessary.
+fn symbol(ident: &Identifier) -> String {
+    let name = &ident.0;
+    let is_simple = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "~!@$%^&*_-+=<>.?/".contains(c))
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && !name.is_empty();
+    if is_simple {
+        name.clone()
+    } else {
+        format!("|{}|", name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::{
+        exprs::{
+            BinaryExpression, BinaryOp, ConstantValue, Expression, MathConstant, UnaryExpression,
+            UnaryOp,
+        },
+        test_util::{binary, ident},
+        types::JaniType,
+        Identifier,
+    };
+
+    use super::SmtlibError;
+
+    #[test]
+    fn test_to_smtlib() {
+        let env = HashMap::from([
+            (Identifier("x".to_owned()), JaniType::Int),
+            (Identifier("my var".to_owned()), JaniType::Int),
+            (Identifier("r".to_owned()), JaniType::Real),
+            (Identifier("b".to_owned()), JaniType::Bool),
+        ]);
+        let expr = binary(
+            BinaryOp::GreaterOrEqual,
+            ident("x") * Expression::Constant(ConstantValue::try_from(-2.5).unwrap()),
+            ident("my var") - 3.into(),
+        );
+        assert_eq!(
+            expr.to_smtlib(&env).unwrap(),
+            "(>= (* (to_real x) (- (/ 5.0 2.0))) (to_real (- |my var| 3)))"
+        );
+        let expr = binary(BinaryOp::Divide, ident("x"), 2.into());
+        assert_eq!(expr.to_smtlib(&env).unwrap(), "(/ (to_real x) (to_real 2))");
+        let expr = binary(BinaryOp::Min, ident("x"), 2.into()).eq_expr(ident("r"));
+        assert_eq!(
+            expr.to_smtlib(&env).unwrap(),
+            "(= (to_real (ite (<= x 2) x 2)) r)"
+        );
+        // the floor of an integer is the integer itself
+        let expr = Expression::from(UnaryExpression {
+            op: UnaryOp::Floor,
+            exp: ident("x"),
+        });
+        assert_eq!(expr.to_smtlib(&env).unwrap(), "x");
+        assert_eq!(
+            binary(BinaryOp::Modulo, ident("r"), 2.into()).to_smtlib(&env),
+            Err(SmtlibError::Unsupported("modulo of reals"))
+        );
+        assert_eq!(
+            (ident("b") + 1.into()).to_smtlib(&env),
+            Err(SmtlibError::TypeMismatch {
+                expected: "Int or Real",
+                found: "Bool"
+            })
+        );
+
+        let expr: Expression = BinaryExpression {
+            op: BinaryOp::Min,
+            left: ident("x"),
+            right: Expression::Constant(ConstantValue::MathConstant(MathConstant::Pi)),
+        }
+        .into();
+        assert_eq!(
+            expr.to_smtlib(&env),
+            Err(SmtlibError::Unsupported("math constant"))
+        );
+    }
+
+    #[test]
+    fn test_to_smtlib_program() {
+        let guard: Expression = BinaryExpression {
+            op: BinaryOp::Less,
+            left: ident("n") + 1.into(),
+            right: ident("r"),
+        }
+        .into();
+        let expr = guard & ident("b");
+        let mut env = HashMap::from([
+            (Identifier("n".to_owned()), JaniType::Int),
+            (Identifier("r".to_owned()), JaniType::Real),
+            (Identifier("b".to_owned()), JaniType::Bool),
+        ]);
+        assert_eq!(
+            expr.to_smtlib_program(&env).unwrap(),
+            "(declare-const b Bool)\n(declare-const n Int)\n(declare-const r Real)\n(assert (and (< (to_real (+ n 1)) r) b))\n"
+        );
+
+        env.insert(
+            Identifier("n".to_owned()),
+            JaniType::BoundedInt {
+                lower: Some(-1),
+                upper: Some(10),
+            },
+        );
+        assert_eq!(
+            expr.to_smtlib_program(&env).unwrap(),
+            "(declare-const b Bool)\n(declare-const n Int)\n(assert (<= (- 1) n))\n(assert (<= n 10))\n(declare-const r Real)\n(assert (and (< (to_real (+ n 1)) r) b))\n"
+        );
+
+        env.remove(&Identifier("r".to_owned()));
+        assert_eq!(
+            expr.to_smtlib_program(&env),
+            Err(SmtlibError::UnboundIdentifier(Identifier("r".to_owned())))
+        );
+    }
+}
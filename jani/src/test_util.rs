@@ -0,0 +1,16 @@
+//! Helpers shared by the tests of this crate.
+
+use crate::{
+    exprs::{BinaryExpression, BinaryOp, Expression},
+    Identifier,
+};
+
+/// The expression referring to the identifier `name`.
+pub(crate) fn ident(name: &str) -> Expression {
+    Expression::Identifier(Identifier(name.to_owned()))
+}
+
+/// The binary expression `left op right`.
+pub(crate) fn binary(op: BinaryOp, left: Expression, right: Expression) -> Expression {
+    BinaryExpression { op, left, right }.into()
+}
@@ -1,8 +1,10 @@
 //! JANI types.
 
+use std::fmt::Display;
+
 use serde::{Deserialize, Serialize};
 
-use crate::exprs::Expression;
+use crate::exprs::{ConstantValue, Expression};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -67,3 +69,152 @@ pub enum Type {
     BoundedType(BoundedType),
     OtherType(OtherType),
 }
+
+/// The type of a JANI variable or expression as far as it is relevant for
+/// reasoning about it with an SMT solver. It is (de)serialized like the
+/// corresponding [`Type`], but only integer bounds are supported for bounded
+/// types.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "Type", into = "Type")]
+pub enum JaniType {
+    Bool,
+    Int,
+    Real,
+    /// An integer with an optional lower and upper bound (inclusive). At least
+    /// one of the bounds must be present.
+    BoundedInt {
+        lower: Option<i64>,
+        upper: Option<i64>,
+    },
+}
+
+impl JaniType {
+    /// The name of the corresponding SMT-LIB sort. Bounded integers are
+    /// represented by `Int` and need separate constraints for their range.
+    pub fn to_smt_sort_name(&self) -> &'static str {
+        match self {
+            JaniType::Bool => "Bool",
+            JaniType::Int | JaniType::BoundedInt { .. } => "Int",
+            JaniType::Real => "Real",
+        }
+    }
+}
+
+/// This error is emitted from the [`TryFrom<Type>`] implementation for
+/// [`JaniType`] if the type has no corresponding [`JaniType`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedTypeError(pub &'static str);
+
+impl Display for UnsupportedTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unsupported type: {}", self.0)
+    }
+}
+
+impl TryFrom<Type> for JaniType {
+    type Error = UnsupportedTypeError;
+
+    fn try_from(value: Type) -> Result<Self, Self::Error> {
+        match value {
+            Type::BasicType(BasicType::Bool) => Ok(JaniType::Bool),
+            Type::BasicType(BasicType::Int) => Ok(JaniType::Int),
+            Type::BasicType(BasicType::Real) => Ok(JaniType::Real),
+            Type::BoundedType(bounded) => {
+                if bounded.base != BoundedTypeBase::Int {
+                    return Err(UnsupportedTypeError("bounded real"));
+                }
+                if !bounded.is_valid() {
+                    return Err(UnsupportedTypeError("bounded type without bounds"));
+                }
+                let bound = |bound: Option<Box<Expression>>| match bound.as_deref() {
+                    None => Ok(None),
+                    Some(Expression::Constant(ConstantValue::Number(number))) => number
+                        .as_i64()
+                        .map(Some)
+                        .ok_or(UnsupportedTypeError("non-integer bound")),
+                    Some(_) => Err(UnsupportedTypeError("non-literal bound")),
+                };
+                Ok(JaniType::BoundedInt {
+                    lower: bound(bounded.lower_bound)?,
+                    upper: bound(bounded.upper_bound)?,
+                })
+            }
+            Type::OtherType(OtherType::Clock) => Err(UnsupportedTypeError("clock")),
+            Type::OtherType(OtherType::Continuous) => Err(UnsupportedTypeError("continuous")),
+        }
+    }
+}
+
+impl From<JaniType> for Type {
+    fn from(value: JaniType) -> Self {
+        match value {
+            JaniType::Bool => Type::BasicType(BasicType::Bool),
+            JaniType::Int => Type::BasicType(BasicType::Int),
+            JaniType::Real => Type::BasicType(BasicType::Real),
+            JaniType::BoundedInt { lower, upper } => {
+                let bound = |bound: Option<i64>| {
+                    bound.map(|bound| {
+                        Box::new(Expression::Constant(ConstantValue::Number(bound.into())))
+                    })
+                };
+                Type::BoundedType(BoundedType {
+                    base: BoundedTypeBase::Int,
+                    lower_bound: bound(lower),
+                    upper_bound: bound(upper),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::JaniType;
+
+    #[test]
+    fn test_smt_sort_name() {
+        assert_eq!(JaniType::Bool.to_smt_sort_name(), "Bool");
+        assert_eq!(JaniType::Int.to_smt_sort_name(), "Int");
+        assert_eq!(JaniType::Real.to_smt_sort_name(), "Real");
+        let bounded = JaniType::BoundedInt {
+            lower: Some(0),
+            upper: None,
+        };
+        assert_eq!(bounded.to_smt_sort_name(), "Int");
+    }
+
+    #[test]
+    fn test_serde() {
+        let cases = [
+            (JaniType::Bool, r#""bool""#),
+            (JaniType::Int, r#""int""#),
+            (JaniType::Real, r#""real""#),
+            (
+                JaniType::BoundedInt {
+                    lower: Some(-1),
+                    upper: Some(10),
+                },
+                r#"{"kind":"bounded","base":"int","lower-bound":-1,"upper-bound":10}"#,
+            ),
+            (
+                JaniType::BoundedInt {
+                    lower: None,
+                    upper: Some(3),
+                },
+                r#"{"kind":"bounded","base":"int","upper-bound":3}"#,
+            ),
+        ];
+        for (ty, json) in cases {
+            assert_eq!(serde_json::to_string(&ty).unwrap(), json);
+            assert_eq!(serde_json::from_str::<JaniType>(json).unwrap(), ty);
+        }
+
+        for json in [
+            r#""clock""#,
+            r#"{"kind":"bounded","base":"real","lower-bound":0}"#,
+            r#"{"kind":"bounded","base":"int","lower-bound":0.5}"#,
+        ] {
+            assert!(serde_json::from_str::<JaniType>(json).is_err());
+        }
+    }
+}
@@ -183,7 +183,7 @@ impl<'ctx> SliceSolver<'ctx> {
             .map(|(_, value)| value.clone())
             .collect();
         self.prover.add_assumption(&Bool::and(
-            self.prover.solver().get_context(),
+            self.prover.get_context(),
             &active_toggle_values,
         ));
         self.prover.check_proof()
@@ -40,6 +40,12 @@ use z3::{
     Context, Sort,
 };
 
+/// The full version string of the Z3 library that is linked, e.g. for bug
+/// reports.
+pub fn z3_version() -> String {
+    z3::full_version().to_owned()
+}
+
 /// SMT values who have an associated factory to create new values of its type.
 pub trait SmtFactory<'ctx> {
     /// The value of the factory type. You'll probably use [`Factory`] most of the time.
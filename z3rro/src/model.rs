@@ -1,20 +1,26 @@
 //! SMT objects that can be evaluated in a model to return a concrete SMT type.
 
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
     fmt::{self, Display},
-    str::FromStr,
+    io::{self, Write},
+    time::Instant,
 };
 
-use num::{BigInt, BigRational};
+use num::{BigInt, BigRational, Zero};
 
 use thiserror::Error;
 
 use z3::{
-    ast::{Ast, Bool, Dynamic, Int, Real},
-    FuncDecl, FuncInterp, Model,
+    ast::{Array, Ast, Bool, Datatype, Dynamic, Int, Real},
+    DatatypeVariant, DeclKind, FuncDecl, FuncInterp, Model, SortKind,
 };
 
+use crate::smtlib::parse_rational;
+
 /// Whether the model is guaranteed to be consistent with the constraints added
 /// to the solver or not. When the SMT solver returns `SAT`, the model is
 /// consistent (modulo bugs), but when the solver returns `UNKNOWN` we can also
@@ -43,6 +49,11 @@ pub struct InstrumentedModel<'ctx> {
     consistency: ModelConsistency,
     model: Model<'ctx>,
     accessed_decls: RefCell<AccessedDecls<'ctx>>,
+    /// Values computed by the [`SmtEval`] implementations, keyed by the term
+    /// and whether model completion was used. The model is immutable, so the
+    /// cached values never change.
+    value_cache: RefCell<HashMap<(Dynamic<'ctx>, bool), ConcreteValue>>,
+    cache_hits: Cell<usize>,
 }
 
 impl<'ctx> InstrumentedModel<'ctx> {
@@ -51,6 +62,8 @@ impl<'ctx> InstrumentedModel<'ctx> {
             consistency,
             model,
             accessed_decls: Default::default(),
+            value_cache: Default::default(),
+            cache_hits: Cell::new(0),
         }
     }
 
@@ -83,12 +96,328 @@ impl<'ctx> InstrumentedModel<'ctx> {
         Some(res)
     }
 
+    /// Like [`InstrumentedModel::eval_ast`], but does not count as an access.
+    pub(crate) fn eval_ast_untracked<T: Ast<'ctx>>(
+        &self,
+        ast: &T,
+        model_completion: bool,
+    ) -> Option<T> {
+        self.model.eval(ast, model_completion)
+    }
+
+    /// Like [`InstrumentedModel::eval_ast`], but gives up with
+    /// [`SmtEvalError::Timeout`] once `deadline` has passed. Z3's own
+    /// evaluation cannot be interrupted, so the deadline is checked while
+    /// tracking the accessed declarations and before and after evaluating the
+    /// node. Tracked accesses are rolled back on timeout.
+    pub fn eval_ast_with_deadline<T: Ast<'ctx>>(
+        &self,
+        ast: &T,
+        model_completion: bool,
+        deadline: Instant,
+    ) -> Result<T, SmtEvalError> {
+        self.atomically(|| {
+            self.accessed_decls
+                .borrow_mut()
+                .mark_expr_until(ast, Some(deadline))?;
+            check_deadline(deadline)?;
+            let res = self
+                .model
+                .eval(ast, model_completion)
+                .ok_or(SmtEvalError::EvalError)?;
+            check_deadline(deadline)?;
+            Ok(res)
+        })
+    }
+
+    /// Evaluate the field with index `field` of the datatype `value`, which
+    /// must be constructed by `variant`. Z3 does not specify the result of an
+    /// accessor applied to a value of a different constructor, so this is
+    /// checked first and reported as [`SmtEvalError::WrongConstructor`].
+    pub fn eval_datatype_field(
+        &self,
+        value: &Datatype<'ctx>,
+        variant: &DatatypeVariant<'ctx>,
+        field: usize,
+    ) -> Result<Dynamic<'ctx>, SmtEvalError> {
+        let accessor = variant
+            .accessors
+            .get(field)
+            .ok_or_else(|| SmtEvalError::NoSuchField {
+                constructor: variant.constructor.name(),
+                field,
+            })?;
+        self.atomically(|| {
+            let is_variant = variant.tester.apply(&[value]).as_bool().unwrap();
+            if !is_variant.eval(self)? {
+                let constructor = self
+                    .eval_ast(value, true)
+                    .ok_or(SmtEvalError::EvalError)?
+                    .decl()
+                    .name();
+                return Err(SmtEvalError::WrongConstructor {
+                    accessor: accessor.name(),
+                    constructor,
+                });
+            }
+            self.eval_ast(&accessor.apply(&[value]), true)
+                .ok_or(SmtEvalError::EvalError)
+        })
+    }
+
+    /// Return the cached value of `ast` or compute it with `eval` and cache
+    /// the result. Values computed with and without `model_completion` are
+    /// cached separately. Accesses are tracked in both cases.
+    fn eval_cached<T: Ast<'ctx>>(
+        &self,
+        ast: &T,
+        model_completion: bool,
+        eval: impl FnOnce() -> Result<ConcreteValue, SmtEvalError>,
+    ) -> Result<ConcreteValue, SmtEvalError> {
+        let key = (Dynamic::from_ast(ast), model_completion);
+        if let Some(value) = self.value_cache.borrow().get(&key) {
+            self.accessed_decls.borrow_mut().mark_expr(ast);
+            self.cache_hits.set(self.cache_hits.get() + 1);
+            return Ok(value.clone());
+        }
+        let value = eval()?;
+        self.value_cache.borrow_mut().insert(key, value.clone());
+        Ok(value)
+    }
+
+    /// The number of evaluations that were answered from the cache.
+    pub fn cache_hits(&self) -> usize {
+        self.cache_hits.get()
+    }
+
     /// Get the function interpretation for this `f`.
     pub fn get_func_interp(&self, f: &FuncDecl<'ctx>) -> Option<FuncInterp<'ctx>> {
         self.accessed_decls.borrow_mut().mark_func_decl(f);
         self.model.get_func_interp(f)
     }
 
+    /// Iterate over the entries of the interpretation of `f`, each evaluated
+    /// into the [`ConcreteValue`]s of its arguments and its value. Entries
+    /// are converted lazily, so large tables can be printed incrementally.
+    /// Values that cannot be converted are returned as
+    /// [`ConcreteValue::Raw`]. The default value is returned by
+    /// [`Self::func_interp_else`]. `f` is marked as accessed.
+    pub fn func_interp_entries(
+        &self,
+        f: &FuncDecl<'ctx>,
+    ) -> impl Iterator<Item = (Vec<ConcreteValue>, ConcreteValue)> + '_ {
+        let entries = match self.get_func_interp(f) {
+            Some(interp) => interp.get_entries(),
+            None => Vec::new(),
+        };
+        entries.into_iter().map(|entry| {
+            let args = entry
+                .get_args()
+                .iter()
+                .map(to_concrete_value_or_raw)
+                .collect();
+            (args, to_concrete_value_or_raw(&entry.get_value()))
+        })
+    }
+
+    /// The default value of the interpretation of `f` for all arguments not
+    /// listed in [`Self::func_interp_entries`]. `f` is marked as accessed.
+    pub fn func_interp_else(&self, f: &FuncDecl<'ctx>) -> Option<ConcreteValue> {
+        let interp = self.get_func_interp(f)?;
+        Some(to_concrete_value_or_raw(&interp.get_else()))
+    }
+
+    /// Return the values of all indices of `array` that were read during the
+    /// evaluation of `select` terms so far, in no particular order.
+    pub fn accessed_array_indices(&self, array: &Array<'ctx>) -> Vec<ConcreteValue> {
+        let indices = self
+            .accessed_decls
+            .borrow()
+            .accessed_indices
+            .get(&array.decl().name())
+            .cloned()
+            .unwrap_or_default();
+        let mut values: Vec<ConcreteValue> = Vec::new();
+        for index in indices {
+            if let Some(value) = self.concrete_value(&index) {
+                if !values.contains(&value) {
+                    values.push(value);
+                }
+            }
+        }
+        values
+    }
+
+    /// Evaluate `ast` to a [`ConcreteValue`] without tracking accesses.
+    pub(crate) fn concrete_value(&self, ast: &Dynamic<'ctx>) -> Option<ConcreteValue> {
+        to_concrete_value(&self.model.eval(ast, true)?)
+    }
+
+    /// Evaluate each of `vars` into a [`ConcreteValue`] with model completion,
+    /// keyed by the printed variable. Unlike [`SmtEval`], evaluation does not
+    /// stop at the first failure: the values that could be evaluated and the
+    /// errors for all others are returned, so a partial model can still be
+    /// reported.
+    pub fn try_assignments(
+        &self,
+        vars: &[Dynamic<'ctx>],
+    ) -> (HashMap<String, ConcreteValue>, Vec<(String, SmtEvalError)>) {
+        let mut values = HashMap::new();
+        let mut errors = Vec::new();
+        for var in vars {
+            let res = self.eval_cached(var, true, || {
+                let value = self.eval_ast(var, true).ok_or(SmtEvalError::EvalError)?;
+                to_concrete_value(&value).ok_or(SmtEvalError::ParseError)
+            });
+            match res {
+                Ok(value) => {
+                    values.insert(var.to_string(), value);
+                }
+                Err(err) => errors.push((var.to_string(), err)),
+            }
+        }
+        (values, errors)
+    }
+
+    /// Evaluate all assignments of this model into an [`OwnedModel`], which
+    /// does not depend on the Z3 context anymore. Constants whose values
+    /// cannot be converted and function interpretations are stored as
+    /// [`ConcreteValue::Raw`]. This does not count as an access.
+    pub fn to_owned_model(&self) -> OwnedModel {
+        let mut assignments = BTreeMap::new();
+        for decl in self.model.iter() {
+            let value = if decl.arity() == 0 {
+                match self.model.get_const_interp(&decl.apply(&[])) {
+                    Some(value) => to_concrete_value_or_raw(&value),
+                    None => continue,
+                }
+            } else {
+                match self.model.get_func_interp(&decl) {
+                    Some(interp) => ConcreteValue::Raw(interp.to_string()),
+                    None => continue,
+                }
+            };
+            assignments.insert(decl.name(), value);
+        }
+        OwnedModel {
+            consistency: self.consistency,
+            assignments,
+        }
+    }
+
+    /// Format this model using the given [`ModelFormatter`]. Declarations that
+    /// were accessed so far come first, followed by the section of unaccessed
+    /// declarations. Formatting does not count as an access.
+    pub fn format_with(&self, formatter: &impl ModelFormatter) -> String {
+        let mut buf = Vec::new();
+        self.write_with(formatter, &mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    /// Like [`Self::format_with`], but the accessed declarations are streamed
+    /// to `w`. Only the section of unaccessed declarations is built in memory.
+    pub fn write_with(&self, formatter: &impl ModelFormatter, w: &mut dyn Write) -> io::Result<()> {
+        let mut unaccessed = Vec::new();
+        let mut first = true;
+        for (decl, entry) in self.entries(formatter) {
+            if !self.accessed_decls.borrow().is_func_decl_accessed(&decl) {
+                unaccessed.push(entry);
+                continue;
+            }
+            if !first {
+                w.write_all(formatter.separator().as_bytes())?;
+            }
+            w.write_all(entry.as_bytes())?;
+            first = false;
+        }
+        if !unaccessed.is_empty() {
+            if !first {
+                w.write_all(formatter.separator().as_bytes())?;
+            }
+            w.write_all(formatter.format_unaccessed(unaccessed).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Render the model with one `name -> value` line per declaration like
+    /// [`DefaultFormatter`], but in a stable order independent of Z3: all
+    /// constants sorted by name, followed by all function interpretations
+    /// sorted by name. Rendering does not count as an access.
+    pub fn to_sorted_string(&self) -> String {
+        let mut res = String::new();
+        self.write_lines(true, |line| {
+            res.push_str(line);
+            Ok::<_, Infallible>(())
+        })
+        .unwrap();
+        res
+    }
+
+    /// Stream the output of [`Self::to_sorted_string`] to `w`. The entries are
+    /// formatted one by one, but must be collected for sorting.
+    pub fn write_sorted_to(&self, w: &mut dyn Write) -> io::Result<()> {
+        self.write_lines(true, |line| w.write_all(line.as_bytes()))
+    }
+
+    /// Stream the model with one `name -> value` line per declaration in Z3's
+    /// order to `w`, without rendering the whole model into a string first.
+    /// Unlike the [`Display`] output, function interpretations are printed
+    /// like [`DefaultFormatter`] does. Writing does not count as an access.
+    pub fn write_to(&self, w: &mut dyn Write) -> io::Result<()> {
+        self.write_lines(false, |line| w.write_all(line.as_bytes()))
+    }
+
+    /// The declarations with an interpretation in Z3's order, together with
+    /// their entry formatted by `formatter`.
+    fn entries<'a>(
+        &'a self,
+        formatter: &'a impl ModelFormatter,
+    ) -> impl Iterator<Item = (FuncDecl<'ctx>, String)> + 'a {
+        self.model.iter().filter_map(move |decl| {
+            let name = decl.name();
+            let entry = if decl.arity() == 0 {
+                let value = self.model.get_const_interp(&decl.apply(&[]))?;
+                formatter.format_constant(&name, &value)
+            } else {
+                let interp = self.model.get_func_interp(&decl)?;
+                formatter.format_function(&name, &interp)
+            };
+            Some((decl, entry))
+        })
+    }
+
+    /// Pass each entry formatted by [`DefaultFormatter`] as a line to `write`,
+    /// either in Z3's order or sorted like [`Self::to_sorted_string`].
+    fn write_lines<E>(
+        &self,
+        sorted: bool,
+        mut write: impl FnMut(&str) -> Result<(), E>,
+    ) -> Result<(), E> {
+        if !sorted {
+            for (_, entry) in self.entries(&DefaultFormatter) {
+                write(&entry)?;
+                write("\n")?;
+            }
+            return Ok(());
+        }
+        let mut constants = Vec::new();
+        let mut functions = Vec::new();
+        for (decl, entry) in self.entries(&DefaultFormatter) {
+            if decl.arity() == 0 {
+                constants.push((decl.name(), entry));
+            } else {
+                functions.push((decl.name(), entry));
+            }
+        }
+        constants.sort();
+        functions.sort();
+        for (_, entry) in constants.into_iter().chain(functions) {
+            write(&entry)?;
+            write("\n")?;
+        }
+        Ok(())
+    }
+
     /// Iterate over all function declarations that were not accessed using
     /// `eval` so far.
     pub fn iter_unaccessed(&self) -> impl Iterator<Item = FuncDecl<'ctx>> + '_ {
@@ -98,6 +427,7 @@ impl<'ctx> InstrumentedModel<'ctx> {
     }
 
     /// Reset the internally tracked accessed declarations and expressions.
+    /// Cached values are kept since they do not change.
     pub fn reset_accessed(&mut self) {
         self.accessed_decls = Default::default();
     }
@@ -107,6 +437,46 @@ impl<'ctx> InstrumentedModel<'ctx> {
     }
 }
 
+/// Convert a value from a model into a [`ConcreteValue`].
+fn to_concrete_value(value: &Dynamic<'_>) -> Option<ConcreteValue> {
+    Some(match value.sort_kind() {
+        SortKind::Bool => ConcreteValue::Bool(value.as_bool()?.as_bool()?),
+        SortKind::Int => ConcreteValue::Int(parse_int(&value.as_int()?)?),
+        SortKind::Real => {
+            let (num, den) = value.as_real()?.as_real()?;
+            ConcreteValue::Real(BigRational::new(num.into(), den.into()))
+        }
+        SortKind::BV => {
+            let bv = value.as_bv()?;
+            ConcreteValue::BitVec {
+                value: bv.as_u64()?.into(),
+                width: bv.get_size(),
+            }
+        }
+        SortKind::Seq => match value.as_string().and_then(|string| string.as_string()) {
+            Some(string) => ConcreteValue::String(string),
+            None => ConcreteValue::Raw(value.to_string()),
+        },
+        _ => ConcreteValue::Raw(value.to_string()),
+    })
+}
+
+/// Convert an integer numeral into a [`BigInt`]. Z3's `as_i64` only covers
+/// values that fit into an `i64`, so larger ones are parsed from their
+/// printed form.
+fn parse_int(value: &Int<'_>) -> Option<BigInt> {
+    match value.as_i64() {
+        Some(value) => Some(value.into()),
+        None => parse_rational(&value.to_string())
+            .filter(|value| value.is_integer())
+            .map(|value| value.to_integer()),
+    }
+}
+
+fn to_concrete_value_or_raw(value: &Dynamic<'_>) -> ConcreteValue {
+    to_concrete_value(value).unwrap_or_else(|| ConcreteValue::Raw(value.to_string()))
+}
+
 /// The [`Display`] implementation simply defers to the underlying
 /// [`z3::Model`]'s implementation.
 impl Display for InstrumentedModel<'_> {
@@ -115,12 +485,255 @@ impl Display for InstrumentedModel<'_> {
     }
 }
 
+/// Customizable formatting of an [`InstrumentedModel`], see
+/// [`InstrumentedModel::format_with`].
+pub trait ModelFormatter {
+    /// Format the assignment of a constant.
+    fn format_constant(&self, name: &str, value: &Dynamic<'_>) -> String;
+
+    /// Format the interpretation of a function.
+    fn format_function(&self, name: &str, interp: &FuncInterp<'_>) -> String;
+
+    /// Format the section of already formatted entries for declarations that
+    /// were not accessed during evaluation.
+    fn format_unaccessed(&self, entries: Vec<String>) -> String {
+        entries.join(self.separator())
+    }
+
+    /// The separator between entries.
+    fn separator(&self) -> &str {
+        "\n"
+    }
+}
+
+/// Formats each entry like Z3's model output, i.e. `name -> value`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultFormatter;
+
+impl ModelFormatter for DefaultFormatter {
+    fn format_constant(&self, name: &str, value: &Dynamic<'_>) -> String {
+        format!("{} -> {}", name, value)
+    }
+
+    fn format_function(&self, name: &str, interp: &FuncInterp<'_>) -> String {
+        format!("{} -> {}", name, interp)
+    }
+}
+
+/// Formats the model on a single line and only shows the number of
+/// declarations that were not accessed.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CompactFormatter;
+
+impl ModelFormatter for CompactFormatter {
+    fn format_constant(&self, name: &str, value: &Dynamic<'_>) -> String {
+        format!("{}={}", name, value)
+    }
+
+    fn format_function(&self, name: &str, interp: &FuncInterp<'_>) -> String {
+        format!("{}={}", name, interp)
+    }
+
+    fn format_unaccessed(&self, entries: Vec<String>) -> String {
+        format!("({} more)", entries.len())
+    }
+
+    fn separator(&self) -> &str {
+        ", "
+    }
+}
+
+/// A concrete value obtained by evaluating an SMT expression in a model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConcreteValue {
+    Bool(bool),
+    Int(BigInt),
+    Real(BigRational),
+    /// A bit-vector value of the given width, interpreted as unsigned.
+    BitVec {
+        value: BigInt,
+        width: u32,
+    },
+    String(String),
+    Array(ArrayValue),
+    Datatype(DatatypeValue),
+    /// Any other value, in Z3's textual representation.
+    Raw(String),
+}
+
+impl ConcreteValue {
+    /// Compare a numeric value with zero. Bit-vectors are unsigned, so they
+    /// are never negative. Returns `None` for values that are not numbers.
+    pub fn sign(&self) -> Option<Ordering> {
+        match self {
+            ConcreteValue::Int(value) | ConcreteValue::BitVec { value, .. } => {
+                Some(value.cmp(&BigInt::zero()))
+            }
+            ConcreteValue::Real(value) => Some(value.cmp(&BigRational::zero())),
+            _ => None,
+        }
+    }
+}
+
+/// The value of an array: a finite list of entries and a default value for all
+/// other indices.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArrayValue {
+    pub entries: Vec<(ConcreteValue, ConcreteValue)>,
+    pub default: Box<ConcreteValue>,
+}
+
+/// The value of a datatype: a constructor applied to the values of its fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatatypeValue {
+    pub constructor: String,
+    pub fields: Vec<ConcreteValue>,
+}
+
+impl From<bool> for ConcreteValue {
+    fn from(value: bool) -> Self {
+        ConcreteValue::Bool(value)
+    }
+}
+
+impl From<BigInt> for ConcreteValue {
+    fn from(value: BigInt) -> Self {
+        ConcreteValue::Int(value)
+    }
+}
+
+impl From<BigRational> for ConcreteValue {
+    fn from(value: BigRational) -> Self {
+        ConcreteValue::Real(value)
+    }
+}
+
+impl From<ArrayValue> for ConcreteValue {
+    fn from(value: ArrayValue) -> Self {
+        ConcreteValue::Array(value)
+    }
+}
+
+impl From<DatatypeValue> for ConcreteValue {
+    fn from(value: DatatypeValue) -> Self {
+        ConcreteValue::Datatype(value)
+    }
+}
+
+/// Values are displayed in an SMT-LIB-like syntax. Reals are printed as exact
+/// fractions.
+impl Display for ConcreteValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConcreteValue::Bool(value) => write!(f, "{}", value),
+            ConcreteValue::Int(value) => write!(f, "{}", value),
+            ConcreteValue::Real(value) => write!(f, "{}", value),
+            ConcreteValue::BitVec { value, width } => write!(f, "(_ bv{} {})", value, width),
+            ConcreteValue::String(value) => write!(f, "\"{}\"", value.replace('"', "\"\"")),
+            ConcreteValue::Array(value) => write!(f, "{}", value),
+            ConcreteValue::Datatype(value) => write!(f, "{}", value),
+            ConcreteValue::Raw(value) => f.write_str(value),
+        }
+    }
+}
+
+impl Display for ArrayValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[")?;
+        for (index, value) in &self.entries {
+            write!(f, "{} -> {}, ", index, value)?;
+        }
+        write!(f, "else -> {}]", self.default)
+    }
+}
+
+impl Display for DatatypeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.fields.is_empty() {
+            return f.write_str(&self.constructor);
+        }
+        write!(f, "({}", self.constructor)?;
+        for field in &self.fields {
+            write!(f, " {}", field)?;
+        }
+        f.write_str(")")
+    }
+}
+
+/// A model whose assignments were evaluated into [`ConcreteValue`]s by
+/// [`InstrumentedModel::to_owned_model`]. In contrast to the
+/// [`InstrumentedModel`], it does not borrow the Z3 context and can be sent to
+/// other threads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedModel {
+    consistency: ModelConsistency,
+    assignments: BTreeMap<String, ConcreteValue>,
+}
+
+impl OwnedModel {
+    /// Get the consistency of the model this was created from.
+    pub fn consistency(&self) -> ModelConsistency {
+        self.consistency
+    }
+
+    /// Get the value assigned to the declaration with the given name.
+    pub fn get(&self, name: &str) -> Option<&ConcreteValue> {
+        self.assignments.get(name)
+    }
+
+    /// Iterate over all assignments, ordered by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ConcreteValue)> {
+        self.assignments
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.assignments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assignments.is_empty()
+    }
+}
+
+/// Formats each assignment like the [`DefaultFormatter`], ordered by name.
+impl Display for OwnedModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.assignments {
+            writeln!(f, "{} -> {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Error, Debug, Clone)]
 pub enum SmtEvalError {
     #[error("solver failed to evaluate a value")]
     EvalError,
     #[error("could not parse value from solver")]
     ParseError,
+    /// The value is an irrational algebraic number, e.g. the square root of
+    /// two. It is given as the solver's `root-obj` term.
+    #[error("value {0} is irrational")]
+    Irrational(String),
+    #[error("evaluation exceeded its deadline")]
+    Timeout,
+    #[error("accessor {accessor} does not belong to the constructor {constructor} of the value")]
+    WrongConstructor {
+        accessor: String,
+        constructor: String,
+    },
+    #[error("constructor {constructor} has no field with index {field}")]
+    NoSuchField { constructor: String, field: usize },
+}
+
+fn check_deadline(deadline: Instant) -> Result<(), SmtEvalError> {
+    if Instant::now() >= deadline {
+        Err(SmtEvalError::Timeout)
+    } else {
+        Ok(())
+    }
 }
 
 /// Keeps track of the accessed declarations during evaluation of the model.
@@ -131,6 +744,9 @@ struct AccessedDecls<'ctx> {
     // TODO: turn this into a HashSet of FuncDecls when the type implements Hash
     accessed_decls: im_rc::HashSet<String>,
     accessed_exprs: im_rc::HashSet<Dynamic<'ctx>>,
+    /// The index expressions of `select` terms for each accessed array
+    /// constant.
+    accessed_indices: im_rc::HashMap<String, im_rc::HashSet<Dynamic<'ctx>>>,
 }
 
 impl<'ctx> AccessedDecls<'ctx> {
@@ -143,19 +759,51 @@ impl<'ctx> AccessedDecls<'ctx> {
     }
 
     pub fn mark_expr<T: Ast<'ctx>>(&mut self, ast: &T) {
-        if ast.is_const() {
-            self.accessed_decls.insert(ast.decl().name());
-        } else if ast.is_app() {
-            for child in ast.children() {
-                // some Z3 expressions might be extremely big because they
-                // contain big expressions repeatedly. so the following check is
-                // necessary to avoid walking through these expressions for a
-                // very long time.
-                let prev = self.accessed_exprs.insert(child.clone());
-                if prev.is_some() {
-                    continue;
+        // without a deadline, marking cannot fail
+        let _ = self.mark_expr_until(ast, None);
+    }
+
+    /// Mark all declarations in `ast`, returning [`SmtEvalError::Timeout`] if
+    /// `deadline` passes before the whole expression was visited.
+    pub fn mark_expr_until<T: Ast<'ctx>>(
+        &mut self,
+        ast: &T,
+        deadline: Option<Instant>,
+    ) -> Result<(), SmtEvalError> {
+        let mut worklist = vec![Dynamic::from_ast(ast)];
+        while let Some(ast) = worklist.pop() {
+            if let Some(deadline) = deadline {
+                check_deadline(deadline)?;
+            }
+            if ast.is_const() {
+                self.accessed_decls.insert(ast.decl().name());
+            } else if ast.is_app() {
+                if ast.decl().kind() == DeclKind::SELECT {
+                    self.mark_array_index(&ast);
+                }
+                for child in ast.children() {
+                    // some Z3 expressions might be extremely big because they
+                    // contain big expressions repeatedly. so the following
+                    // check is necessary to avoid walking through these
+                    // expressions for a very long time.
+                    let prev = self.accessed_exprs.insert(child.clone());
+                    if prev.is_some() {
+                        continue;
+                    }
+                    worklist.push(child);
                 }
-                self.mark_expr(&child);
+            }
+        }
+        Ok(())
+    }
+
+    fn mark_array_index<T: Ast<'ctx>>(&mut self, select: &T) {
+        if let [array, index] = select.children().as_slice() {
+            if array.is_const() {
+                self.accessed_indices
+                    .entry(array.decl().name())
+                    .or_default()
+                    .insert(index.clone());
             }
         }
     }
@@ -167,17 +815,33 @@ pub trait SmtEval<'ctx> {
 
     // TODO: pass a model completion option?
     fn eval(&self, model: &InstrumentedModel<'ctx>) -> Result<Self::Value, SmtEvalError>;
+
+    /// Evaluate to a [`ConcreteValue`].
+    fn eval_concrete(&self, model: &InstrumentedModel<'ctx>) -> Result<ConcreteValue, SmtEvalError>
+    where
+        Self::Value: Into<ConcreteValue>,
+    {
+        self.eval(model).map(Into::into)
+    }
 }
 
 impl<'ctx> SmtEval<'ctx> for Bool<'ctx> {
     type Value = bool;
 
     fn eval(&self, model: &InstrumentedModel<'ctx>) -> Result<bool, SmtEvalError> {
-        Ok(model
-            .eval_ast(self, false)
-            .ok_or(SmtEvalError::EvalError)?
-            .as_bool()
-            .unwrap_or(true))
+        let value = model.eval_cached(self, false, || {
+            Ok(ConcreteValue::Bool(
+                model
+                    .eval_ast(self, false)
+                    .ok_or(SmtEvalError::EvalError)?
+                    .as_bool()
+                    .unwrap_or(true),
+            ))
+        })?;
+        match value {
+            ConcreteValue::Bool(value) => Ok(value),
+            _ => unreachable!(),
+        }
     }
 }
 
@@ -185,13 +849,16 @@ impl<'ctx> SmtEval<'ctx> for Int<'ctx> {
     type Value = BigInt;
 
     fn eval(&self, model: &InstrumentedModel<'ctx>) -> Result<BigInt, SmtEvalError> {
-        // TODO: Z3's as_i64 only returns an i64 value. is there something more complete?
-        let value = model
-            .eval_ast(self, true)
-            .ok_or(SmtEvalError::EvalError)?
-            .as_i64()
-            .ok_or(SmtEvalError::ParseError)?;
-        Ok(BigInt::from(value))
+        let value = model.eval_cached(self, true, || {
+            let value = model.eval_ast(self, true).ok_or(SmtEvalError::EvalError)?;
+            Ok(ConcreteValue::Int(
+                parse_int(&value).ok_or(SmtEvalError::ParseError)?,
+            ))
+        })?;
+        match value {
+            ConcreteValue::Int(value) => Ok(value),
+            _ => unreachable!(),
+        }
     }
 }
 
@@ -199,38 +866,562 @@ impl<'ctx> SmtEval<'ctx> for Real<'ctx> {
     type Value = BigRational;
 
     fn eval(&self, model: &InstrumentedModel<'ctx>) -> Result<Self::Value, SmtEvalError> {
-        let res = model
-            .eval_ast(self, false) // TODO
-            .ok_or(SmtEvalError::EvalError)?;
-
-        // The .as_real() method only returns a pair of i64 values. If the
-        // results don't fit in these types, we start some funky string parsing.
-        if let Some((num, den)) = res.as_real() {
-            Ok(BigRational::new(num.into(), den.into()))
-        } else {
-            // we parse a string of the form "(/ num.0 denom.0)"
-            let division_expr = format!("{:?}", res);
-            if !division_expr.starts_with("(/ ") || !division_expr.ends_with(".0)") {
-                return Err(SmtEvalError::ParseError);
-            }
+        let value = model.eval_cached(self, false, || {
+            Ok(ConcreteValue::Real(eval_real(self, model)?))
+        })?;
+        match value {
+            ConcreteValue::Real(value) => Ok(value),
+            _ => unreachable!(),
+        }
+    }
+}
 
-            let mut parts = division_expr.split_ascii_whitespace();
+fn eval_real<'ctx>(
+    real: &Real<'ctx>,
+    model: &InstrumentedModel<'ctx>,
+) -> Result<BigRational, SmtEvalError> {
+    let res = model
+        .eval_ast(real, false) // TODO
+        .ok_or(SmtEvalError::EvalError)?;
 
-            let first_part = parts.next().ok_or(SmtEvalError::ParseError)?;
-            if first_part != "(/" {
-                return Err(SmtEvalError::ParseError);
-            }
+    // The .as_real() method only returns a pair of i64 values. If the
+    // results don't fit in these types, we start some funky string parsing.
+    if let Some((num, den)) = res.as_real() {
+        Ok(BigRational::new(num.into(), den.into()))
+    } else {
+        parse_real(&format!("{:?}", res))
+    }
+}
+
+/// Parse a real value printed by Z3, e.g. `(/ 1.0 3.0)` or `(- (/ 1.0 3.0))`.
+/// Like the values of the fast path in [`eval_real`], the result is in lowest
+/// terms with a positive denominator, so equal values compare equal.
+fn parse_real(printed: &str) -> Result<BigRational, SmtEvalError> {
+    // nonlinear real arithmetic can produce roots of polynomials
+    if printed.starts_with("(root-obj ") {
+        return Err(SmtEvalError::Irrational(printed.to_owned()));
+    }
+    parse_rational(printed).ok_or(SmtEvalError::ParseError)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        cmp::Ordering,
+        time::{Duration, Instant},
+    };
+
+    use num::{BigInt, BigRational};
+    use z3::{
+        ast::{Array, Ast, Bool, Datatype, Dynamic, Int, Real},
+        Config, Context, DatatypeAccessor, DatatypeBuilder, FuncDecl, FuncInterp, SatResult,
+        Solver, Sort,
+    };
+
+    use super::{
+        parse_real, ArrayValue, CompactFormatter, ConcreteValue, DatatypeValue, DefaultFormatter,
+        InstrumentedModel, ModelConsistency, ModelFormatter, OwnedModel, SmtEval, SmtEvalError,
+    };
 
-            let second_part = parts.next().ok_or(SmtEvalError::ParseError)?;
-            let second_part = second_part.replace(".0", "");
-            let numerator = BigInt::from_str(&second_part).map_err(|_| SmtEvalError::ParseError)?;
+    #[test]
+    fn test_eval_cache() {
+        let ctx = Context::new(&Config::default());
+        let x = Int::new_const(&ctx, "x");
+        let solver = Solver::new(&ctx);
+        solver.assert(&x._eq(&Int::from_i64(&ctx, 2)));
+        assert_eq!(solver.check(), SatResult::Sat);
+        let mut model =
+            InstrumentedModel::new(ModelConsistency::Consistent, solver.get_model().unwrap());
 
-            let third_part = parts.next().ok_or(SmtEvalError::ParseError)?;
-            let third_part = third_part.replace(".0)", "");
-            let denominator =
-                BigInt::from_str(&third_part).map_err(|_| SmtEvalError::ParseError)?;
+        let shared = &x * &x;
+        assert_eq!(shared.eval(&model).unwrap(), BigInt::from(4));
+        assert_eq!(model.cache_hits(), 0);
+        assert_eq!(shared.eval(&model).unwrap(), BigInt::from(4));
+        assert_eq!(model.cache_hits(), 1);
 
-            Ok(BigRational::new(numerator, denominator))
+        // cached values survive resetting the accessed decls, and cache hits
+        // still mark the accessed decls
+        model.reset_accessed();
+        assert_eq!(shared.eval(&model).unwrap(), BigInt::from(4));
+        assert_eq!(model.cache_hits(), 2);
+        assert_eq!(model.iter_unaccessed().count(), 0);
+    }
+
+    #[test]
+    fn test_accessed_array_indices() {
+        let ctx = Context::new(&Config::default());
+        let int = |value| Int::from_i64(&ctx, value);
+        let array = Array::new_const(&ctx, "a", &Sort::int(&ctx), &Sort::int(&ctx));
+        let x = Int::new_const(&ctx, "x");
+        let solver = Solver::new(&ctx);
+        for i in 1..=3 {
+            solver.assert(&array.select(&int(i))._eq(&int(10 * i).into()));
+        }
+        solver.assert(&x._eq(&int(3)));
+        assert_eq!(solver.check(), SatResult::Sat);
+        let model =
+            InstrumentedModel::new(ModelConsistency::Consistent, solver.get_model().unwrap());
+
+        for (index, expected) in [(int(1), 10), (x, 30)] {
+            let value = array.select(&index).as_int().unwrap().eval(&model);
+            assert_eq!(value.unwrap(), BigInt::from(expected));
         }
+
+        let mut indices: Vec<_> = model
+            .accessed_array_indices(&array)
+            .into_iter()
+            .map(|value| match value {
+                ConcreteValue::Int(index) => index,
+                _ => panic!("unexpected index {:?}", value),
+            })
+            .collect();
+        indices.sort();
+        assert_eq!(indices, vec![BigInt::from(1), BigInt::from(3)]);
+    }
+
+    struct UppercaseFormatter;
+
+    impl ModelFormatter for UppercaseFormatter {
+        fn format_constant(&self, name: &str, value: &Dynamic<'_>) -> String {
+            format!("{} := {}", name.to_uppercase(), value)
+        }
+
+        fn format_function(&self, name: &str, interp: &FuncInterp<'_>) -> String {
+            format!("{} := {}", name.to_uppercase(), interp)
+        }
+
+        fn format_unaccessed(&self, entries: Vec<String>) -> String {
+            format!("unaccessed: {}", entries.join(self.separator()))
+        }
+    }
+
+    #[test]
+    fn test_format_with() {
+        let ctx = Context::new(&Config::default());
+        let x = Int::new_const(&ctx, "x");
+        let y = Int::new_const(&ctx, "y");
+        let solver = Solver::new(&ctx);
+        solver.assert(&x._eq(&Int::from_i64(&ctx, 2)));
+        solver.assert(&y._eq(&Int::from_i64(&ctx, 3)));
+        assert_eq!(solver.check(), SatResult::Sat);
+        let model =
+            InstrumentedModel::new(ModelConsistency::Consistent, solver.get_model().unwrap());
+
+        assert_eq!(x.eval(&model).unwrap(), BigInt::from(2));
+        assert_eq!(
+            model.format_with(&UppercaseFormatter),
+            "X := 2\nunaccessed: Y := 3"
+        );
+        assert_eq!(model.format_with(&CompactFormatter), "x=2, (1 more)");
+    }
+
+    #[test]
+    fn test_concrete_value_sign() {
+        let int = |value: i64| ConcreteValue::Int(value.into());
+        let real = |numer: i64, denom: i64| {
+            ConcreteValue::Real(BigRational::new(numer.into(), denom.into()))
+        };
+        assert_eq!(int(5).sign(), Some(Ordering::Greater));
+        assert_eq!(int(-5).sign(), Some(Ordering::Less));
+        assert_eq!(int(0).sign(), Some(Ordering::Equal));
+        assert_eq!(real(1, 3).sign(), Some(Ordering::Greater));
+        assert_eq!(real(-1, 3).sign(), Some(Ordering::Less));
+        assert_eq!(real(1, -3).sign(), Some(Ordering::Less));
+        assert_eq!(real(0, 3).sign(), Some(Ordering::Equal));
+        let bitvec = |value: i64| ConcreteValue::BitVec {
+            value: value.into(),
+            width: 8,
+        };
+        assert_eq!(bitvec(255).sign(), Some(Ordering::Greater));
+        assert_eq!(bitvec(0).sign(), Some(Ordering::Equal));
+        assert_eq!(ConcreteValue::Bool(false).sign(), None);
+        assert_eq!(ConcreteValue::Raw("x".to_owned()).sign(), None);
+    }
+
+    #[test]
+    fn test_concrete_value_display() {
+        let int = |value: i64| ConcreteValue::Int(value.into());
+        let cases = [
+            (ConcreteValue::Bool(true), "true"),
+            (int(-3), "-3"),
+            (
+                ConcreteValue::Real(BigRational::new(1.into(), 3.into())),
+                "1/3",
+            ),
+            (
+                ConcreteValue::BitVec {
+                    value: 5.into(),
+                    width: 8,
+                },
+                "(_ bv5 8)",
+            ),
+            (ConcreteValue::String("a\"b".to_owned()), "\"a\"\"b\""),
+            (
+                ArrayValue {
+                    entries: vec![(int(1), int(10)), (int(2), int(20))],
+                    default: Box::new(int(0)),
+                }
+                .into(),
+                "[1 -> 10, 2 -> 20, else -> 0]",
+            ),
+            (
+                DatatypeValue {
+                    constructor: "pair".to_owned(),
+                    fields: vec![int(1), ConcreteValue::Bool(false)],
+                }
+                .into(),
+                "(pair 1 false)",
+            ),
+            (
+                DatatypeValue {
+                    constructor: "nil".to_owned(),
+                    fields: vec![],
+                }
+                .into(),
+                "nil",
+            ),
+            (
+                ConcreteValue::Raw("(as-array k!0)".to_owned()),
+                "(as-array k!0)",
+            ),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(value.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_eval_concrete() {
+        let ctx = Context::new(&Config::default());
+        let x = Int::new_const(&ctx, "x");
+        let solver = Solver::new(&ctx);
+        solver.assert(&x._eq(&Int::from_i64(&ctx, 7)));
+        assert_eq!(solver.check(), SatResult::Sat);
+        let model =
+            InstrumentedModel::new(ModelConsistency::Consistent, solver.get_model().unwrap());
+        assert_eq!(
+            x.eval_concrete(&model).unwrap(),
+            ConcreteValue::Int(7.into())
+        );
+        assert_eq!(
+            x.gt(&Int::from_i64(&ctx, 0)).eval_concrete(&model).unwrap(),
+            ConcreteValue::Bool(true)
+        );
+    }
+
+    #[test]
+    fn test_parse_real_canonical() {
+        // the fast path constructs the rational from Z3's numerator and
+        // denominator
+        let expected = BigRational::new((-3).into(), 6.into());
+        for printed in [
+            "(/ (- 1.0) 2.0)",
+            "(- (/ 1.0 2.0))",
+            "(/ 1.0 (- 2.0))",
+            "(/ (- 3.0) 6.0)",
+            "(- 0.5)",
+        ] {
+            let parsed = parse_real(printed).unwrap();
+            assert_eq!(parsed.numer(), expected.numer(), "{}", printed);
+            assert_eq!(parsed.denom(), expected.denom(), "{}", printed);
+        }
+        let big = format!("(/ {0}0.0 {0}.0)", "12345678901234567890");
+        assert_eq!(
+            parse_real(&big).unwrap(),
+            BigRational::from_integer(10.into())
+        );
+        assert!(matches!(
+            parse_real("(/ 1.0 0.0)"),
+            Err(SmtEvalError::ParseError)
+        ));
+        assert!(matches!(parse_real("x"), Err(SmtEvalError::ParseError)));
+    }
+
+    #[test]
+    fn test_parse_real_agrees_with_as_real() {
+        let ctx = Context::new(&Config::default());
+        let solver = Solver::new(&ctx);
+        let values = [(-3, 6), (7, 1), (22, 7), (0, 5), (-12, 1)];
+        let vars: Vec<Real> = (0..values.len())
+            .map(|i| Real::new_const(&ctx, format!("r{}", i)))
+            .collect();
+        for (var, (num, den)) in vars.iter().zip(values) {
+            solver.assert(&var._eq(&Real::from_real(&ctx, num, den)));
+        }
+        assert_eq!(solver.check(), SatResult::Sat);
+        let model =
+            InstrumentedModel::new(ModelConsistency::Consistent, solver.get_model().unwrap());
+        for (var, (num, den)) in vars.iter().zip(values) {
+            let value = model.eval_ast(var, false).unwrap();
+            let (fast_num, fast_den) = value.as_real().unwrap();
+            let fast = BigRational::new(fast_num.into(), fast_den.into());
+            let parsed = parse_real(&format!("{:?}", value)).unwrap();
+            assert_eq!(parsed, fast, "{:?}", value);
+            assert_eq!(parsed, BigRational::new(num.into(), den.into()));
+        }
+    }
+
+    #[test]
+    fn test_eval_irrational_real() {
+        let ctx = Context::new(&Config::default());
+        let x = Real::new_const(&ctx, "x");
+        let solver = Solver::new_for_logic(&ctx, "QF_NRA").unwrap();
+        solver.assert(&(&x * &x)._eq(&Real::from_real(&ctx, 2, 1)));
+        solver.assert(&x.gt(&Real::from_real(&ctx, 0, 1)));
+        assert_eq!(solver.check(), SatResult::Sat);
+        let model =
+            InstrumentedModel::new(ModelConsistency::Consistent, solver.get_model().unwrap());
+        match x.eval(&model) {
+            Err(SmtEvalError::Irrational(root)) => assert!(root.starts_with("(root-obj")),
+            res => panic!("expected an irrational value, got {:?}", res),
+        }
+        let half = Real::from_real(&ctx, 1, 2);
+        assert_eq!(
+            half.eval(&model).unwrap(),
+            BigRational::new(1.into(), 2.into())
+        );
+    }
+
+    #[test]
+    fn test_eval_ast_with_deadline() {
+        let ctx = Context::new(&Config::default());
+        let mut sum = Int::from_i64(&ctx, 0);
+        for i in 0..1000 {
+            sum = sum + Int::new_const(&ctx, format!("x{}", i));
+        }
+        let solver = Solver::new(&ctx);
+        assert_eq!(solver.check(), SatResult::Sat);
+        let model =
+            InstrumentedModel::new(ModelConsistency::Consistent, solver.get_model().unwrap());
+
+        let res = model.eval_ast_with_deadline(&sum, true, Instant::now());
+        assert!(matches!(res, Err(SmtEvalError::Timeout)));
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        assert!(model.eval_ast_with_deadline(&sum, true, deadline).is_ok());
+    }
+
+    #[test]
+    fn test_eval_datatype_field() {
+        let ctx = Context::new(&Config::default());
+        let datatype = DatatypeBuilder::new(&ctx, "AB")
+            .variant(
+                "A",
+                vec![("a_val", DatatypeAccessor::Sort(Sort::int(&ctx)))],
+            )
+            .variant(
+                "B",
+                vec![("b_val", DatatypeAccessor::Sort(Sort::bool(&ctx)))],
+            )
+            .finish();
+        let [variant_a, variant_b] = datatype.variants.as_slice() else {
+            unreachable!()
+        };
+
+        let x = Datatype::new_const(&ctx, "x", &datatype.sort);
+        let a_five = variant_a.constructor.apply(&[&Int::from_i64(&ctx, 5)]);
+        let solver = Solver::new(&ctx);
+        solver.assert(&x._eq(&a_five.as_datatype().unwrap()));
+        assert_eq!(solver.check(), SatResult::Sat);
+        let model =
+            InstrumentedModel::new(ModelConsistency::Consistent, solver.get_model().unwrap());
+
+        let field = model.eval_datatype_field(&x, variant_a, 0).unwrap();
+        assert_eq!(field.as_int().unwrap().as_i64(), Some(5));
+
+        let res = model.eval_datatype_field(&x, variant_b, 0);
+        assert!(matches!(
+            res,
+            Err(SmtEvalError::WrongConstructor { accessor, constructor })
+                if accessor == "b_val" && constructor == "A"
+        ));
+
+        let res = model.eval_datatype_field(&x, variant_a, 1);
+        assert!(matches!(
+            res,
+            Err(SmtEvalError::NoSuchField { field: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_to_owned_model() {
+        let ctx = Context::new(&Config::default());
+        let x = Int::new_const(&ctx, "x");
+        let y = Int::new_const(&ctx, "y");
+        let solver = Solver::new(&ctx);
+        solver.assert(&x._eq(&Int::from_i64(&ctx, 3)));
+        solver.assert(&y._eq(&Int::from_i64(&ctx, -4)));
+        assert_eq!(solver.check(), SatResult::Sat);
+
+        let owned = {
+            let model =
+                InstrumentedModel::new(ModelConsistency::Consistent, solver.get_model().unwrap());
+            model.to_owned_model()
+        };
+
+        fn assert_send<T: Send + 'static>(value: T) -> T {
+            value
+        }
+        let owned: OwnedModel = std::thread::spawn(move || assert_send(owned))
+            .join()
+            .unwrap();
+        assert_eq!(owned.consistency(), ModelConsistency::Consistent);
+        assert_eq!(owned.get("x"), Some(&ConcreteValue::Int(3.into())));
+        assert_eq!(owned.get("y"), Some(&ConcreteValue::Int((-4).into())));
+        assert_eq!(owned.get("z"), None);
+        assert_eq!(owned.to_string(), "x -> 3\ny -> -4\n");
+    }
+
+    #[test]
+    fn test_try_assignments() {
+        let ctx = Context::new(&Config::default());
+        let x = Int::new_const(&ctx, "x");
+        let big = Int::new_const(&ctx, "big");
+        let solver = Solver::new(&ctx);
+        solver.assert(&x._eq(&Int::from_i64(&ctx, 7)));
+        // does not fit into an i64
+        solver.assert(&big._eq(&Int::from_str(&ctx, "-100000000000000000000000").unwrap()));
+        assert_eq!(solver.check(), SatResult::Sat);
+        let model =
+            InstrumentedModel::new(ModelConsistency::Consistent, solver.get_model().unwrap());
+
+        let unassigned = Bool::new_const(&ctx, "unassigned");
+        let expected = unassigned.eval(&model);
+        let (values, errors) = model.try_assignments(&[
+            Dynamic::from_ast(&x),
+            Dynamic::from_ast(&big),
+            Dynamic::from_ast(&unassigned),
+        ]);
+        assert!(errors.is_empty());
+        assert_eq!(values.get("x"), Some(&ConcreteValue::Int(7.into())));
+        let big_value: BigInt = "-100000000000000000000000".parse().unwrap();
+        assert_eq!(
+            values.get("big"),
+            Some(&ConcreteValue::Int(big_value.clone()))
+        );
+        assert_eq!(big.eval(&model).unwrap(), big_value);
+
+        // values with model completion do not leak into evaluations without
+        let model =
+            InstrumentedModel::new(ModelConsistency::Consistent, solver.get_model().unwrap());
+        model.try_assignments(&[Dynamic::from_ast(&unassigned)]);
+        assert_eq!(unassigned.eval(&model).unwrap(), expected.unwrap());
+    }
+
+    #[test]
+    fn test_to_sorted_string() {
+        let ctx = Context::new(&Config::default());
+        let int_sort = Sort::int(&ctx);
+        let f = FuncDecl::new(&ctx, "f", &[&int_sort], &int_sort);
+        let solver = Solver::new(&ctx);
+        for (name, value) in [("c", 3), ("a", 1), ("d", 4), ("b", 2)] {
+            solver.assert(&Int::new_const(&ctx, name)._eq(&Int::from_i64(&ctx, value)));
+        }
+        let one = Int::from_i64(&ctx, 1);
+        solver.assert(
+            &f.apply(&[&one])
+                .as_int()
+                .unwrap()
+                ._eq(&Int::from_i64(&ctx, 5)),
+        );
+        assert_eq!(solver.check(), SatResult::Sat);
+        let model =
+            InstrumentedModel::new(ModelConsistency::Consistent, solver.get_model().unwrap());
+
+        let rendered = model.to_sorted_string();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(&lines[..4], ["a -> 1", "b -> 2", "c -> 3", "d -> 4"]);
+        assert!(lines[4].starts_with("f -> "));
+        assert_eq!(model.iter_unaccessed().count(), 5);
+
+        let mut buf = Vec::new();
+        model.write_sorted_to(&mut buf).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), rendered);
+    }
+
+    #[test]
+    fn test_func_interp_entries() {
+        let ctx = Context::new(&Config::default());
+        let int_sort = Sort::int(&ctx);
+        let f = FuncDecl::new(&ctx, "f", &[&int_sort, &int_sort], &int_sort);
+        let solver = Solver::new(&ctx);
+        let int = |value| Int::from_i64(&ctx, value);
+        for (x, y, value) in [(1, 2, 10), (3, 4, 20)] {
+            let app = f.apply(&[&int(x), &int(y)]).as_int().unwrap();
+            solver.assert(&app._eq(&int(value)));
+        }
+        assert_eq!(solver.check(), SatResult::Sat);
+        let model =
+            InstrumentedModel::new(ModelConsistency::Consistent, solver.get_model().unwrap());
+
+        let mut entries: Vec<_> = model.func_interp_entries(&f).collect();
+        entries.sort_by_key(|(_, value)| value.to_string());
+        let int = |value: i64| ConcreteValue::Int(value.into());
+        assert_eq!(
+            entries,
+            vec![
+                (vec![int(1), int(2)], int(10)),
+                (vec![int(3), int(4)], int(20)),
+            ]
+        );
+        assert!(matches!(
+            model.func_interp_else(&f),
+            Some(ConcreteValue::Int(_))
+        ));
+        assert_eq!(model.iter_unaccessed().count(), 0);
+    }
+
+    #[test]
+    fn test_write_to() {
+        let ctx = Context::new(&Config::default());
+        let solver = Solver::new(&ctx);
+        for (name, value) in [("x", 3), ("y", -4)] {
+            solver.assert(&Int::new_const(&ctx, name)._eq(&Int::from_i64(&ctx, value)));
+        }
+        assert_eq!(solver.check(), SatResult::Sat);
+        let model =
+            InstrumentedModel::new(ModelConsistency::Consistent, solver.get_model().unwrap());
+
+        let mut buf = Vec::new();
+        model.write_to(&mut buf).unwrap();
+        let written = String::from_utf8(buf).unwrap();
+        assert_eq!(written.lines().count(), 2);
+        assert!(written.contains("y -> (- 4)\n"));
+
+        let mut buf = Vec::new();
+        model.write_with(&DefaultFormatter, &mut buf).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            model.format_with(&DefaultFormatter)
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let ctx = Context::new(&Config::default());
+        let int_sort = Sort::int(&ctx);
+        let f = FuncDecl::new(&ctx, "f", &[&int_sort], &int_sort);
+        let solver = Solver::new(&ctx);
+        solver.assert(&Int::new_const(&ctx, "x")._eq(&Int::from_i64(&ctx, 3)));
+        for (arg, value) in [(1, 5), (2, 7)] {
+            let arg = Int::from_i64(&ctx, arg);
+            solver.assert(
+                &f.apply(&[&arg])
+                    .as_int()
+                    .unwrap()
+                    ._eq(&Int::from_i64(&ctx, value)),
+            );
+        }
+        assert_eq!(solver.check(), SatResult::Sat);
+        let expected = solver.get_model().unwrap().to_string();
+        let model =
+            InstrumentedModel::new(ModelConsistency::Consistent, solver.get_model().unwrap());
+
+        // Z3's format prints function interpretations as `{ ... }` blocks
+        assert_eq!(model.to_string(), expected);
+        assert!(expected.contains("x -> 3\n"));
+        assert!(expected.contains("f -> {\n"));
+        assert_eq!(model.iter_unaccessed().count(), 2);
     }
 }
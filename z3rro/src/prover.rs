@@ -1,27 +1,34 @@
 //! Not a SAT solver, but a prover. There's a difference.
 use itertools::Itertools;
+use once_cell::sync::Lazy;
 use thiserror::Error;
 
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, HashSet, VecDeque},
     fmt::Display,
-    io::{Seek, SeekFrom, Write},
-    path::Path,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
     process::{Command, Output},
-    time::Duration,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
 };
 
 use tempfile::NamedTempFile;
 
 use z3::{
     ast::{forall_const, Ast, Bool, Dynamic},
-    Context, SatResult, Solver, Statistics,
+    Config, Context, DeclKind, Goal, Params, Pattern, SatResult, Solver, Statistics, Tactic,
 };
 
 use crate::{
-    model::{InstrumentedModel, ModelConsistency},
-    smtlib::Smtlib,
+    model::{ConcreteValue, DefaultFormatter, InstrumentedModel, ModelConsistency, OwnedModel},
+    smtlib::{Assertion, SExpr, Smtlib},
     util::{set_solver_timeout, ReasonUnknown},
+    z3_version,
 };
 
 #[derive(Debug, Error, PartialEq)]
@@ -32,6 +39,36 @@ pub enum ProverCommandError {
     ParseError,
     #[error("Unexpected result from prover: {0}")]
     UnexpectedResultError(String),
+    #[error("Tactic failed: {0}")]
+    TacticError(String),
+    #[error("Unknown tactic: {0}")]
+    UnknownTactic(String),
+    #[error("Malformed SMT-LIB input: {0}")]
+    MalformedInput(MalformedSmtlib),
+    #[error("Mock prover has no more results")]
+    MockExhausted,
+    #[error("Exists-forall provers must be checked with check_sat")]
+    ExistsForallProof,
+    #[error("Invalid prover configuration: {0}")]
+    InvalidConfiguration(String),
+}
+
+/// The SMT-LIB input for an external solver does not have balanced
+/// parentheses.
+#[derive(Debug, Error, PartialEq)]
+pub enum MalformedSmtlib {
+    #[error("unmatched closing parenthesis at character {0}")]
+    UnmatchedClose(usize),
+    #[error("{0} unclosed parentheses at end of input")]
+    Unclosed(usize),
+}
+
+/// The solver executable of a prover cannot be run, see [`Prover::try_new`].
+#[derive(Debug, Error, PartialEq)]
+#[error("solver {} is unavailable: {reason}", .executable.display())]
+pub struct BackendUnavailable {
+    pub executable: PathBuf,
+    pub reason: String,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -43,6 +80,55 @@ pub enum SolverType {
     YICES,
 }
 
+/// The solver backend of a [`Prover`] together with its options. A
+/// [`SolverType`] converts into the configuration with default options, so
+/// it can be passed to [`Prover::new`] directly.
+#[derive(Debug, PartialEq, Clone)]
+pub enum SolverConfig {
+    /// The internal Z3. With a tactic, the solver is created from the tactic
+    /// like in [`Prover::with_tactic`].
+    Z3 {
+        tactic: Option<String>,
+    },
+    /// Z3 run as an external process.
+    ExternalZ3,
+    /// SwInE, run from `binary` if it is not found on the `PATH`.
+    Swine {
+        binary: Option<PathBuf>,
+    },
+    /// cvc5, with additional command line arguments.
+    Cvc5 {
+        extra_args: Vec<String>,
+    },
+    Yices,
+}
+
+impl SolverConfig {
+    pub fn solver_type(&self) -> SolverType {
+        match self {
+            SolverConfig::Z3 { .. } => SolverType::InternalZ3,
+            SolverConfig::ExternalZ3 => SolverType::ExternalZ3,
+            SolverConfig::Swine { .. } => SolverType::SWINE,
+            SolverConfig::Cvc5 { .. } => SolverType::CVC5,
+            SolverConfig::Yices => SolverType::YICES,
+        }
+    }
+}
+
+impl From<SolverType> for SolverConfig {
+    fn from(solver_type: SolverType) -> Self {
+        match solver_type {
+            SolverType::InternalZ3 => SolverConfig::Z3 { tactic: None },
+            SolverType::ExternalZ3 => SolverConfig::ExternalZ3,
+            SolverType::SWINE => SolverConfig::Swine { binary: None },
+            SolverType::CVC5 => SolverConfig::Cvc5 {
+                extra_args: Vec::new(),
+            },
+            SolverType::YICES => SolverConfig::Yices,
+        }
+    }
+}
+
 /// The result of a prove query.
 #[derive(Debug)]
 pub enum ProveResult {
@@ -51,6 +137,74 @@ pub enum ProveResult {
     Unknown(ReasonUnknown),
 }
 
+impl ProveResult {
+    /// A verbose description of this result for logs. For a
+    /// [`ProveResult::Counterexample`], the given model is included using the
+    /// [`DefaultFormatter`], so declarations that were not accessed come last.
+    /// The terse [`Display`] implementation is used otherwise.
+    pub fn describe(&self, model: Option<&InstrumentedModel<'_>>) -> String {
+        match (self, model) {
+            (ProveResult::Counterexample, Some(model)) => {
+                let formatted = model.format_with(&DefaultFormatter);
+                if formatted.is_empty() {
+                    "Counterexample (empty model)".to_owned()
+                } else {
+                    format!("Counterexample:\n{}", formatted)
+                }
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
+/// Combine the results of several proof obligations: [`ProveResult::Proof`]
+/// if all are proven, otherwise the first [`ProveResult::Counterexample`], or
+/// the first [`ProveResult::Unknown`] if there is no counterexample. The
+/// results after the first counterexample are not consumed, so checks run
+/// lazily by the iterator are skipped then.
+pub fn combine_results(results: impl IntoIterator<Item = ProveResult>) -> ProveResult {
+    let mut unknown = None;
+    for res in results {
+        match res {
+            ProveResult::Proof => {}
+            ProveResult::Counterexample => return ProveResult::Counterexample,
+            ProveResult::Unknown(reason) => {
+                unknown.get_or_insert(reason);
+            }
+        }
+    }
+    match unknown {
+        Some(reason) => ProveResult::Unknown(reason),
+        None => ProveResult::Proof,
+    }
+}
+
+/// The result of [`Prover::cross_check`].
+#[derive(Debug)]
+pub enum CrossCheckResult {
+    /// The results do not conflict. This is the conclusive result if there is
+    /// one, and Z3's result otherwise.
+    Agree(ProveResult),
+    /// One backend proved the provables and the other one found a
+    /// counterexample.
+    Disagree { z3: ProveResult, swine: ProveResult },
+}
+
+/// Compare the results of Z3 and SwInE for [`Prover::cross_check`]. An
+/// unknown result does not conflict with any other result.
+fn compare_results(z3: ProveResult, swine: ProveResult) -> CrossCheckResult {
+    match (&z3, &swine) {
+        (ProveResult::Proof, ProveResult::Counterexample)
+        | (ProveResult::Counterexample, ProveResult::Proof) => {
+            CrossCheckResult::Disagree { z3, swine }
+        }
+        (ProveResult::Unknown(_), ProveResult::Proof | ProveResult::Counterexample) => {
+            CrossCheckResult::Agree(swine)
+        }
+        _ => CrossCheckResult::Agree(z3),
+    }
+}
+
 /// If z3 is used as the SMT solver, it is not necessary to store
 /// a counterexample (for Sat) or reason (for Unknown), since the
 /// Z3 solver already retains this information internally.
@@ -76,13 +230,58 @@ impl SolverResult<'_> {
     }
 }
 
+/// The name of the executable of an external solver.
+fn default_executable(solver: &SolverType) -> &'static str {
+    match solver {
+        SolverType::InternalZ3 | SolverType::ExternalZ3 => "z3",
+        SolverType::SWINE => "swine",
+        SolverType::CVC5 => "cvc5",
+        SolverType::YICES => "yices-smt2",
+    }
+}
+
+/// Check that the executable of an external solver can be started by running
+/// it with `--version`. The internal Z3 is always available.
+fn check_backend(solver: &SolverType, executable: Option<&Path>) -> Result<(), BackendUnavailable> {
+    if *solver == SolverType::InternalZ3 {
+        return Ok(());
+    }
+    let executable = executable.unwrap_or_else(|| Path::new(default_executable(solver)));
+    let unavailable = |reason: String| BackendUnavailable {
+        executable: executable.to_owned(),
+        reason,
+    };
+    let output = Command::new(executable)
+        .arg("--version")
+        .output()
+        .map_err(|err| unavailable(err.to_string()))?;
+    if !output.status.success() {
+        return Err(unavailable(format!(
+            "--version exited with {}",
+            output.status
+        )));
+    }
+    Ok(())
+}
+
+/// The output of `--version` of each external solver executable that was
+/// queried so far.
+static EXTERNAL_VERSIONS: Lazy<Mutex<HashMap<PathBuf, String>>> = Lazy::new(Default::default);
+
+/// Run an external solver on the SMT-LIB file at `file_path`. The process is
+/// waited for before returning, so a [`Prover`] never owns a running solver
+/// process and there is nothing to kill when it is dropped. The input file is
+/// owned by the caller.
 fn call_solver(
     file_path: &Path,
     solver: SolverType,
+    executable: Option<&Path>,
+    extra_args: &[String],
     timeout: Option<Duration>,
     sat_result: Option<SatResult>,
 ) -> Result<Output, std::io::Error> {
-    let (solver, args) = match solver {
+    let program = executable.unwrap_or_else(|| Path::new(default_executable(&solver)));
+    let args = match solver {
         SolverType::InternalZ3 => {
             unreachable!("The function 'call_solver' should never be called for z3");
         }
@@ -99,7 +298,7 @@ fn call_solver(
                 args.push(format!("-t:{}", t.as_millis()));
             }
 
-            ("z3", args)
+            args
         }
         SolverType::SWINE => {
             let args: Vec<String> = match sat_result {
@@ -109,7 +308,7 @@ fn call_solver(
                 _ => vec!["--no-version".to_string()],
             };
 
-            ("swine", args)
+            args
         }
         SolverType::CVC5 => {
             let mut args: Vec<String> = match sat_result {
@@ -124,7 +323,7 @@ fn call_solver(
                 args.push(format!("--tlimit={}", t.as_millis()));
             }
 
-            ("cvc5", args)
+            args
         }
         SolverType::YICES => {
             let mut args: Vec<String> = match sat_result {
@@ -145,18 +344,42 @@ fn call_solver(
                 }
             }
 
-            ("yices-smt2", args)
+            args
         }
     };
 
-    Command::new(solver).args(&args).arg(file_path).output()
+    Command::new(program)
+        .args(&args)
+        .args(extra_args)
+        .arg(file_path)
+        .output()
+}
+
+/// Whether the SMT-LIB `input` declares a function with arguments, which
+/// requires a logic with uninterpreted functions. Declarations of constants
+/// like `(declare-fun x () Int)` do not count.
+fn declares_functions(input: &str) -> bool {
+    input.split("(declare-fun ").skip(1).any(|decl| {
+        let rest = match decl.strip_prefix('|') {
+            Some(quoted) => quoted.split_once('|').map_or("", |(_, rest)| rest),
+            None => decl.trim_start_matches(|c: char| !c.is_whitespace() && c != '('),
+        };
+        !rest.trim_start().starts_with("()")
+    })
 }
 
 /// To execute the SMT solver correctly, specific modifications to the input are required:
 /// 1) For SwInE, remove lines that contain a `forall` quantifier or the declaration of the exponential function (`exp``).
 /// 2) For other solvers, add a line to set logic, and remove incorrect assertions such as `(assert add)`.
 /// 3) For solvers that do not support at-most, convert those assertions into equivalent logic.
-fn transform_input_lines(input: &str, solver: SolverType, timeout: Option<Duration>) -> String {
+///
+/// Returns an error if the input's parentheses are not balanced, since the
+/// blocks could not be split correctly then.
+fn transform_input_lines(
+    input: &str,
+    solver: SolverType,
+    timeout: Option<Duration>,
+) -> Result<String, MalformedSmtlib> {
     let timeout_option = if let Some(t) = timeout {
         match solver {
             SolverType::InternalZ3 => {
@@ -173,14 +396,16 @@ fn transform_input_lines(input: &str, solver: SolverType, timeout: Option<Durati
 
     let mut output = match solver {
         SolverType::CVC5 | SolverType::YICES => {
-            let mut output = String::new();
-            let logic = if input.contains("*") || input.contains("/") {
-                "(set-logic QF_NIRA)\n"
-            } else {
-                "(set-logic QF_LIRA)\n"
-            };
-            output.push_str(logic);
-            output
+            let nonlinear = input.contains("*") || input.contains("/");
+            // quantifiers, e.g. with their patterns, are rejected by
+            // quantifier-free logics
+            let quantified = input.contains("(forall") || input.contains("(exists");
+            format!(
+                "(set-logic {}{}{})\n",
+                if quantified { "" } else { "QF_" },
+                if declares_functions(input) { "UF" } else { "" },
+                if nonlinear { "NIRA" } else { "LIRA" }
+            )
         }
         _ => String::new(),
     };
@@ -193,6 +418,14 @@ fn transform_input_lines(input: &str, solver: SolverType, timeout: Option<Durati
         let mut tmp_buffer: VecDeque<char> = VecDeque::new();
         let mut input_buffer: VecDeque<char> = input.chars().collect();
         let mut cnt = 0;
+        let mut pos = 0;
+        // comments and string literals or quoted symbols may contain
+        // parentheses which must not be counted
+        let mut in_comment = false;
+        let mut quote = None;
+        // the start of the current command in tmp_buffer, after any comments
+        // preceding it
+        let mut cmd_start = 0;
 
         let condition = |tmp: &str| match solver {
             SolverType::SWINE => !tmp.contains("declare-fun exp") && !tmp.contains("forall"),
@@ -201,18 +434,38 @@ fn transform_input_lines(input: &str, solver: SolverType, timeout: Option<Durati
 
         // Collect characters until all opened parentheses are closed, and
         // keep this block if it does not contain 'declare-fun exp' or 'forall'.
+        // Comments before a command are kept or removed together with it.
         while let Some(c) = input_buffer.pop_front() {
             tmp_buffer.push_back(c);
+            pos += 1;
+            if in_comment {
+                in_comment = c != '\n';
+                continue;
+            }
+            if let Some(q) = quote {
+                if c == q {
+                    quote = None;
+                }
+                continue;
+            }
             match c {
+                ';' => in_comment = true,
+                '|' | '"' => quote = Some(c),
                 '(' => {
+                    if cnt == 0 {
+                        cmd_start = tmp_buffer.len() - 1;
+                    }
                     cnt += 1;
                 }
                 ')' => {
+                    if cnt == 0 {
+                        return Err(MalformedSmtlib::UnmatchedClose(pos - 1));
+                    }
                     cnt -= 1;
                     if cnt == 0 {
-                        let tmp: String = tmp_buffer.iter().collect();
-                        if condition(&tmp) {
-                            output.push_str(&tmp);
+                        let cmd: String = tmp_buffer.iter().skip(cmd_start).collect();
+                        if condition(&cmd) {
+                            output.extend(tmp_buffer.iter());
                         }
                         tmp_buffer.clear();
                     }
@@ -220,9 +473,12 @@ fn transform_input_lines(input: &str, solver: SolverType, timeout: Option<Durati
                 _ => {}
             }
         }
+        if cnt != 0 {
+            return Err(MalformedSmtlib::Unclosed(cnt));
+        }
     }
 
-    output
+    Ok(output)
 }
 
 impl Display for ProveResult {
@@ -287,29 +543,392 @@ pub struct Prover<'ctx> {
     level: usize,
     /// The minimum level where an assertion was added to the solver.
     min_level_with_provables: Option<usize>,
+    /// Number of assumptions and provables added at each level.
+    assertion_counts: Vec<AssertionCounts>,
     /// SMT solver type
     smt_solver: SolverType,
     /// Cached information about the last SAT/proof check call.
     last_result: Option<LastSatSolverResult<'ctx>>,
+    /// The SMT-LIB of the current assertions for external solvers. It is reset
+    /// any time the assertions on the solver are modified.
+    base_smtlib: Option<Smtlib>,
+    /// Number of times the SMT-LIB for external solvers was generated.
+    smtlib_generations: usize,
+    /// Source locations of assertions together with the level they were added
+    /// on, printed as comments by [`Prover::get_smtlib`].
+    locations: Vec<(usize, Bool<'ctx>, String)>,
+    /// Assumptions added by [`Prover::add_named_assumption`] together with
+    /// the level they were added on.
+    named_assumptions: Vec<(usize, String, Bool<'ctx>)>,
+    /// Results returned by the proof checks of a mock prover, see
+    /// [`Prover::new_mock`].
+    mock_results: Option<VecDeque<ProveResult>>,
+    /// Whether this prover was created by [`Prover::to_exists_forall`] and
+    /// must be used as a solver.
+    exists_forall: bool,
+    /// The unsat core reported by an external solver in the last call of
+    /// [`Prover::check_proof_assuming_core`].
+    external_unsat_core: Vec<Bool<'ctx>>,
+    /// Where the SMT-LIB of every query is written to, see
+    /// [`Prover::set_query_log`].
+    query_log: Option<QueryLog>,
+    /// The global deadline for all checks, see [`Prover::set_deadline`].
+    deadline: Option<Instant>,
+    /// The minimum level where an assertion was added that simplifies to
+    /// `false`, i.e. a provable that simplifies to `true` or an assumption
+    /// that simplifies to `false`. Proof checks succeed trivially then.
+    min_level_trivially_unsat: Option<usize>,
+    /// The executable of the external solver if it is not found on the
+    /// `PATH`, see [`ProverBuilder::swine_path`].
+    solver_executable: Option<PathBuf>,
+    /// Additional command line arguments of the external solver, see
+    /// [`SolverConfig::Cvc5`].
+    extra_args: Vec<String>,
+    /// The directory where the SMT-LIB of queries with an unknown result is
+    /// written to, see [`Prover::set_dump_on_unknown`].
+    dump_on_unknown: Option<PathBuf>,
+    /// Number of queries written to `dump_on_unknown` so far.
+    unknown_dumps: usize,
+    /// Number of queries sent to a solver, see [`Prover::check_count`].
+    check_count: usize,
+    /// Whether the last proof check succeeded only because there were no
+    /// provables, see [`Prover::last_proof_was_vacuous`].
+    last_proof_vacuous: bool,
+    /// Whether all provables simplified to `true` in the last proof check,
+    /// see [`Prover::all_provables_trivial`].
+    last_proof_trivial: bool,
+    /// Levels pushed by [`Prover::push_labeled`].
+    labeled_frames: Vec<LabeledFrame<'ctx>>,
+}
+
+/// When the buffered query log of a [`Prover`] is flushed, see
+/// [`Prover::set_query_log_flush`]. In all cases, the buffer is flushed
+/// whenever it is full.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// Flush after each query.
+    EveryQuery,
+    /// Never flush explicitly. Queries still in the buffer when the prover
+    /// is dropped are lost.
+    Never,
+    /// Flush when the prover is dropped.
+    #[default]
+    OnDrop,
+}
+
+struct QueryLog {
+    writer: Option<BufWriter<Box<dyn Write>>>,
+    flush: FlushPolicy,
+    queries: usize,
+}
+
+impl QueryLog {
+    fn write_query(&mut self, query: &str) -> std::io::Result<()> {
+        let writer = self.writer.as_mut().unwrap();
+        self.queries += 1;
+        writeln!(writer, "; query {}\n{}", self.queries, query)?;
+        if self.flush == FlushPolicy::EveryQuery {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for QueryLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueryLog")
+            .field("flush", &self.flush)
+            .field("queries", &self.queries)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for QueryLog {
+    fn drop(&mut self) {
+        let writer = self.writer.take().unwrap();
+        if self.flush == FlushPolicy::Never {
+            // dropping the writer itself would flush the buffer
+            let _ = writer.into_parts();
+        } else if let Err(err) = writer.into_inner() {
+            tracing::warn!("failed to flush the query log: {}", err.error());
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct AssertionCounts {
+    assumptions: usize,
+    provables: usize,
+    /// Provables that simplify to `true`, see
+    /// [`Prover::all_provables_trivial`].
+    trivial_provables: usize,
+    /// Assertions added to the solver, see [`Prover::debug_assert_tracking`].
+    assertions: usize,
+}
+
+/// A level pushed by [`Prover::push_labeled`].
+#[derive(Debug)]
+struct LabeledFrame<'ctx> {
+    level: usize,
+    label: String,
+    /// The literal which tracks the assertions on the level.
+    tracker: Bool<'ctx>,
+    /// Whether an assertion was added on the level, i.e. whether the solver
+    /// knows the literal.
+    has_assertions: bool,
+}
+
+/// Pops the prover back to the level it had when the guard was created once
+/// the guard is dropped, including during unwinding.
+struct ScopeGuard<'a, 'ctx> {
+    prover: &'a mut Prover<'ctx>,
+    level: usize,
+}
+
+impl<'a, 'ctx> ScopeGuard<'a, 'ctx> {
+    fn new(prover: &'a mut Prover<'ctx>) -> Self {
+        let level = prover.level;
+        prover.push();
+        ScopeGuard { prover, level }
+    }
+}
+
+impl<'ctx> Deref for ScopeGuard<'_, 'ctx> {
+    type Target = Prover<'ctx>;
+
+    fn deref(&self) -> &Self::Target {
+        self.prover
+    }
+}
+
+impl DerefMut for ScopeGuard<'_, '_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.prover
+    }
+}
+
+impl Drop for ScopeGuard<'_, '_> {
+    fn drop(&mut self) {
+        while self.prover.level > self.level {
+            self.prover.pop();
+        }
+    }
+}
+
+/// Configuration of a [`Prover`] with chainable methods, as an alternative to
+/// calling the setters after [`Prover::new`]. Options that the chosen solver
+/// does not support are rejected by [`ProverBuilder::build`].
+pub struct ProverBuilder {
+    solver_type: SolverType,
+    timeout: Option<Duration>,
+    random_seed: Option<u32>,
+    logic: Option<String>,
+    swine_path: Option<PathBuf>,
+    dump_on_unknown: Option<PathBuf>,
+    query_log: Option<Box<dyn Write>>,
+}
+
+impl Default for ProverBuilder {
+    fn default() -> Self {
+        ProverBuilder {
+            solver_type: SolverType::InternalZ3,
+            timeout: None,
+            random_seed: None,
+            logic: None,
+            swine_path: None,
+            dump_on_unknown: None,
+            query_log: None,
+        }
+    }
+}
+
+impl ProverBuilder {
+    /// A builder for a prover using the internal Z3 without further options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// See [`Prover::set_timeout`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// See [`Prover::set_random_seed`]. Only supported by the internal Z3.
+    pub fn random_seed(mut self, seed: u32) -> Self {
+        self.random_seed = Some(seed);
+        self
+    }
+
+    /// Create the solver for the given SMT-LIB logic, e.g. `QF_LIA`. Only
+    /// supported by the internal Z3.
+    pub fn logic(mut self, logic: &str) -> Self {
+        self.logic = Some(logic.to_owned());
+        self
+    }
+
+    pub fn solver_type(mut self, solver_type: SolverType) -> Self {
+        self.solver_type = solver_type;
+        self
+    }
+
+    /// The path of the SwInE executable if it is not found on the `PATH`.
+    /// Requires [`SolverType::SWINE`].
+    pub fn swine_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.swine_path = Some(path.into());
+        self
+    }
+
+    /// See [`Prover::set_dump_on_unknown`].
+    pub fn dump_on_unknown(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dump_on_unknown = Some(dir.into());
+        self
+    }
+
+    /// See [`Prover::set_query_log`].
+    pub fn query_log(mut self, writer: impl Write + 'static) -> Self {
+        self.query_log = Some(Box::new(writer));
+        self
+    }
+
+    /// Create the configured prover. It uses [`IncrementalMode::Native`].
+    /// Fails with [`ProverCommandError::InvalidConfiguration`] if an option
+    /// is not supported by the solver type or Z3 does not know the logic.
+    pub fn build(self, ctx: &Context) -> Result<Prover<'_>, ProverCommandError> {
+        let invalid = |msg: &str| Err(ProverCommandError::InvalidConfiguration(msg.to_owned()));
+        let internal = self.solver_type == SolverType::InternalZ3;
+        if self.random_seed.is_some() && !internal {
+            return invalid("random seeds require the internal Z3");
+        }
+        if self.logic.is_some() && !internal {
+            return invalid("logics require the internal Z3");
+        }
+        if self.swine_path.is_some() && self.solver_type != SolverType::SWINE {
+            return invalid("a SwInE path requires the SwInE solver");
+        }
+
+        let mut prover = Prover::new(ctx, IncrementalMode::Native, self.solver_type);
+        if let Some(logic) = &self.logic {
+            match Solver::new_for_logic(ctx, logic.as_str()) {
+                Some(solver) => prover.solver = StackSolver::Native(solver),
+                None => return invalid(&format!("unknown logic {}", logic)),
+            }
+        }
+        if let Some(timeout) = self.timeout {
+            prover.set_timeout(timeout);
+        }
+        if let Some(seed) = self.random_seed {
+            prover.set_random_seed(seed);
+        }
+        prover.solver_executable = self.swine_path;
+        prover.dump_on_unknown = self.dump_on_unknown;
+        if let Some(writer) = self.query_log {
+            prover.set_query_log(writer);
+        }
+        Ok(prover)
+    }
 }
 
 impl<'ctx> Prover<'ctx> {
     /// Create a new prover with the given [`Context`] and [`IncrementalMode`].
-    pub fn new(ctx: &'ctx Context, mode: IncrementalMode, solver_type: SolverType) -> Self {
+    ///
+    /// The `solver` is a [`SolverType`] or a [`SolverConfig`] with options for
+    /// the backend. Panics if the configuration has a tactic which Z3 does
+    /// not know, see [`Self::with_tactic`] for a fallible alternative.
+    pub fn new(ctx: &'ctx Context, mode: IncrementalMode, solver: impl Into<SolverConfig>) -> Self {
+        let config = solver.into();
+        let solver_type = config.solver_type();
+        let (z3_solver, solver_executable, extra_args) = match config {
+            SolverConfig::Z3 {
+                tactic: Some(tactic),
+            } => (Tactic::new(ctx, &tactic).solver(), None, Vec::new()),
+            SolverConfig::Swine { binary } => (Solver::new(ctx), binary, Vec::new()),
+            SolverConfig::Cvc5 { extra_args } => (Solver::new(ctx), None, extra_args),
+            _ => (Solver::new(ctx), None, Vec::new()),
+        };
         Prover {
             ctx,
             timeout: None,
             solver: match mode {
-                IncrementalMode::Native => StackSolver::Native(Solver::new(ctx)),
-                IncrementalMode::Emulated => {
-                    StackSolver::Emulated(Solver::new(ctx), vec![Vec::new()])
-                }
+                IncrementalMode::Native => StackSolver::Native(z3_solver),
+                IncrementalMode::Emulated => StackSolver::Emulated(z3_solver, vec![Vec::new()]),
             },
             level: 0,
             min_level_with_provables: None,
+            assertion_counts: vec![AssertionCounts::default()],
             smt_solver: solver_type,
             last_result: None,
+            base_smtlib: None,
+            smtlib_generations: 0,
+            locations: Vec::new(),
+            named_assumptions: Vec::new(),
+            mock_results: None,
+            exists_forall: false,
+            external_unsat_core: Vec::new(),
+            query_log: None,
+            deadline: None,
+            min_level_trivially_unsat: None,
+            solver_executable,
+            extra_args,
+            dump_on_unknown: None,
+            unknown_dumps: 0,
+            check_count: 0,
+            last_proof_vacuous: false,
+            last_proof_trivial: false,
+            labeled_frames: Vec::new(),
+        }
+    }
+
+    /// Like [`Self::new`] with [`IncrementalMode::Native`], but first checks
+    /// that the executable of an external `solver_type` can be started.
+    /// Applications can use this to fall back to another backend.
+    ///
+    /// The internal Z3 is linked into the program and always available here:
+    /// if its library could not be loaded, creating the [`Context`] would
+    /// have failed already.
+    pub fn try_new(
+        ctx: &'ctx Context,
+        solver: impl Into<SolverConfig>,
+    ) -> Result<Self, BackendUnavailable> {
+        let config = solver.into();
+        let executable = match &config {
+            SolverConfig::Swine { binary } => binary.as_deref(),
+            _ => None,
+        };
+        check_backend(&config.solver_type(), executable)?;
+        Ok(Prover::new(ctx, IncrementalMode::Native, config))
+    }
+
+    /// Create a prover for testing code built on top of it. Instead of calling
+    /// a solver, each call of [`Self::check_proof_assuming`] with provables
+    /// returns the next one of `results` and fails with
+    /// [`ProverCommandError::MockExhausted`] once they are used up. Pushes,
+    /// pops and the bookkeeping of provables work as usual, so a check without
+    /// provables still returns [`ProveResult::Proof`] without consuming a
+    /// result.
+    pub fn new_mock(ctx: &'ctx Context, results: impl IntoIterator<Item = ProveResult>) -> Self {
+        let mut prover = Prover::new(ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        prover.mock_results = Some(results.into_iter().collect());
+        prover
+    }
+
+    /// Create a new prover whose solver is built from the Z3 tactic with the
+    /// given name (e.g. `qe`, `smt` or `nlsat`). Returns an error if Z3 does
+    /// not know the tactic.
+    ///
+    /// The prover uses [`IncrementalMode::Native`]. For the emulated mode, use
+    /// [`Self::new`] with a [`SolverConfig::Z3`] instead.
+    pub fn with_tactic(
+        ctx: &'ctx Context,
+        tactic: &str,
+        solver_type: SolverType,
+    ) -> Result<Self, ProverCommandError> {
+        if !Tactic::list_all(ctx).any(|name| matches!(name, Ok(name) if name == tactic)) {
+            return Err(ProverCommandError::UnknownTactic(tactic.to_owned()));
         }
+        let mut prover = Prover::new(ctx, IncrementalMode::Native, solver_type);
+        prover.solver = StackSolver::Native(Tactic::new(ctx, tactic).solver());
+        Ok(prover)
     }
 
     /// Get the Z3 context of this prover.
@@ -324,9 +943,145 @@ impl<'ctx> Prover<'ctx> {
         }
     }
 
-    /// Get all assertions added to the underlying solver.
+    /// Get all assertions added to the underlying solver, followed by the
+    /// literals of the current labeled frames (see [`Self::push_labeled`]).
+    /// The literals are not asserted in the solver but assumed in every
+    /// check, so they are part of the constraints.
     pub fn get_assertions(&self) -> Vec<Bool<'ctx>> {
-        self.get_solver().get_assertions()
+        let mut assertions = self.get_solver().get_assertions();
+        assertions.extend(self.frame_trackers());
+        assertions
+    }
+
+    /// The literals of the current labeled frames, see [`Self::push_labeled`].
+    fn frame_trackers(&self) -> Vec<Bool<'ctx>> {
+        self.labeled_frames
+            .iter()
+            .map(|frame| frame.tracker.clone())
+            .collect()
+    }
+
+    /// A hash of the set of assertions, suitable as a key for caching results
+    /// across runs. The assertions are printed, sorted and deduplicated, so
+    /// the fingerprint does not depend on the order in which they were added.
+    /// It is computed with 64-bit FNV-1a and thus stable across platforms
+    /// and builds, but assertions are not normalized beyond Z3's printing.
+    pub fn assertions_fingerprint(&self) -> u64 {
+        let mut assertions: Vec<String> = self
+            .get_assertions()
+            .iter()
+            .map(|assertion| assertion.to_string())
+            .collect();
+        assertions.sort();
+        assertions.dedup();
+        let mut hash = FNV_OFFSET_BASIS;
+        for assertion in &assertions {
+            // the separator does not occur in printed terms
+            for byte in assertion.bytes().chain([0]) {
+                hash = (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME);
+            }
+        }
+        hash
+    }
+
+    /// A cheap, incomplete check whether the assertions are contradictory
+    /// without calling the solver. The assertions are simplified and split
+    /// at conjunctions. They are obviously unsatisfiable if one of the
+    /// conjuncts is `false` or if both a literal and its negation occur.
+    ///
+    /// Since the negations of provables are assertions as well, `true` means
+    /// that [`Self::check_proof`] would return [`ProveResult::Proof`]. A result
+    /// of `false` says nothing.
+    pub fn assumptions_obviously_unsat(&self) -> bool {
+        let mut literals = HashSet::new();
+        let mut worklist = self.get_assertions();
+        while let Some(assertion) = worklist.pop() {
+            let assertion = assertion.simplify();
+            match assertion.as_bool() {
+                Some(false) => return true,
+                Some(true) => continue,
+                None => {}
+            }
+            let kind = assertion.is_app().then(|| assertion.decl().kind());
+            if kind == Some(DeclKind::AND) {
+                worklist.extend(
+                    assertion
+                        .children()
+                        .iter()
+                        .filter_map(|child| child.as_bool()),
+                );
+                continue;
+            }
+            let literal = match kind {
+                Some(DeclKind::NOT) => match assertion.children()[0].as_bool() {
+                    Some(atom) => (atom, false),
+                    None => continue,
+                },
+                _ => (assertion, true),
+            };
+            if literals.contains(&(literal.0.clone(), !literal.1)) {
+                return true;
+            }
+            literals.insert(literal);
+        }
+        false
+    }
+
+    /// Write the SMT-LIB of every query that is sent to the solver to
+    /// `writer`. Results answered from the cache are not logged. Writes are
+    /// buffered and flushed according to the [`FlushPolicy`], which defaults
+    /// to [`FlushPolicy::OnDrop`].
+    pub fn set_query_log(&mut self, writer: impl Write + 'static) {
+        let flush = self.query_log.as_ref().map(|log| log.flush);
+        self.query_log = Some(QueryLog {
+            writer: Some(BufWriter::new(Box::new(writer))),
+            flush: flush.unwrap_or_default(),
+            queries: 0,
+        });
+    }
+
+    /// Set when the query log is flushed. Has no effect without a query log.
+    pub fn set_query_log_flush(&mut self, policy: FlushPolicy) {
+        if let Some(log) = &mut self.query_log {
+            log.flush = policy;
+        }
+    }
+
+    fn log_query(&mut self, assumptions: &[Bool<'ctx>]) {
+        if self.query_log.is_none() {
+            return;
+        }
+        let mut smtlib = self.get_smtlib();
+        add_check_sat_tracked(&mut smtlib, assumptions);
+        let log = self.query_log.as_mut().unwrap();
+        if let Err(err) = log.write_query(&smtlib.into_string()) {
+            tracing::warn!("failed to write to the query log: {}", err);
+        }
+    }
+
+    /// Set a global deadline for all following proof checks. The timeout of
+    /// each solver call is clamped to the remaining budget, and once the
+    /// deadline has passed, [`Self::check_proof_assuming`] returns
+    /// [`ReasonUnknown::Timeout`] without calling the solver.
+    pub fn set_deadline(&mut self, deadline: Instant) {
+        self.deadline = Some(deadline);
+    }
+
+    /// The time left until the deadline, or `None` if there is no deadline.
+    pub fn remaining_budget(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// The timeout for the next solver call: the smaller one of the timeout
+    /// and the remaining budget, but at least a millisecond, since a timeout
+    /// of zero disables the timeout for Z3.
+    fn effective_timeout(&self) -> Option<Duration> {
+        let timeout = match (self.timeout, self.remaining_budget()) {
+            (Some(timeout), Some(budget)) => Some(timeout.min(budget)),
+            (timeout, budget) => timeout.or(budget),
+        };
+        timeout.map(|timeout| timeout.max(Duration::from_millis(1)))
     }
 
     /// Set a timeout for every `check` call.
@@ -335,18 +1090,68 @@ impl<'ctx> Prover<'ctx> {
         set_solver_timeout(self.get_solver(), duration);
     }
 
+    /// Set the random seed of the internal Z3 solver. External solvers are
+    /// not affected.
+    pub fn set_random_seed(&mut self, seed: u32) {
+        let mut params = Params::new(self.ctx);
+        params.set_u32("random_seed", seed);
+        self.get_solver().set_params(&params);
+    }
+
+    /// Write the SMT-LIB of every query with an unknown result to a new file
+    /// `unknown-N.smt2` in `dir`, e.g. to report solver issues. Results
+    /// answered from the cache are not written again.
+    pub fn set_dump_on_unknown(&mut self, dir: impl Into<PathBuf>) {
+        self.dump_on_unknown = Some(dir.into());
+    }
+
+    fn dump_unknown(&mut self, assumptions: &[Bool<'ctx>]) {
+        let Some(dir) = &self.dump_on_unknown else {
+            return;
+        };
+        self.unknown_dumps += 1;
+        let path = dir.join(format!("unknown-{}.smt2", self.unknown_dumps));
+        let mut smtlib = self.get_smtlib();
+        add_check_sat_tracked(&mut smtlib, assumptions);
+        if let Err(err) = std::fs::write(&path, smtlib.into_string()) {
+            tracing::warn!("failed to write {}: {}", path.display(), err);
+        }
+    }
+
     /// Add an assumption to this prover.
     pub fn add_assumption(&mut self, value: &Bool<'ctx>) {
+        self.add_assertion(value);
+        self.assertion_counts.last_mut().unwrap().assumptions += 1;
+    }
+
+    /// Add the assertion to the solver and return whether it simplifies to
+    /// `false`.
+    fn add_assertion(&mut self, value: &Bool<'ctx>) -> bool {
+        let trivially_unsat = value.simplify().as_bool() == Some(false);
+        if trivially_unsat {
+            self.min_level_trivially_unsat.get_or_insert(self.level);
+        }
+        // assertions on a labeled level only hold if its literal is assumed
+        let value = match self.labeled_frames.last_mut() {
+            Some(frame) if frame.level == self.level => {
+                frame.has_assertions = true;
+                frame.tracker.implies(value)
+            }
+            _ => value.clone(),
+        };
         match &mut self.solver {
             StackSolver::Native(solver) => {
-                solver.assert(value);
+                solver.assert(&value);
             }
             StackSolver::Emulated(solver, stack) => {
-                solver.assert(value);
-                stack.last_mut().unwrap().push(value.clone());
+                solver.assert(&value);
+                stack.last_mut().unwrap().push(value);
             }
         }
+        self.assertion_counts.last_mut().unwrap().assertions += 1;
         self.last_result = None;
+        self.base_smtlib = None;
+        trivially_unsat
     }
 
     /// Add a proof obligation to this prover. It adds the negated formula to
@@ -356,23 +1161,198 @@ impl<'ctx> Prover<'ctx> {
     /// We call it `provable` to avoid confusion between the Z3 solver's
     /// `assert` methods.
     pub fn add_provable(&mut self, value: &Bool<'ctx>) {
-        self.add_assumption(&value.not());
+        let trivial = self.add_assertion(&value.not());
+        let counts = self.assertion_counts.last_mut().unwrap();
+        counts.provables += 1;
+        if trivial {
+            counts.trivial_provables += 1;
+        }
+        self.min_level_with_provables.get_or_insert(self.level);
+    }
+
+    /// Like [`Self::add_provable`], but if `value` is syntactically a
+    /// conjunction `(and g1 ... gn)`, each conjunct is negated individually
+    /// and counts as a separate provable. [`Self::check_proof`] then treats
+    /// the conjunction as proved only if all conjuncts are.
+    ///
+    /// The negated conjuncts are asserted as one disjunction: asserting them
+    /// separately would only prove that one of the conjuncts holds. A
+    /// counterexample falsifies at least one conjunct, which can be found by
+    /// evaluating the conjuncts in the model.
+    pub fn add_provable_split(&mut self, value: &Bool<'ctx>) {
+        let is_and = value.is_app() && value.decl().kind() == DeclKind::AND;
+        let conjuncts: Vec<Bool<'ctx>> = if is_and {
+            value
+                .children()
+                .iter()
+                .filter_map(|child| child.as_bool())
+                .collect()
+        } else {
+            vec![]
+        };
+        if conjuncts.len() < 2 {
+            self.add_provable(value);
+            return;
+        }
+        let negated: Vec<Bool<'ctx>> = conjuncts.iter().map(Bool::not).collect();
+        // the disjunction only simplifies to false if all conjuncts are trivial
+        let trivial = self.add_assertion(&Bool::or(self.ctx, &negated));
+        let counts = self.assertion_counts.last_mut().unwrap();
+        counts.provables += conjuncts.len();
+        if trivial {
+            counts.trivial_provables += conjuncts.len();
+        }
         self.min_level_with_provables.get_or_insert(self.level);
     }
 
+    /// Like [`Self::add_assumption`], but the assumption is annotated with a
+    /// comment containing `location` in the output of [`Self::get_smtlib`].
+    pub fn add_assumption_located(&mut self, value: &Bool<'ctx>, location: &str) {
+        self.add_assumption(value);
+        self.locations
+            .push((self.level, value.clone(), location.to_owned()));
+    }
+
+    /// Like [`Self::add_provable`], but the provable is annotated with a
+    /// comment containing `location` in the output of [`Self::get_smtlib`].
+    pub fn add_provable_located(&mut self, value: &Bool<'ctx>, location: &str) {
+        self.add_provable(value);
+        self.locations
+            .push((self.level, value.not(), location.to_owned()));
+    }
+
+    /// Like [`Self::add_assumption`], but the assumption is recorded under
+    /// `name` so that its value in a model can be inspected with
+    /// [`Self::named_assumption_values`]. The name is removed again when the
+    /// current level is popped.
+    pub fn add_named_assumption(&mut self, value: &Bool<'ctx>, name: &str) {
+        self.add_assumption(value);
+        self.named_assumptions
+            .push((self.level, name.to_owned(), value.clone()));
+    }
+
+    /// Evaluate the assumptions added by [`Self::add_named_assumption`] in
+    /// `model`, e.g. to see which assumptions hold under a counterexample
+    /// obtained with [`ModelConsistency::Unknown`]. Assumptions whose value
+    /// cannot be determined are omitted.
+    pub fn named_assumption_values(
+        &self,
+        model: &InstrumentedModel<'ctx>,
+    ) -> HashMap<String, bool> {
+        self.named_assumptions
+            .iter()
+            .filter_map(|(_, name, value)| {
+                let value = model.eval_ast(value, true)?.as_bool()?;
+                Some((name.clone(), value))
+            })
+            .collect()
+    }
+
+    /// The number of assumptions added on all current levels.
+    pub fn num_assumptions(&self) -> usize {
+        self.assertion_counts
+            .iter()
+            .map(|counts| counts.assumptions)
+            .sum()
+    }
+
+    /// The number of provables added on all current levels.
+    pub fn num_provables(&self) -> usize {
+        self.assertion_counts
+            .iter()
+            .map(|counts| counts.provables)
+            .sum()
+    }
+
+    /// Add all assertions of `other` to the current level of this prover. The
+    /// assumptions and provables of `other` are counted as assumptions and
+    /// provables of this prover, so a check of the merged prover only
+    /// succeeds trivially if neither prover has provables. Locations and named
+    /// assumptions are taken over as well.
+    ///
+    /// Both provers must use the same [`Context`], which is checked with a
+    /// debug assertion.
+    pub fn merge_from(&mut self, other: &Prover<'ctx>) {
+        debug_assert!(
+            std::ptr::eq(self.ctx, other.ctx),
+            "merged provers must share a context"
+        );
+        for assertion in other.get_assertions() {
+            self.add_assertion(&assertion);
+        }
+        let counts = self.assertion_counts.last_mut().unwrap();
+        counts.assumptions += other.num_assumptions();
+        counts.provables += other.num_provables();
+        counts.trivial_provables += other
+            .assertion_counts
+            .iter()
+            .map(|counts| counts.trivial_provables)
+            .sum::<usize>();
+        if other.min_level_with_provables.is_some() {
+            self.min_level_with_provables.get_or_insert(self.level);
+        }
+        for (_, value, location) in &other.locations {
+            self.locations
+                .push((self.level, value.clone(), location.clone()));
+        }
+        for (_, name, value) in &other.named_assumptions {
+            self.named_assumptions
+                .push((self.level, name.clone(), value.clone()));
+        }
+    }
+
+    /// Check the provables under the `assumptions` with both the internal Z3
+    /// and SwInE, and compare the conclusive results. This is meant for
+    /// differential testing of the backends, e.g. to find unsound
+    /// adjustments of the SMT-LIB for SwInE. The assertions are copied to
+    /// new provers, so this prover's state and cached results are not
+    /// changed. The SwInE executable of this prover is used if it has one.
+    ///
+    /// Fails if one of the checks fails, e.g. because SwInE is not installed.
+    pub fn cross_check(
+        &mut self,
+        assumptions: &[Bool<'ctx>],
+    ) -> Result<CrossCheckResult, ProverCommandError> {
+        let swine = SolverConfig::Swine {
+            binary: match self.smt_solver {
+                SolverType::SWINE => self.solver_executable.clone(),
+                _ => None,
+            },
+        };
+        let mut check = |config: SolverConfig| {
+            let mut prover = Prover::new(self.ctx, IncrementalMode::Native, config);
+            if let Some(timeout) = self.effective_timeout() {
+                prover.set_timeout(timeout);
+            }
+            prover.merge_from(self);
+            let res = prover.check_proof_assuming(assumptions);
+            self.check_count += prover.check_count();
+            res
+        };
+        let z3 = check(SolverType::InternalZ3.into())?;
+        let swine = check(swine)?;
+        Ok(compare_results(z3, swine))
+    }
+
     /// `self.check_proof_assuming(&[])`.
     pub fn check_proof(&mut self) -> Result<ProveResult, ProverCommandError> {
         self.check_proof_assuming(&[])
     }
 
     /// Do the SAT check, but consider a check with no provables to be a
-    /// [`ProveResult::Proof`].
+    /// [`ProveResult::Proof`]. If a provable simplifies to `true` or an
+    /// assumption simplifies to `false`, the result is a
+    /// [`ProveResult::Proof`] as well and the solver is not called.
+    ///
+    /// Returns [`ProverCommandError::ExistsForallProof`] on a prover created by
+    /// [`Self::to_exists_forall`], since it only has assumptions and the check
+    /// would always succeed.
     pub fn check_proof_assuming(
         &mut self,
         assumptions: &[Bool<'ctx>],
     ) -> Result<ProveResult, ProverCommandError> {
-        if !self.has_provables() {
-            return Ok(ProveResult::Proof);
+        if let Some(res) = self.result_without_solver()? {
+            return Ok(res);
         }
 
         match self.smt_solver {
@@ -382,11 +1362,18 @@ impl<'ctx> Prover<'ctx> {
                         cached_result.last_result.clone()
                     }
                     _ => {
+                        self.log_query(assumptions);
+                        self.check_count += 1;
                         let solver = self.get_solver();
-                        let res = if assumptions.is_empty() {
+                        if self.deadline.is_some() {
+                            set_solver_timeout(solver, self.effective_timeout().unwrap());
+                        }
+                        let mut checked = assumptions.to_vec();
+                        checked.extend(self.frame_trackers());
+                        let res = if checked.is_empty() {
                             solver.check()
                         } else {
-                            solver.check_assumptions(assumptions)
+                            solver.check_assumptions(&checked)
                         };
 
                         let solver_result = match res {
@@ -395,6 +1382,9 @@ impl<'ctx> Prover<'ctx> {
                             SatResult::Sat => SolverResult::Sat(None),
                         };
                         self.cache_result(solver_result.clone());
+                        if let SolverResult::Unknown(_) = solver_result {
+                            self.dump_unknown(assumptions);
+                        }
                         solver_result
                     }
                 };
@@ -413,31 +1403,290 @@ impl<'ctx> Prover<'ctx> {
                         Ok(cached_result.last_result.clone())
                     }
                     _ => {
+                        self.log_query(assumptions);
                         let solver_result = self.run_solver(assumptions)?;
+                        if let SolverResult::Unknown(_) = solver_result {
+                            self.dump_unknown(assumptions);
+                        }
                         Ok(solver_result)
                     }
                 };
 
-                let sat_result = res?;
-
-                match sat_result {
-                    SolverResult::Unsat => Ok(ProveResult::Proof),
-                    SolverResult::Unknown(r) => {
-                        let reason = r.unwrap_or(ReasonUnknown::Other("".to_string()));
-                        Ok(ProveResult::Unknown(reason))
-                    }
-                    SolverResult::Sat(_) => Ok(ProveResult::Counterexample),
-                }
+                Ok(external_prove_result(res?))
             }
         }
     }
 
-    /// Whether this prover has any provables added (excluding assumptions). If
-    /// so, then any call to [`Self::check_proof`] or
-    /// [`Self::check_proof_assuming`] will return [`ProveResult::Proof`]
-    /// immediately.
-    pub fn has_provables(&mut self) -> bool {
-        self.min_level_with_provables.is_some()
+    /// The checks of [`Self::check_proof_assuming`] before the solver is
+    /// called. Returns the result if it is known without calling the solver,
+    /// e.g. because there are no provables or the budget is exhausted.
+    fn result_without_solver(&mut self) -> Result<Option<ProveResult>, ProverCommandError> {
+        if self.exists_forall {
+            tracing::warn!("check_proof called on an exists-forall prover, use check_sat instead");
+            return Err(ProverCommandError::ExistsForallProof);
+        }
+        self.debug_assert_tracking();
+        self.update_proof_flags();
+        if !self.has_provables() || self.min_level_trivially_unsat.is_some() {
+            return Ok(Some(ProveResult::Proof));
+        }
+
+        if self.remaining_budget() == Some(Duration::ZERO) {
+            return Ok(Some(ProveResult::Unknown(ReasonUnknown::Timeout)));
+        }
+
+        if let Some(results) = &mut self.mock_results {
+            return results
+                .pop_front()
+                .map(Some)
+                .ok_or(ProverCommandError::MockExhausted);
+        }
+        Ok(None)
+    }
+
+    /// Like [`Self::check_proof_assuming`], but on a [`ProveResult::Proof`]
+    /// also return the indices of those `assumptions` which are in the unsat
+    /// core. Every assumption is tracked by a fresh literal so that the core
+    /// can be mapped back reliably. For other results, the list of indices is
+    /// empty.
+    ///
+    /// For external solvers, the assumptions are asserted with `:named`
+    /// attributes and the core is requested via `(get-unsat-core)`.
+    pub fn check_proof_assuming_core(
+        &mut self,
+        assumptions: &[Bool<'ctx>],
+    ) -> Result<(ProveResult, Vec<usize>), ProverCommandError> {
+        if self.smt_solver != SolverType::InternalZ3 {
+            return self.run_solver_core(assumptions);
+        }
+        if let Some(res) = self.result_without_solver()? {
+            // the solver is not called, so there is no core
+            return Ok((res, Vec::new()));
+        }
+
+        let mut guard = ScopeGuard::new(self);
+        let trackers: Vec<Bool<'ctx>> = assumptions
+            .iter()
+            .map(|assumption| {
+                let tracker = Bool::fresh_const(guard.ctx, "assumption");
+                guard.add_assumption(&tracker.implies(assumption));
+                tracker
+            })
+            .collect();
+        let res = guard.check_proof_assuming(&trackers)?;
+        let core = match res {
+            ProveResult::Proof => {
+                let core = guard.get_unsat_core();
+                trackers
+                    .iter()
+                    .positions(|tracker| core.contains(tracker))
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+        Ok((res, core))
+    }
+
+    /// Compute a minimal subset of the `assumptions` under which the provables
+    /// can still be proven, returned as sorted indices into `assumptions`.
+    /// Removing any single assumption from the result makes the proof fail
+    /// (or the result unknown). The search starts from the unsat core and then
+    /// tries to drop each remaining assumption in turn.
+    ///
+    /// If the provables cannot be proven with all assumptions, all indices are
+    /// returned.
+    pub fn minimize_assumptions(
+        &mut self,
+        assumptions: &[Bool<'ctx>],
+    ) -> Result<Vec<usize>, ProverCommandError> {
+        let (res, mut current) = self.check_proof_assuming_core(assumptions)?;
+        if !matches!(res, ProveResult::Proof) {
+            return Ok((0..assumptions.len()).collect());
+        }
+        let mut i = 0;
+        while i < current.len() {
+            let candidate: Vec<Bool<'ctx>> = current
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, index)| assumptions[*index].clone())
+                .collect();
+            // with an empty list of assumptions, a cached result of a check
+            // with other assumptions could be returned
+            self.last_result = None;
+            if let ProveResult::Proof = self.check_proof_assuming(&candidate)? {
+                current.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        Ok(current)
+    }
+
+    /// Call [`Self::check_proof`] with a timeout of `base_timeout`. As long as
+    /// the result is unknown because of a timeout, retry up to `max_retries`
+    /// times, doubling the timeout every time. Other unknown results are
+    /// returned immediately since more time will not help there.
+    ///
+    /// Afterwards, the prover keeps the last timeout that was used.
+    pub fn check_proof_with_retry(
+        &mut self,
+        base_timeout: Duration,
+        max_retries: usize,
+    ) -> Result<ProveResult, ProverCommandError> {
+        let mut timeout = base_timeout;
+        let mut retries = 0;
+        loop {
+            self.set_timeout(timeout);
+            // a cached result was obtained with a different timeout
+            self.last_result = None;
+            let res = self.check_proof()?;
+            if !matches!(res, ProveResult::Unknown(ReasonUnknown::Timeout))
+                || retries == max_retries
+            {
+                return Ok(res);
+            }
+            retries += 1;
+            timeout = timeout.saturating_mul(2);
+        }
+    }
+
+    /// Prove each of the `goals` independently under the current assertions
+    /// and return the results in the same order. Every goal is added as a
+    /// provable in its own scope, which is popped again after the check, so
+    /// the goals do not interfere with each other. Provables that were added
+    /// before are not removed, so this is usually called on a prover with
+    /// assumptions only.
+    ///
+    /// On an error, the prover is restored to its previous level and the error
+    /// is returned.
+    pub fn prove_each(
+        &mut self,
+        goals: &[Bool<'ctx>],
+    ) -> Result<Vec<ProveResult>, ProverCommandError> {
+        let mut results = Vec::with_capacity(goals.len());
+        for goal in goals {
+            self.push();
+            self.add_provable(goal);
+            let res = self.check_proof();
+            self.pop();
+            results.push(res?);
+        }
+        Ok(results)
+    }
+
+    /// Like [`Self::prove_each`], but the goals are checked concurrently by
+    /// `threads` workers, each with its own Z3 context and solver. The
+    /// current assertions are sent to the workers as SMT-LIB and parsed once
+    /// per worker. The results are returned in the order of the goals,
+    /// together with the model of each counterexample or unknown result.
+    ///
+    /// The workers always use Z3's internal solver with the timeout of this
+    /// prover, clamped to the remaining budget like for
+    /// [`Self::check_proof_assuming`]. Once the deadline has passed, every
+    /// result is [`ReasonUnknown::Timeout`]. Other options like the random
+    /// seed or the logic are not transferred.
+    pub fn prove_each_parallel(
+        &self,
+        goals: &[Bool<'ctx>],
+        threads: usize,
+    ) -> Vec<(ProveResult, Option<OwnedModel>)> {
+        if self.remaining_budget() == Some(Duration::ZERO) {
+            return goals
+                .iter()
+                .map(|_| (ProveResult::Unknown(ReasonUnknown::Timeout), None))
+                .collect();
+        }
+        let base = self.get_smtlib().into_string();
+        let queries: Vec<String> = goals
+            .iter()
+            .map(|goal| {
+                let solver = Solver::new(self.ctx);
+                solver.assert(&goal.not());
+                Smtlib::from_solver(&solver).into_string()
+            })
+            .collect();
+        let timeout = self.effective_timeout();
+        let next = AtomicUsize::new(0);
+        let mut results: Vec<(usize, (ProveResult, Option<OwnedModel>))> =
+            std::thread::scope(|scope| {
+                let workers: Vec<_> = (0..threads.clamp(1, goals.len().max(1)))
+                    .map(|_| {
+                        scope.spawn(|| {
+                            let ctx = Context::new(&Config::default());
+                            let solver = Solver::new(&ctx);
+                            if let Some(timeout) = timeout {
+                                set_solver_timeout(&solver, timeout);
+                            }
+                            solver.from_string(base.as_str());
+                            let mut results = Vec::new();
+                            // goals are distributed dynamically since their
+                            // difficulty varies
+                            loop {
+                                let index = next.fetch_add(1, Ordering::Relaxed);
+                                let Some(query) = queries.get(index) else {
+                                    break;
+                                };
+                                solver.push();
+                                solver.from_string(query.as_str());
+                                results.push((index, check_owned(&solver)));
+                                solver.pop(1);
+                            }
+                            results
+                        })
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .flat_map(|worker| worker.join().unwrap())
+                    .collect()
+            });
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, res)| res).collect()
+    }
+
+    /// Like [`Self::prove_each`], but stops at the first goal that is not
+    /// proven. Returns `Ok(Err((index, result)))` with the index of that goal
+    /// and its result, or `Ok(Ok(()))` if all goals are proven. The model or
+    /// reason of a failing goal is not retained since its scope is popped.
+    pub fn prove_all(
+        &mut self,
+        goals: &[Bool<'ctx>],
+    ) -> Result<Result<(), (usize, ProveResult)>, ProverCommandError> {
+        for (index, goal) in goals.iter().enumerate() {
+            self.push();
+            self.add_provable(goal);
+            let res = self.check_proof();
+            self.pop();
+            match res? {
+                ProveResult::Proof => {}
+                res => return Ok(Err((index, res))),
+            }
+        }
+        Ok(Ok(()))
+    }
+
+    /// Run `f` on this prover with the `assumptions` added in a new scope. The
+    /// scope is popped when `f` returns or panics, so the assumptions are
+    /// retracted afterwards.
+    pub fn with_assumptions<T>(
+        &mut self,
+        assumptions: &[Bool<'ctx>],
+        f: impl FnOnce(&mut Prover<'ctx>) -> T,
+    ) -> T {
+        let mut guard = ScopeGuard::new(self);
+        for assumption in assumptions {
+            guard.add_assumption(assumption);
+        }
+        f(&mut guard)
+    }
+
+    /// Whether this prover has any provables added (excluding assumptions). If
+    /// so, then any call to [`Self::check_proof`] or
+    /// [`Self::check_proof_assuming`] will return [`ProveResult::Proof`]
+    /// immediately.
+    pub fn has_provables(&mut self) -> bool {
+        self.min_level_with_provables.is_some()
     }
 
     /// Do the regular SAT check.
@@ -446,9 +1695,16 @@ impl<'ctx> Prover<'ctx> {
             return Ok(cached_result.last_result.to_sat_result());
         }
 
+        self.log_query(&[]);
         let sat_result = match self.smt_solver {
             SolverType::InternalZ3 => {
-                let sat_result = self.get_solver().check();
+                self.check_count += 1;
+                let trackers = self.frame_trackers();
+                let sat_result = if trackers.is_empty() {
+                    self.get_solver().check()
+                } else {
+                    self.get_solver().check_assumptions(&trackers)
+                };
 
                 let solver_result = match sat_result {
                     SatResult::Unsat => SolverResult::Unsat,
@@ -481,6 +1737,45 @@ impl<'ctx> Prover<'ctx> {
         });
     }
 
+    /// Like [`Self::check_sat`], but also return the model for a
+    /// [`SatResult::Sat`] or [`SatResult::Unknown`] result. Its consistency is
+    /// as described for [`Self::get_model`].
+    pub fn check_sat_model(
+        &mut self,
+    ) -> Result<(SatResult, Option<InstrumentedModel<'ctx>>), ProverCommandError> {
+        let res = self.check_sat()?;
+        let model = match res {
+            SatResult::Unsat => None,
+            SatResult::Sat | SatResult::Unknown => self.get_model(),
+        };
+        Ok((res, model))
+    }
+
+    /// Check the proof again with the `keep` terms fixed to their values in
+    /// `model`, which usually is a counterexample of the last check. All
+    /// other constants are free, so the solver may find a simpler
+    /// counterexample that agrees with `model` on the essential part. The
+    /// check runs in its own scope, so the assertions are unchanged
+    /// afterwards, and the new model is returned along with the result.
+    pub fn generalize_counterexample(
+        &mut self,
+        model: &InstrumentedModel<'ctx>,
+        keep: &[Dynamic<'ctx>],
+    ) -> Result<(ProveResult, Option<InstrumentedModel<'ctx>>), ProverCommandError> {
+        let mut guard = ScopeGuard::new(self);
+        for term in keep {
+            if let Some(value) = model.eval_ast(term, true) {
+                guard.add_assumption(&term._eq(&value));
+            }
+        }
+        let res = guard.check_proof()?;
+        let model = match res {
+            ProveResult::Proof => None,
+            ProveResult::Counterexample | ProveResult::Unknown(_) => guard.get_model(),
+        };
+        Ok((res, model))
+    }
+
     /// Retrieve the model from the solver. If the result of the latest check
     /// was [`ProveResult::Counterexample`] or [`SatResult::Sat`], then the
     /// model is guaranteed to be consistent with the assertions
@@ -508,39 +1803,96 @@ impl<'ctx> Prover<'ctx> {
         Some(InstrumentedModel::new(consistency, model))
     }
 
-    /// Retrieve the UNSAT core. See [`Solver::get_unsat_core()`].
+    /// Retrieve the UNSAT core. See [`Solver::get_unsat_core()`]. For
+    /// external solvers, this is the core of the assumptions passed to the
+    /// last call of [`Self::check_proof_assuming_core`].
     pub fn get_unsat_core(&self) -> Vec<Bool<'ctx>> {
-        self.get_solver().get_unsat_core()
+        match self.smt_solver {
+            SolverType::InternalZ3 => self.get_solver().get_unsat_core(),
+            _ => self.external_unsat_core.clone(),
+        }
     }
 
     /// See [`Solver::get_reason_unknown`].
     pub fn get_reason_unknown(&self) -> Option<ReasonUnknown> {
+        self.get_reason_unknown_raw().map(|(reason, _)| reason)
+    }
+
+    /// Like [`Self::get_reason_unknown`], but also returns the exact reason
+    /// string reported by the solver, e.g. for bug reports. The parsed
+    /// variants like [`ReasonUnknown::Interrupted`] do not retain it.
+    pub fn get_reason_unknown_raw(&self) -> Option<(ReasonUnknown, String)> {
         match self.smt_solver {
             SolverType::InternalZ3 => self
                 .get_solver()
                 .get_reason_unknown()
-                .map(|reason| reason.parse().unwrap()),
+                .map(|raw| (raw.parse().unwrap(), raw)),
             _ => {
                 if let Some(cached_result) = &self.last_result {
                     if let SolverResult::Unknown(reason_unknown) = &cached_result.last_result {
-                        reason_unknown.clone()
+                        reason_unknown
+                            .as_ref()
+                            .map(|reason| (reason.clone(), reason.to_string()))
                     } else {
-                        Some(ReasonUnknown::Other("".to_string()))
+                        Some((ReasonUnknown::Other("".to_string()), "".to_string()))
                     }
                 } else {
-                    Some(ReasonUnknown::Other("".to_string()))
+                    Some((ReasonUnknown::Other("".to_string()), "".to_string()))
                 }
             }
         }
     }
 
+    /// Like [`Self::push`], but the new level is tagged with a `label`. The
+    /// assertions added on this level are asserted as implications of a fresh
+    /// literal, which is assumed in every check, so that
+    /// [`Self::core_labels`] can tell whether they were needed for a proof.
+    /// The literal is part of [`Self::get_assertions`] and
+    /// [`Self::get_smtlib`], so copies of the assertions keep their meaning.
+    ///
+    /// Fails with [`ProverCommandError::InvalidConfiguration`] for external
+    /// solvers, which do not report unsat cores for proof checks.
+    pub fn push_labeled(&mut self, label: &str) -> Result<(), ProverCommandError> {
+        if self.smt_solver != SolverType::InternalZ3 {
+            return Err(ProverCommandError::InvalidConfiguration(
+                "labeled frames require the internal Z3".to_owned(),
+            ));
+        }
+        self.push();
+        let tracker = Bool::fresh_const(self.ctx, &format!("frame!{}", label));
+        self.labeled_frames.push(LabeledFrame {
+            level: self.level,
+            label: label.to_owned(),
+            tracker,
+            has_assertions: false,
+        });
+        Ok(())
+    }
+
+    /// The labels of the current levels pushed by [`Self::push_labeled`]
+    /// whose assertions are in the unsat core of the last check. This is only
+    /// meaningful after a check which called the solver and returned
+    /// [`ProveResult::Proof`], i.e. not if the result was trivial (see
+    /// [`Self::check_proof_assuming`]).
+    pub fn core_labels(&self) -> Vec<String> {
+        let core = self.get_unsat_core();
+        self.labeled_frames
+            .iter()
+            .filter(|frame| core.contains(&frame.tracker))
+            .map(|frame| frame.label.clone())
+            .collect()
+    }
+
     /// See [`Solver::push`].
     pub fn push(&mut self) {
         self.level += 1;
+        self.assertion_counts.push(AssertionCounts::default());
+        self.base_smtlib = None;
         match &mut self.solver {
             StackSolver::Native(solver) => solver.push(),
             StackSolver::Emulated(_, stack) => stack.push(Vec::new()),
         }
+        self.debug_assert_consistent();
     }
 
     /// See [`Solver::pop`].
@@ -552,12 +1904,22 @@ impl<'ctx> Prover<'ctx> {
                 self.min_level_with_provables.take();
             }
         }
+        if self.min_level_trivially_unsat > Some(self.level) {
+            self.min_level_trivially_unsat = None;
+        }
+        self.assertion_counts.pop();
+        self.locations.retain(|(level, _, _)| *level <= self.level);
+        self.named_assumptions
+            .retain(|(level, _, _)| *level <= self.level);
+        self.labeled_frames
+            .retain(|frame| frame.level <= self.level);
 
         match &mut self.solver {
             StackSolver::Native(solver) => {
                 // we don't know if the pop will change the state, so reset in
                 // every case
                 self.last_result = None;
+                self.base_smtlib = None;
                 solver.pop(1)
             }
             StackSolver::Emulated(ref mut solver, stack) => {
@@ -565,17 +1927,131 @@ impl<'ctx> Prover<'ctx> {
                 debug_assert_eq!(stack.len(), self.level + 1);
 
                 // if we didn't change the solver state, we do not need to reset
-                if old_top.is_empty() {
-                    return;
-                }
-
-                self.last_result = None;
-                *solver = Solver::new(self.ctx);
-                for level in stack.iter().flatten() {
-                    solver.assert(level);
+                if !old_top.is_empty() {
+                    self.last_result = None;
+                    self.base_smtlib = None;
+                    // resetting keeps the solver's parameters and tactic
+                    solver.reset();
+                    for level in stack.iter().flatten() {
+                        solver.assert(level);
+                    }
                 }
             }
         }
+        self.debug_assert_consistent();
+    }
+
+    /// Check that the tracked level agrees with the scopes of the underlying
+    /// solver and with the per-level bookkeeping. The z3 bindings do not expose
+    /// the number of scopes of a native solver, so only the bookkeeping is
+    /// checked in that case. Does nothing without `debug_assertions`.
+    pub fn debug_assert_consistent(&self) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        assert_eq!(
+            self.assertion_counts.len(),
+            self.level + 1,
+            "assertion counts do not match prover level"
+        );
+        if let StackSolver::Emulated(_, stack) = &self.solver {
+            assert_eq!(
+                stack.len(),
+                self.level + 1,
+                "emulated stack does not match prover level"
+            );
+        }
+        if let Some(min_level) = self.min_level_with_provables {
+            assert!(
+                min_level <= self.level,
+                "provables recorded above the current level"
+            );
+        }
+        assert_eq!(
+            self.min_level_with_provables.is_some(),
+            self.num_provables() > 0,
+            "provable tracking is out of sync"
+        );
+    }
+
+    /// Check that the underlying solver has exactly the assertions added
+    /// through this prover. Assertions added to the solver directly bypass
+    /// the tracking of provables, so a check might wrongly return
+    /// [`ProveResult::Proof`] without calling the solver. This is called by
+    /// [`Self::check_proof_assuming`]. Does nothing without
+    /// `debug_assertions`.
+    pub fn debug_assert_tracking(&self) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        let tracked: usize = self
+            .assertion_counts
+            .iter()
+            .map(|counts| counts.assertions)
+            .sum();
+        assert_eq!(
+            self.get_solver().get_assertions().len(),
+            tracked,
+            "assertions were added to the solver without the prover"
+        );
+    }
+
+    /// Simplify the current assertions with Z3's `simplify` and
+    /// `ctx-solver-simplify` tactics and replace them by the result. The
+    /// result is equisatisfiable with the previous assertions.
+    ///
+    /// This operates on the raw set of assertions: since provables are added
+    /// in negated form, the simplified assertions cannot be split into
+    /// assumptions and provables again. The prover still remembers whether
+    /// provables were added. Fails with
+    /// [`ProverCommandError::InvalidConfiguration`] if the prover is not at
+    /// level 0.
+    pub fn simplify_assertions(&mut self) -> Result<(), ProverCommandError> {
+        if self.level != 0 {
+            return Err(ProverCommandError::InvalidConfiguration(
+                "can only simplify assertions at level 0".to_owned(),
+            ));
+        }
+
+        let goal = Goal::new(self.ctx, false, false, false);
+        for assertion in self.get_assertions() {
+            goal.assert(&assertion);
+        }
+        let tactic = Tactic::new(self.ctx, "simplify")
+            .and_then(&Tactic::new(self.ctx, "ctx-solver-simplify"));
+        let mut subgoals: Vec<Vec<Bool<'ctx>>> = tactic
+            .apply(&goal, None)
+            .map_err(ProverCommandError::TacticError)?
+            .list_subgoals()
+            .map(|subgoal| subgoal.get_formulas())
+            .collect();
+        // the tactics produce a single subgoal, but in general the result is
+        // the disjunction of all subgoals
+        let simplified = if subgoals.len() == 1 {
+            subgoals.pop().unwrap()
+        } else {
+            let disjuncts: Vec<Bool<'ctx>> = subgoals
+                .iter()
+                .map(|formulas| Bool::and(self.ctx, formulas))
+                .collect();
+            vec![Bool::or(self.ctx, &disjuncts)]
+        };
+
+        // resetting keeps the solver's parameters and tactic
+        let solver = self.get_solver();
+        solver.reset();
+        for assertion in &simplified {
+            solver.assert(assertion);
+        }
+        self.assertion_counts[0].assertions = simplified.len();
+        if let StackSolver::Emulated(_, stack) = &mut self.solver {
+            *stack = vec![simplified];
+        }
+        // the original assertions are gone
+        self.locations.clear();
+        self.last_result = None;
+        self.base_smtlib = None;
+        Ok(())
     }
 
     /// Retrieve the current stack level. Useful for debug assertions.
@@ -605,41 +2081,254 @@ impl<'ctx> Prover<'ctx> {
     ///
     /// The result is a [`Prover`] for convenience (such as using the
     /// [`Self::level()`] function), but it should be used as a [`Solver`] via
-    /// [`Self::check_sat()`].
+    /// [`Self::check_sat()`]. Proof checks return
+    /// [`ProverCommandError::ExistsForallProof`].
+    ///
+    /// If `universal` is empty, no quantifier is created and the result just
+    /// has the negated conjunction of the assertions.
     pub fn to_exists_forall(&self, universal: &[Dynamic<'ctx>]) -> Prover<'ctx> {
-        let universal: Vec<&dyn Ast<'ctx>> =
-            universal.iter().map(|v| v as &dyn Ast<'ctx>).collect();
-        let theorem = forall_const(
-            self.ctx,
-            &universal,
-            &[],
-            &Bool::and(self.ctx, &self.get_assertions()).not(),
-        );
+        self.to_exists_forall_with_patterns(universal, &[])
+    }
+
+    /// Like [`Self::to_exists_forall`], but the universal quantifier is
+    /// annotated with the given patterns to guide Z3's quantifier
+    /// instantiation.
+    pub fn to_exists_forall_with_patterns(
+        &self,
+        universal: &[Dynamic<'ctx>],
+        patterns: &[Pattern<'ctx>],
+    ) -> Prover<'ctx> {
+        let body = Bool::and(self.ctx, &self.get_assertions()).not();
+        let theorem = if universal.is_empty() {
+            // a quantifier without bound variables is degenerate
+            body
+        } else {
+            let universal: Vec<&dyn Ast<'ctx>> =
+                universal.iter().map(|v| v as &dyn Ast<'ctx>).collect();
+            let patterns: Vec<&Pattern<'ctx>> = patterns.iter().collect();
+            forall_const(self.ctx, &universal, &patterns, &body)
+        };
         let mut res = Prover::new(self.ctx, IncrementalMode::Native, SolverType::InternalZ3); // TODO
         res.add_assumption(&theorem);
+        res.exists_forall = true;
         res
     }
 
-    /// Return the SMT-LIB that represents the solver state.
+    /// Retrieve the values of the existentially quantified constants after
+    /// [`Self::check_sat`] returned [`SatResult::Sat`] on a prover created by
+    /// [`Self::to_exists_forall`]. The result maps the names of the constants
+    /// to their values. Returns `None` if there is no consistent model.
+    pub fn exists_forall_witness(
+        &self,
+        existentials: &[Dynamic<'ctx>],
+    ) -> Option<HashMap<String, ConcreteValue>> {
+        let model = self.get_model()?;
+        if model.consistency() != ModelConsistency::Consistent {
+            return None;
+        }
+        existentials
+            .iter()
+            .map(|constant| Some((constant.decl().name(), model.concrete_value(constant)?)))
+            .collect()
+    }
+
+    /// Return the SMT-LIB of the current assertions. Assertions added with a
+    /// location are preceded by a `; location: ...` comment. Assertions that
+    /// Z3 prints differently in the solver than on their own are not
+    /// annotated.
     pub fn get_smtlib(&self) -> Smtlib {
-        Smtlib::from_solver(self.get_solver())
+        let mut smtlib = Smtlib::from_solver(self.get_solver());
+        if !self.locations.is_empty() {
+            let comments: Vec<(String, String)> = self
+                .locations
+                .iter()
+                .map(|(_, assertion, location)| {
+                    (
+                        format!("(assert {})", assertion),
+                        format!("location: {}", location),
+                    )
+                })
+                .collect();
+            smtlib.add_comments(&comments);
+        }
+        // the literal is only declared if there are assertions on its level
+        for frame in &self.labeled_frames {
+            if frame.has_assertions {
+                smtlib.add_assertion(&Assertion::new(frame.tracker.to_string()));
+            }
+        }
+        smtlib
+    }
+
+    /// Return the SMT-LIB of the current assertions (see [`Self::get_smtlib`])
+    /// together with an `(assert (= var value))` for every constant in the
+    /// assertions and its value in the `model`, followed by a `(check-sat)`.
+    /// For a counterexample, any solver reports `sat` on this query, so it can
+    /// be used to replay the counterexample.
+    ///
+    /// Constants whose value refers to an auxiliary function of the model
+    /// (i.e. arrays represented by `as-array`) are not pinned. This does not
+    /// count as an access of the model.
+    pub fn counterexample_smtlib(&self, model: &InstrumentedModel<'ctx>) -> String {
+        let mut smtlib = self.get_smtlib();
+        for constant in self.assertion_constants() {
+            let Some(value) = model.eval_ast_untracked(&constant, true) else {
+                continue;
+            };
+            if value.is_app() && value.decl().kind() == DeclKind::AS_ARRAY {
+                continue;
+            }
+            smtlib.add_assertion(&Assertion::new(format!("(= {} {})", constant, value)));
+        }
+        smtlib.add_check_sat();
+        smtlib.into_string()
+    }
+
+    /// The uninterpreted constants in the current assertions, in the order of
+    /// their first occurrence.
+    fn assertion_constants(&self) -> Vec<Dynamic<'ctx>> {
+        let mut seen = HashSet::new();
+        let mut constants = Vec::new();
+        let mut worklist: Vec<Dynamic<'ctx>> = self
+            .get_assertions()
+            .iter()
+            .rev()
+            .map(|assertion| Dynamic::from_ast(assertion))
+            .collect();
+        while let Some(term) = worklist.pop() {
+            if !seen.insert(term.clone()) {
+                continue;
+            }
+            if is_uninterpreted_constant(&term) {
+                constants.push(term);
+            } else if term.is_app() {
+                worklist.extend(term.children().into_iter().rev());
+            }
+        }
+        constants
     }
 
     pub fn get_smt_solver(&self) -> SolverType {
         self.smt_solver.clone()
     }
 
-    /// Execute an SMT solver (other than z3)
-    fn run_solver(&mut self, assumptions: &[Bool<'_>]) -> Result<SolverResult, ProverCommandError> {
-        let mut smt_file: NamedTempFile = NamedTempFile::new().unwrap();
-        smt_file
-            .write_all(self.generate_smtlib(assumptions).as_bytes())
-            .unwrap();
+    /// The version of the solver behind this prover for logs and bug reports:
+    /// [`z3_version`] for the internal Z3, and the output of `--version` of
+    /// the executable for external solvers. The output of each executable is
+    /// cached for the whole process.
+    pub fn backend_version(&self) -> String {
+        if self.smt_solver == SolverType::InternalZ3 {
+            return z3_version();
+        }
+        let executable = match &self.solver_executable {
+            Some(executable) => executable.clone(),
+            None => PathBuf::from(default_executable(&self.smt_solver)),
+        };
+        let mut versions = EXTERNAL_VERSIONS.lock().unwrap();
+        let version =
+            versions.entry(executable).or_insert_with_key(|executable| {
+                match Command::new(executable).arg("--version").output() {
+                    Ok(output) => String::from_utf8_lossy(&output.stdout).trim().to_owned(),
+                    Err(err) => format!("unavailable ({})", err),
+                }
+            });
+        version.clone()
+    }
 
-        let mut output = call_solver(smt_file.path(), self.get_smt_solver(), self.timeout, None)
-            .map_err(|e| ProverCommandError::ProcessError(e.to_string()))?;
+    /// Execute an external SMT solver on the assertions together with the
+    /// `assumptions` as named assertions and request the unsat core if the
+    /// result is unsat. For other results, the model or the reason is
+    /// requested like in [`Self::check_proof_assuming`], so that they are
+    /// available as usual.
+    fn run_solver_core(
+        &mut self,
+        assumptions: &[Bool<'ctx>],
+    ) -> Result<(ProveResult, Vec<usize>), ProverCommandError> {
+        self.external_unsat_core.clear();
+        if let Some(res) = self.result_without_solver()? {
+            return Ok((res, Vec::new()));
+        }
+
+        let name = |i: usize| format!("assumption!{}", i);
+        let mut smtlib = self.base_smtlib();
+        for (i, assumption) in assumptions.iter().enumerate() {
+            let assertion =
+                Assertion::new(assumption.to_string()).with_attribute("named", Some(&name(i)));
+            smtlib.add_assertion(&assertion);
+        }
+        smtlib.add_check_sat();
+        let input = transform_input_lines(
+            &smtlib.into_string(),
+            self.get_smt_solver(),
+            self.effective_timeout(),
+        )
+        .map_err(ProverCommandError::MalformedInput)?;
+        // the option must precede set-logic
+        let input = format!("(set-option :produce-unsat-cores true)\n{}\n", input);
+
+        self.log_query(assumptions);
+        let (solver_result, rest) = self.run_solver_on(&input, "(get-unsat-core)\n")?;
+        self.cache_result(solver_result.clone());
+        if !matches!(solver_result, SolverResult::Unsat) {
+            if let SolverResult::Unknown(_) = solver_result {
+                self.dump_unknown(assumptions);
+            }
+            return Ok((external_prove_result(solver_result), Vec::new()));
+        }
+
+        let names = match SExpr::parse_all(&rest).as_deref() {
+            Ok([SExpr::List(names)]) => names.clone(),
+            _ => return Err(ProverCommandError::UnexpectedResultError(rest)),
+        };
+        let core: Vec<usize> = (0..assumptions.len())
+            .filter(|&i| {
+                names
+                    .iter()
+                    .any(|label| matches!(label, SExpr::Atom(label) if label.trim_matches('|') == name(i)))
+            })
+            .collect();
+        self.external_unsat_core = core.iter().map(|&i| assumptions[i].clone()).collect();
+        Ok((ProveResult::Proof, core))
+    }
 
-        if !output.status.success() {
+    /// Execute an SMT solver (other than z3)
+    fn run_solver(
+        &mut self,
+        assumptions: &[Bool<'_>],
+    ) -> Result<SolverResult<'ctx>, ProverCommandError> {
+        let input = self.generate_smtlib(assumptions)?;
+        let (solver_result, _) = self.run_solver_on(&input, "")?;
+        self.cache_result(solver_result.clone());
+        Ok(solver_result)
+    }
+
+    /// Execute an SMT solver (other than z3) on `input` followed by
+    /// `on_unsat`. For a sat or unknown result, the solver is run again on
+    /// `input` followed by a request for the model or the reason. For an
+    /// unsat result, the output after the result line is returned as well,
+    /// i.e. the answers to the commands in `on_unsat`.
+    fn run_solver_on(
+        &mut self,
+        input: &str,
+        on_unsat: &str,
+    ) -> Result<(SolverResult<'ctx>, String), ProverCommandError> {
+        let mut smt_file: NamedTempFile = NamedTempFile::new().unwrap();
+        smt_file.write_all(input.as_bytes()).unwrap();
+        smt_file.write_all(on_unsat.as_bytes()).unwrap();
+
+        self.check_count += 1;
+        let mut output = call_solver(
+            smt_file.path(),
+            self.get_smt_solver(),
+            self.solver_executable.as_deref(),
+            &self.extra_args,
+            self.effective_timeout(),
+            None,
+        )
+        .map_err(|e| ProverCommandError::ProcessError(e.to_string()))?;
+
+        // the commands in on_unsat fail unless the result is unsat
+        if !output.status.success() && on_unsat.is_empty() {
             return Err(ProverCommandError::ProcessError(
                 String::from_utf8_lossy(&output.stderr).to_string(),
             ));
@@ -647,6 +2336,13 @@ impl<'ctx> Prover<'ctx> {
 
         let stdout = String::from_utf8_lossy(&output.stdout);
         let first_line = stdout.lines().next().unwrap_or("").trim().to_lowercase();
+        if !on_unsat.is_empty() && first_line != "unsat" {
+            // drop the commands in on_unsat before requesting more
+            smt_file
+                .as_file_mut()
+                .set_len(input.len() as u64)
+                .map_err(|e| ProverCommandError::ProcessError(e.to_string()))?;
+        }
 
         let sat_result = match first_line.as_str() {
             "sat" => {
@@ -673,6 +2369,11 @@ impl<'ctx> Prover<'ctx> {
                 }
                 SatResult::Unknown
             }
+            _ if !output.status.success() => {
+                return Err(ProverCommandError::ProcessError(
+                    String::from_utf8_lossy(&output.stderr).to_string(),
+                ))
+            }
             _ => {
                 return Err(ProverCommandError::UnexpectedResultError(
                     stdout.into_owned(),
@@ -684,7 +2385,9 @@ impl<'ctx> Prover<'ctx> {
             output = call_solver(
                 smt_file.path(),
                 self.get_smt_solver(),
-                self.timeout,
+                self.solver_executable.as_deref(),
+                &self.extra_args,
+                self.effective_timeout(),
                 Some(sat_result),
             )
             .map_err(|e| ProverCommandError::ProcessError(e.to_string()))?;
@@ -696,9 +2399,16 @@ impl<'ctx> Prover<'ctx> {
             .pop_front()
             .ok_or(ProverCommandError::ParseError)?;
         let solver_result = match sat_result {
-            SatResult::Unsat => SolverResult::Unsat,
+            SatResult::Unsat => {
+                return Ok((SolverResult::Unsat, lines_buffer.iter().join("\n")));
+            }
             SatResult::Unknown => {
-                SolverResult::Unknown(Some(ReasonUnknown::Other(lines_buffer.iter().join("\n"))))
+                let output = lines_buffer.iter().join("\n");
+                let reason = match self.smt_solver {
+                    SolverType::SWINE => ReasonUnknown::from_swine(&output),
+                    _ => ReasonUnknown::Other(output),
+                };
+                SolverResult::Unknown(Some(reason))
             }
             SatResult::Sat => {
                 let cex = lines_buffer.iter().join("");
@@ -708,33 +2418,193 @@ impl<'ctx> Prover<'ctx> {
                 SolverResult::Sat(Some(solver))
             }
         };
+        Ok((solver_result, String::new()))
+    }
 
-        self.cache_result(solver_result.clone());
-        Ok(solver_result)
+    /// The number of queries this prover sent to a solver so far, i.e. checks
+    /// of the internal Z3 and runs of an external solver. Results that are
+    /// cached or known without a query (e.g. for trivial provables) are not
+    /// counted, and neither are the checks of [`Self::prove_each_parallel`],
+    /// which run on separate solvers.
+    pub fn check_count(&self) -> usize {
+        self.check_count
     }
 
-    fn generate_smtlib(&self, assumptions: &[Bool<'_>]) -> String {
-        let mut smtlib = self.get_smtlib();
+    /// Whether the last proof check returned [`ProveResult::Proof`] only
+    /// because no provables were added. Such a proof is correct, but it often
+    /// means that a proof obligation was forgotten.
+    pub fn last_proof_was_vacuous(&self) -> bool {
+        self.last_proof_vacuous
+    }
 
-        if assumptions.is_empty() {
-            smtlib.add_check_sat();
-        } else {
-            smtlib.add_check_sat_assuming(assumptions.iter().map(|a| a.to_string()).collect());
-        };
+    /// Whether there were provables in the last proof check and all of them
+    /// simplified to `true`. The result was [`ProveResult::Proof`] then,
+    /// without calling the solver.
+    pub fn all_provables_trivial(&self) -> bool {
+        self.last_proof_trivial
+    }
+
+    fn update_proof_flags(&mut self) {
+        let trivial_provables: usize = self
+            .assertion_counts
+            .iter()
+            .map(|counts| counts.trivial_provables)
+            .sum();
+        self.last_proof_vacuous = !self.has_provables();
+        self.last_proof_trivial = self.has_provables() && trivial_provables == self.num_provables();
+    }
+
+    /// How often the SMT-LIB of the assertions was generated to run an
+    /// external solver. It is only regenerated after the assertions changed.
+    pub fn smtlib_generations(&self) -> usize {
+        self.smtlib_generations
+    }
+
+    /// The SMT-LIB of the current assertions, generated only if they changed
+    /// since the last call.
+    fn base_smtlib(&mut self) -> Smtlib {
+        match &self.base_smtlib {
+            Some(smtlib) => smtlib.clone(),
+            None => {
+                let smtlib = self.get_smtlib();
+                self.smtlib_generations += 1;
+                self.base_smtlib = Some(smtlib.clone());
+                smtlib
+            }
+        }
+    }
+
+    /// Return the SMT-LIB query that [`Self::check_proof_assuming`] would
+    /// send to the solver for `assumptions`, without running it. For
+    /// external solvers, this is exactly the solver's input, including the
+    /// adjustments for the solver like the removed commands for SWINE. For
+    /// the internal Z3, it is the SMT-LIB of the assertions followed by a
+    /// check command like in the query log (see [`Self::set_query_log`]).
+    ///
+    /// Fails with [`ProverCommandError::MalformedInput`] if the SMT-LIB cannot
+    /// be adjusted for an external solver, like the check would.
+    pub fn emit_query(&mut self, assumptions: &[Bool<'ctx>]) -> Result<String, ProverCommandError> {
+        match self.smt_solver {
+            SolverType::InternalZ3 => {
+                let mut smtlib = self.get_smtlib();
+                add_check_sat_tracked(&mut smtlib, assumptions);
+                Ok(smtlib.into_string())
+            }
+            _ => self.generate_smtlib(assumptions),
+        }
+    }
+
+    fn generate_smtlib(&mut self, assumptions: &[Bool<'_>]) -> Result<String, ProverCommandError> {
+        let mut smtlib = self.base_smtlib();
+        add_check_sat_tracked(&mut smtlib, assumptions);
 
         let smtlib = smtlib.into_string();
 
-        transform_input_lines(&smtlib, self.get_smt_solver(), self.timeout)
+        transform_input_lines(&smtlib, self.get_smt_solver(), self.effective_timeout())
+            .map_err(ProverCommandError::MalformedInput)
+    }
+}
+
+/// The [`ProveResult`] of an external solver's result.
+fn external_prove_result(solver_result: SolverResult<'_>) -> ProveResult {
+    match solver_result {
+        SolverResult::Unsat => ProveResult::Proof,
+        SolverResult::Unknown(reason) => {
+            ProveResult::Unknown(reason.unwrap_or(ReasonUnknown::Other("".to_string())))
+        }
+        SolverResult::Sat(_) => ProveResult::Counterexample,
+    }
+}
+
+/// Check the solver for [`Prover::prove_each_parallel`] and convert the result
+/// and the model so that they do not depend on the solver's context.
+fn check_owned(solver: &Solver<'_>) -> (ProveResult, Option<OwnedModel>) {
+    let (res, consistency) = match solver.check() {
+        SatResult::Unsat => return (ProveResult::Proof, None),
+        SatResult::Sat => (ProveResult::Counterexample, ModelConsistency::Consistent),
+        SatResult::Unknown => {
+            let reason = solver.get_reason_unknown().unwrap_or_default();
+            (
+                ProveResult::Unknown(reason.parse().unwrap()),
+                ModelConsistency::Unknown,
+            )
+        }
+    };
+    let model = solver
+        .get_model()
+        .map(|model| InstrumentedModel::new(consistency, model).to_owned_model());
+    (res, model)
+}
+
+/// Add a `(check-sat)` command, or a `(check-sat-assuming ...)` command if
+/// there are assumptions. Assumptions which are not literals are tracked by
+/// fresh constants named `assumption!i`, where `i` is the index of the
+/// assumption.
+fn add_check_sat_tracked(smtlib: &mut Smtlib, assumptions: &[Bool<'_>]) {
+    if assumptions.is_empty() {
+        smtlib.add_check_sat();
+        return;
+    }
+    let mut literals = Vec::with_capacity(assumptions.len());
+    for (i, assumption) in assumptions.iter().enumerate() {
+        if is_literal(assumption) {
+            literals.push(assumption.to_string());
+        } else {
+            let name = format!("assumption!{}", i);
+            smtlib.add_declare_const(&name, "Bool");
+            smtlib.add_assertion(&Assertion::new(format!("(= {} {})", name, assumption)));
+            literals.push(name);
+        }
+    }
+    let literals: Vec<&str> = literals.iter().map(String::as_str).collect();
+    smtlib.add_check_sat_assuming(&literals);
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+fn is_uninterpreted_constant(term: &Dynamic<'_>) -> bool {
+    term.is_app() && term.num_children() == 0 && term.decl().kind() == DeclKind::UNINTERPRETED
+}
+
+/// Whether the term is a Boolean constant or the negation of one.
+fn is_literal(term: &Bool<'_>) -> bool {
+    let term = Dynamic::from_ast(term);
+    if is_uninterpreted_constant(&term) {
+        return true;
     }
+    term.is_app()
+        && term.decl().kind() == DeclKind::NOT
+        && is_uninterpreted_constant(&term.children()[0])
 }
 
 #[cfg(test)]
 mod test {
-    use z3::{ast::Bool, Config, Context, SatResult};
+    use std::{
+        collections::HashMap,
+        path::Path,
+        time::{Duration, Instant},
+    };
+
+    use z3::{
+        ast::{forall_const, Ast, Bool, Dynamic, Int},
+        Config, Context, FuncDecl, Params, Pattern, SatResult, Solver, Sort,
+    };
+
+    use crate::{
+        model::{ConcreteValue, ModelConsistency, SmtEval},
+        smtlib::Smtlib,
+        util::ReasonUnknown,
+        z3_version,
+    };
 
     use crate::prover::{IncrementalMode, SolverType};
 
-    use super::{ProveResult, Prover};
+    use super::{
+        check_backend, combine_results, compare_results, transform_input_lines, CrossCheckResult,
+        FlushPolicy, MalformedSmtlib, ProveResult, Prover, ProverBuilder, ProverCommandError,
+        SolverConfig,
+    };
 
     #[test]
     fn test_prover() {
@@ -754,4 +2624,1339 @@ mod test {
             assert_eq!(prover.check_sat(), Ok(SatResult::Sat));
         }
     }
+
+    #[test]
+    fn test_with_tactic() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::with_tactic(&ctx, "smt", SolverType::InternalZ3).unwrap();
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        assert_eq!(prover.check_sat(), Ok(SatResult::Sat));
+
+        prover.push();
+        prover.add_assumption(&Bool::from_bool(&ctx, true));
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        assert_eq!(prover.check_sat(), Ok(SatResult::Sat));
+        prover.add_provable(&Bool::from_bool(&ctx, false));
+        assert!(matches!(
+            prover.check_proof(),
+            Ok(ProveResult::Counterexample)
+        ));
+        prover.pop();
+
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        assert_eq!(prover.check_sat(), Ok(SatResult::Sat));
+
+        assert!(matches!(
+            Prover::with_tactic(&ctx, "no-such-tactic", SolverType::InternalZ3),
+            Err(ProverCommandError::UnknownTactic(_))
+        ));
+
+        // the emulated mode keeps the tactic when it re-asserts after a pop.
+        // `skip` does not decide anything, unlike the default solver.
+        let config = SolverConfig::Z3 {
+            tactic: Some("skip".to_owned()),
+        };
+        let mut prover = Prover::new(&ctx, IncrementalMode::Emulated, config);
+        let x = Int::new_const(&ctx, "x");
+        prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 0)));
+        prover.add_provable(&x.ge(&Int::from_i64(&ctx, 1)));
+        prover.push();
+        prover.add_assumption(&x.lt(&Int::from_i64(&ctx, 5)));
+        prover.pop();
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Unknown(_))));
+    }
+
+    #[test]
+    fn test_assertion_counts() {
+        for mode in [IncrementalMode::Native, IncrementalMode::Emulated] {
+            let ctx = Context::new(&Config::default());
+            let mut prover = Prover::new(&ctx, mode, SolverType::InternalZ3);
+            let x = Int::new_const(&ctx, "x");
+            prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 0)));
+            prover.add_provable(&x.gt(&Int::from_i64(&ctx, -1)));
+            assert_eq!((prover.num_assumptions(), prover.num_provables()), (1, 1));
+
+            prover.push();
+            prover.add_assumption(&x.lt(&Int::from_i64(&ctx, 5)));
+            prover.add_provable(&x.lt(&Int::from_i64(&ctx, 6)));
+            prover.add_provable(&x.lt(&Int::from_i64(&ctx, 7)));
+            assert_eq!((prover.num_assumptions(), prover.num_provables()), (2, 3));
+            prover.pop();
+            assert_eq!((prover.num_assumptions(), prover.num_provables()), (1, 1));
+            assert!(prover.has_provables());
+
+            prover.push();
+            prover.push();
+            prover.add_provable(&x.lt(&Int::from_i64(&ctx, 6)));
+            prover.pop();
+            assert_eq!((prover.num_assumptions(), prover.num_provables()), (1, 1));
+            prover.pop();
+            assert_eq!(prover.assertion_counts.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_exists_forall_witness() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        let c = Int::new_const(&ctx, "c");
+        let five = Int::from_i64(&ctx, 5);
+        prover.add_provable(&(&x + &c)._eq(&(&x + &five)));
+
+        let mut ef_prover = prover.to_exists_forall(&[Dynamic::from_ast(&x)]);
+        assert_eq!(
+            ef_prover.check_proof().unwrap_err(),
+            ProverCommandError::ExistsForallProof
+        );
+        assert_eq!(ef_prover.check_sat(), Ok(SatResult::Sat));
+        let witness = ef_prover
+            .exists_forall_witness(&[Dynamic::from_ast(&c)])
+            .unwrap();
+        assert_eq!(witness.len(), 1);
+        assert_eq!(witness["c"], ConcreteValue::Int(5.into()));
+    }
+
+    #[test]
+    fn test_exists_forall_empty_universal() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 0)));
+        prover.add_provable(&x.gt(&Int::from_i64(&ctx, 1)));
+
+        let mut ef_prover = prover.to_exists_forall(&[]);
+        let negated = Bool::and(&ctx, &prover.get_assertions()).not();
+        assert_eq!(ef_prover.get_assertions(), vec![negated]);
+        // the assertions only hold for x = 1
+        assert_eq!(ef_prover.check_sat(), Ok(SatResult::Sat));
+        let witness = ef_prover
+            .exists_forall_witness(&[Dynamic::from_ast(&x)])
+            .unwrap();
+        assert_ne!(witness["x"], ConcreteValue::Int(1.into()));
+    }
+
+    #[test]
+    fn test_exists_forall_with_patterns() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let int_sort = Sort::int(&ctx);
+        let f = FuncDecl::new(&ctx, "f", &[&int_sort], &int_sort);
+        let x = Int::new_const(&ctx, "x");
+        let c = Int::new_const(&ctx, "c");
+        let f_x = f.apply(&[&x]).as_int().unwrap();
+        prover.add_provable(&(&f_x + &c)._eq(&(&f_x + Int::from_i64(&ctx, 5))));
+
+        let pattern = Pattern::new(&ctx, &[&f_x]);
+        let mut ef_prover =
+            prover.to_exists_forall_with_patterns(&[Dynamic::from_ast(&x)], &[pattern]);
+        assert_eq!(ef_prover.check_sat(), Ok(SatResult::Sat));
+        let witness = ef_prover
+            .exists_forall_witness(&[Dynamic::from_ast(&c)])
+            .unwrap();
+        assert_eq!(witness["c"], ConcreteValue::Int(5.into()));
+    }
+
+    #[test]
+    fn test_smtlib_patterns() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::CVC5);
+        let int_sort = Sort::int(&ctx);
+        let f = FuncDecl::new(&ctx, "f", &[&int_sort], &int_sort);
+        let x = Int::new_const(&ctx, "x");
+        let f_x = f.apply(&[&x]).as_int().unwrap();
+        let pattern = Pattern::new(&ctx, &[&f_x]);
+        let axiom = forall_const(&ctx, &[&x], &[&pattern], &f_x.gt(&x));
+        prover.add_assumption(&axiom);
+
+        let smtlib = prover.get_smtlib().into_string();
+        assert!(smtlib.contains(":pattern ((f x))"));
+        let input = prover.generate_smtlib(&[]).unwrap();
+        assert!(input.starts_with("(set-logic UFLIRA)\n"));
+        assert!(input.contains(":pattern ((f x))"));
+
+        // the pattern survives parsing the exported SMT-LIB again
+        let solver = Solver::new(&ctx);
+        solver.from_string(smtlib.as_str());
+        let reexported = Smtlib::from_solver(&solver).into_string();
+        assert!(reexported.contains(":pattern ((f x))"));
+    }
+
+    #[test]
+    fn test_emit_query() {
+        let ctx = Context::new(&Config::default());
+        let x = Int::new_const(&ctx, "x");
+        let b = Bool::new_const(&ctx, "b");
+        let axiom = forall_const(&ctx, &[&x], &[], &x.ge(&x));
+
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        prover.add_assumption(&axiom);
+        prover.add_provable(&x.gt(&Int::from_i64(&ctx, 0)));
+        let query = prover.emit_query(&[]).unwrap();
+        assert!(query.contains("(assert (not (> x 0)))"));
+        assert!(query.contains("forall"));
+        assert!(query.ends_with("(check-sat)"));
+        let query = prover.emit_query(&[b.not()]).unwrap();
+        assert!(query.ends_with("(check-sat-assuming ((not b)))"));
+        assert_eq!(prover.check_count(), 0);
+
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::SWINE);
+        prover.add_assumption(&axiom);
+        prover.add_provable(&x.gt(&Int::from_i64(&ctx, 0)));
+        let query = prover.emit_query(&[b]).unwrap();
+        assert!(query.contains("(assert (not (> x 0)))"));
+        // SWINE does not support quantifiers
+        assert!(!query.contains("forall"));
+        assert!(query.contains("(check-sat-assuming (b))"));
+        assert_eq!(prover.check_count(), 0);
+    }
+
+    #[test]
+    fn test_smtlib_cache() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::SWINE);
+        let x = Int::new_const(&ctx, "x");
+        prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 0)));
+        prover.generate_smtlib(&[]).unwrap();
+        prover
+            .generate_smtlib(&[x.gt(&Int::from_i64(&ctx, 1))])
+            .unwrap();
+        assert_eq!(prover.smtlib_generations(), 1);
+
+        prover.add_provable(&x.gt(&Int::from_i64(&ctx, -1)));
+        let smtlib = prover.generate_smtlib(&[]).unwrap();
+        assert_eq!(prover.smtlib_generations(), 2);
+        assert!(smtlib.contains("(- 1)"));
+
+        prover.push();
+        prover.generate_smtlib(&[]).unwrap();
+        assert_eq!(prover.smtlib_generations(), 3);
+        prover.pop();
+        prover.generate_smtlib(&[]).unwrap();
+        prover.generate_smtlib(&[]).unwrap();
+        assert_eq!(prover.smtlib_generations(), 4);
+    }
+
+    #[test]
+    fn test_simplify_assertions() {
+        for mode in [IncrementalMode::Native, IncrementalMode::Emulated] {
+            let ctx = Context::new(&Config::default());
+            let mut prover = Prover::new(&ctx, mode, SolverType::InternalZ3);
+            let x = Int::new_const(&ctx, "x");
+            prover.add_assumption(&Bool::from_bool(&ctx, true));
+            prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 0)));
+            prover.add_assumption(&(&x + Int::from_i64(&ctx, 0)).gt(&Int::from_i64(&ctx, 0)));
+            prover.add_provable(&x.gt(&Int::from_i64(&ctx, -1)));
+            assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+
+            let num_assertions = prover.get_assertions().len();
+            prover.simplify_assertions().unwrap();
+            assert!(prover.get_assertions().len() < num_assertions);
+            assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+
+            prover.push();
+            assert!(matches!(
+                prover.simplify_assertions(),
+                Err(ProverCommandError::InvalidConfiguration(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_prove_each() {
+        for mode in [IncrementalMode::Native, IncrementalMode::Emulated] {
+            let ctx = Context::new(&Config::default());
+            let mut prover = Prover::new(&ctx, mode, SolverType::InternalZ3);
+            let x = Int::new_const(&ctx, "x");
+            prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 0)));
+
+            let goals = [x.ge(&Int::from_i64(&ctx, 1)), x.ge(&Int::from_i64(&ctx, 2))];
+            let results = prover.prove_each(&goals).unwrap();
+            assert!(matches!(
+                results.as_slice(),
+                [ProveResult::Proof, ProveResult::Counterexample]
+            ));
+            assert_eq!(prover.level(), 0);
+            assert_eq!(prover.num_provables(), 0);
+        }
+    }
+
+    #[test]
+    fn test_generalize_counterexample() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        let y = Int::new_const(&ctx, "y");
+        let zero = Int::from_i64(&ctx, 0);
+        prover.add_assumption(&x.gt(&zero));
+        prover.add_assumption(&y.gt(&zero));
+        prover.add_provable(&(&x + &y).gt(&Int::from_i64(&ctx, 10)));
+        assert!(matches!(
+            prover.check_proof(),
+            Ok(ProveResult::Counterexample)
+        ));
+        let model = prover.get_model().unwrap();
+        let x_value = x.eval(&model).unwrap();
+
+        let (res, generalized) = prover
+            .generalize_counterexample(&model, &[Dynamic::from(x.clone())])
+            .unwrap();
+        assert!(matches!(res, ProveResult::Counterexample));
+        assert_eq!(x.eval(&generalized.unwrap()).unwrap(), x_value);
+        assert_eq!(prover.level(), 0);
+        assert_eq!(prover.get_assertions().len(), 3);
+    }
+
+    #[test]
+    fn test_prove_each_parallel() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        let y = Int::new_const(&ctx, "y");
+        prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 0)));
+
+        let goals: Vec<Bool> = (-2..6)
+            .map(|i| x.ge(&Int::from_i64(&ctx, i)))
+            .chain([x.gt(&y), (&x * &x).ge(&x)])
+            .collect();
+        let parallel = prover.prove_each_parallel(&goals, 3);
+        let sequential = prover.prove_each(&goals).unwrap();
+        assert_eq!(parallel.len(), sequential.len());
+        for ((res, model), expected) in parallel.iter().zip(&sequential) {
+            assert_eq!(res.to_string(), expected.to_string());
+            if let ProveResult::Counterexample = res {
+                assert!(model.as_ref().unwrap().get("x").is_some());
+            }
+        }
+
+        // the assertions of labeled frames are sent to the workers
+        prover.push_labeled("x large").unwrap();
+        prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 10)));
+        let parallel = prover.prove_each_parallel(&goals[7..8], 2);
+        assert!(matches!(parallel[0].0, ProveResult::Proof));
+        prover.pop();
+
+        prover.set_deadline(Instant::now());
+        let parallel = prover.prove_each_parallel(&goals, 3);
+        assert!(parallel.iter().all(|(res, model)| {
+            matches!(res, ProveResult::Unknown(ReasonUnknown::Timeout)) && model.is_none()
+        }));
+    }
+
+    #[test]
+    fn test_backend_version() {
+        let ctx = Context::new(&Config::default());
+        let prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let version = prover.backend_version();
+        assert!(!version.is_empty());
+        assert_eq!(version, z3_version());
+
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::SWINE);
+        prover.solver_executable = Some("/nonexistent/swine".into());
+        assert!(prover.backend_version().starts_with("unavailable"));
+    }
+
+    #[test]
+    fn test_try_new() {
+        let ctx = Context::new(&Config::default());
+        assert!(Prover::try_new(&ctx, SolverType::InternalZ3).is_ok());
+
+        let missing = Path::new("/nonexistent/swine");
+        let err = check_backend(&SolverType::SWINE, Some(missing)).unwrap_err();
+        assert_eq!(err.executable, missing);
+        assert!(err
+            .to_string()
+            .starts_with("solver /nonexistent/swine is unavailable"));
+        assert!(check_backend(&SolverType::CVC5, Some(Path::new("no-such-cvc5"))).is_err());
+    }
+
+    #[test]
+    fn test_solver_config() {
+        let ctx = Context::new(&Config::default());
+        let x = Int::new_const(&ctx, "x");
+        assert_eq!(
+            SolverConfig::from(SolverType::CVC5),
+            SolverConfig::Cvc5 { extra_args: vec![] }
+        );
+        let configs = [
+            SolverConfig::Z3 { tactic: None },
+            SolverConfig::Z3 {
+                tactic: Some("qflia".to_owned()),
+            },
+            SolverConfig::ExternalZ3,
+            SolverConfig::Swine { binary: None },
+            SolverConfig::Cvc5 {
+                extra_args: vec!["--incremental".to_owned()],
+            },
+        ];
+        for config in configs {
+            let solver_type = config.solver_type();
+            // external solvers might not be installed
+            if let Ok(mut prover) = Prover::try_new(&ctx, config) {
+                assert_eq!(prover.get_smt_solver(), solver_type);
+                prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 0)));
+                prover.add_provable(&x.ge(&Int::from_i64(&ctx, 1)));
+                assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+            }
+        }
+
+        let swine = SolverConfig::Swine {
+            binary: Some("/nonexistent/swine".into()),
+        };
+        let err = Prover::try_new(&ctx, swine.clone()).unwrap_err();
+        assert_eq!(err.executable, Path::new("/nonexistent/swine"));
+        let prover = Prover::new(&ctx, IncrementalMode::Native, swine);
+        assert_eq!(
+            prover.solver_executable.as_deref(),
+            Some(Path::new("/nonexistent/swine"))
+        );
+    }
+
+    #[test]
+    fn test_assertions_fingerprint() {
+        let ctx = Context::new(&Config::default());
+        let x = Int::new_const(&ctx, "x");
+        let y = Int::new_const(&ctx, "y");
+        let assumptions = [
+            x.gt(&Int::from_i64(&ctx, 0)),
+            y.lt(&x),
+            x._eq(&Int::from_i64(&ctx, 3)),
+        ];
+        let fingerprint = |order: &[usize]| {
+            let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+            for &i in order {
+                prover.add_assumption(&assumptions[i]);
+            }
+            prover.add_provable(&y.le(&Int::from_i64(&ctx, 3)));
+            prover.assertions_fingerprint()
+        };
+
+        let expected = fingerprint(&[0, 1, 2]);
+        assert_eq!(fingerprint(&[2, 0, 1]), expected);
+        // duplicates do not change the set of assertions
+        assert_eq!(fingerprint(&[1, 2, 0, 1]), expected);
+        assert_ne!(fingerprint(&[0, 1]), expected);
+
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        for assumption in &assumptions {
+            prover.add_assumption(assumption);
+        }
+        prover.add_provable(&y.le(&Int::from_i64(&ctx, 4)));
+        assert_ne!(prover.assertions_fingerprint(), expected);
+    }
+
+    #[test]
+    fn test_named_assumptions() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        let y = Int::new_const(&ctx, "y");
+        let b = Bool::new_const(&ctx, "b");
+        prover.add_named_assumption(&x.gt(&Int::from_i64(&ctx, 5)), "x big");
+        // an assumption that does not constrain x and y
+        prover.add_named_assumption(&Bool::or(&ctx, &[&b, &b.not()]), "tautology");
+        prover.push();
+        prover.add_named_assumption(&y._eq(&Int::from_i64(&ctx, 1)), "y one");
+        prover.add_provable(&x.lt(&y));
+        assert!(matches!(
+            prover.check_proof(),
+            Ok(ProveResult::Counterexample)
+        ));
+        let model = prover.get_model().unwrap();
+        let values = prover.named_assumption_values(&model);
+        assert_eq!(
+            values,
+            HashMap::from([
+                ("x big".to_owned(), true),
+                ("tautology".to_owned(), true),
+                ("y one".to_owned(), true),
+            ])
+        );
+
+        prover.pop();
+        let values = prover.named_assumption_values(&model);
+        assert_eq!(values.len(), 2);
+        assert!(!values.contains_key("y one"));
+    }
+
+    #[test]
+    fn test_prove_all() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 0)));
+
+        let goals = [
+            x.ge(&Int::from_i64(&ctx, 1)),
+            x.ge(&Int::from_i64(&ctx, 2)),
+            x.ge(&Int::from_i64(&ctx, 3)),
+        ];
+        let res = prover.prove_all(&goals).unwrap();
+        assert!(matches!(res, Err((1, ProveResult::Counterexample))));
+        assert_eq!(prover.level(), 0);
+        assert_eq!(prover.num_provables(), 0);
+
+        let res = prover.prove_all(&goals[..1]).unwrap();
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_add_provable_split() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 0)));
+        let holds = x.ge(&Int::from_i64(&ctx, 1));
+        let fails = x.ge(&Int::from_i64(&ctx, 2));
+
+        prover.push();
+        prover.add_provable_split(&Bool::and(&ctx, &[&holds, &fails]));
+        assert_eq!(prover.num_provables(), 2);
+        assert!(matches!(
+            prover.check_proof(),
+            Ok(ProveResult::Counterexample)
+        ));
+        let model = prover.get_model().unwrap();
+        assert!(holds.eval(&model).unwrap());
+        assert!(!fails.eval(&model).unwrap());
+        prover.pop();
+
+        prover.push();
+        prover.add_provable_split(&Bool::and(&ctx, &[&holds, &x.ge(&Int::from_i64(&ctx, 0))]));
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        prover.pop();
+
+        // not a conjunction
+        prover.add_provable_split(&holds);
+        assert_eq!(prover.num_provables(), 1);
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "assertions were added to the solver without the prover")]
+    fn test_debug_assert_tracking() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let a = Bool::new_const(&ctx, "a");
+        prover.add_assumption(&a);
+        prover.push();
+        prover.add_provable(&a);
+        prover.debug_assert_tracking();
+        prover.pop();
+
+        // the provable is not tracked, so the check would wrongly succeed
+        prover.get_solver().assert(&a.not());
+        let _ = prover.check_proof();
+    }
+
+    #[test]
+    fn test_debug_assert_consistent() {
+        for mode in [IncrementalMode::Native, IncrementalMode::Emulated] {
+            let ctx = Context::new(&Config::default());
+            let mut prover = Prover::new(&ctx, mode, SolverType::InternalZ3);
+            prover.debug_assert_consistent();
+            for i in 0..3 {
+                prover.push();
+                prover.add_assumption(&Bool::new_const(&ctx, format!("a{}", i)));
+                prover.push();
+                prover.add_provable(&Bool::new_const(&ctx, format!("p{}", i)));
+                prover.debug_assert_consistent();
+                prover.pop();
+                prover.debug_assert_consistent();
+            }
+            for _ in 0..3 {
+                prover.pop();
+                prover.debug_assert_consistent();
+            }
+            assert_eq!(prover.level(), 0);
+        }
+    }
+
+    #[test]
+    fn test_with_assumptions() {
+        for mode in [IncrementalMode::Native, IncrementalMode::Emulated] {
+            let ctx = Context::new(&Config::default());
+            let mut prover = Prover::new(&ctx, mode, SolverType::InternalZ3);
+            let x = Int::new_const(&ctx, "x");
+            prover.add_provable(&x.gt(&Int::from_i64(&ctx, 0)));
+            assert!(matches!(
+                prover.check_proof(),
+                Ok(ProveResult::Counterexample)
+            ));
+
+            let positive = x.ge(&Int::from_i64(&ctx, 1));
+            let res = prover.with_assumptions(std::slice::from_ref(&positive), |prover| {
+                assert_eq!(prover.level(), 1);
+                assert_eq!(prover.num_assumptions(), 1);
+                prover.check_proof()
+            });
+            assert!(matches!(res, Ok(ProveResult::Proof)));
+            assert_eq!(prover.level(), 0);
+            assert_eq!(prover.num_assumptions(), 0);
+            assert!(matches!(
+                prover.check_proof(),
+                Ok(ProveResult::Counterexample)
+            ));
+
+            let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                prover.with_assumptions(&[positive], |_| panic!("inside closure"))
+            }));
+            assert!(res.is_err());
+            assert_eq!(prover.level(), 0);
+            assert_eq!(prover.num_assumptions(), 0);
+        }
+    }
+
+    #[test]
+    fn test_transform_input_lines_malformed() {
+        for solver in [SolverType::SWINE, SolverType::CVC5] {
+            let balanced = "(declare-const x Int)\n(assert (> x 0))\n";
+            assert!(transform_input_lines(balanced, solver.clone(), None).is_ok());
+
+            assert_eq!(
+                transform_input_lines("(assert true))", solver.clone(), None),
+                Err(MalformedSmtlib::UnmatchedClose(13))
+            );
+            assert_eq!(
+                transform_input_lines(")(assert true)", solver.clone(), None),
+                Err(MalformedSmtlib::UnmatchedClose(0))
+            );
+            assert_eq!(
+                transform_input_lines("(assert (> x 0)", solver, None),
+                Err(MalformedSmtlib::Unclosed(1))
+            );
+        }
+    }
+
+    #[test]
+    fn test_transform_input_lines_logic() {
+        let logic = |input: &str| {
+            let output = transform_input_lines(input, SolverType::CVC5, None).unwrap();
+            output.lines().next().unwrap().to_owned()
+        };
+        let constant = "(declare-fun x () Int)\n(assert (> x 0))";
+        assert_eq!(logic(constant), "(set-logic QF_LIRA)");
+        let function = "(declare-fun |f x| (Int) Int)\n(assert (> (|f x| 1) 0))";
+        assert_eq!(logic(function), "(set-logic QF_UFLIRA)");
+        let quantified = "(declare-fun f (Int) Int)\n(assert (forall ((y Int)) (> (f y) (* y y))))";
+        assert_eq!(logic(quantified), "(set-logic UFNIRA)");
+    }
+
+    #[test]
+    fn test_transform_input_lines_comments() {
+        let input = "; location: a (b\n(declare-fun exp (Real) Real)\n; location: c) d\n(assert (= |x)| 1))\n(assert (forall ((y Int)) true))";
+        assert_eq!(
+            transform_input_lines(input, SolverType::SWINE, None).unwrap(),
+            "\n; location: c) d\n(assert (= |x)| 1))"
+        );
+    }
+
+    #[test]
+    fn test_located_assertions() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 0)));
+        prover.push();
+        prover.add_assumption_located(&x.lt(&Int::from_i64(&ctx, 5)), "a.heyvl:3:5");
+        prover.add_provable_located(&x.lt(&Int::from_i64(&ctx, 6)), "a.heyvl:4:5");
+
+        let smtlib = prover.get_smtlib().into_string();
+        let lines: Vec<&str> = smtlib.lines().collect();
+        for (location, assertion) in [("a.heyvl:3:5", "(< x 5)"), ("a.heyvl:4:5", "(< x 6)")] {
+            let comment = format!("; location: {}", location);
+            let pos = lines.iter().position(|line| *line == comment).unwrap();
+            assert!(lines[pos + 1].starts_with("(assert "));
+            assert!(lines[pos + 1].contains(assertion));
+        }
+
+        prover.pop();
+        assert!(!prover.get_smtlib().into_string().contains("; location:"));
+    }
+
+    /// Add the pigeonhole principle for 8 pigeons and 7 holes as assumptions
+    /// and `false` as the provable, which is slow to prove.
+    fn add_pigeonhole<'ctx>(ctx: &'ctx Context, prover: &mut Prover<'ctx>) {
+        let (pigeons, holes) = (8, 7);
+        let vars: Vec<Vec<Bool>> = (0..pigeons)
+            .map(|i| {
+                (0..holes)
+                    .map(|j| Bool::new_const(ctx, format!("p_{}_{}", i, j)))
+                    .collect()
+            })
+            .collect();
+        for row in &vars {
+            prover.add_assumption(&Bool::or(ctx, row));
+        }
+        for (i, row) in vars.iter().enumerate() {
+            for other in &vars[i + 1..] {
+                for (a, b) in row.iter().zip(other) {
+                    prover.add_assumption(&Bool::and(ctx, &[a, b]).not());
+                }
+            }
+        }
+        prover.add_provable(&Bool::from_bool(ctx, false));
+    }
+
+    /// Limit the resources of the prover's solver so that hard queries like
+    /// [`add_pigeonhole`] are unknown regardless of the machine's speed.
+    fn set_rlimit(prover: &mut Prover<'_>, rlimit: u32) {
+        let mut params = Params::new(prover.ctx);
+        params.set_u32("rlimit", rlimit);
+        prover.get_solver().set_params(&params);
+    }
+
+    #[test]
+    fn test_check_proof_with_retry() {
+        let ctx = Context::new(&Config::default());
+        let timeout = || ProveResult::Unknown(ReasonUnknown::Timeout);
+        let mut prover = Prover::new_mock(
+            &ctx,
+            [
+                timeout(),
+                timeout(),
+                ProveResult::Proof,
+                timeout(),
+                ProveResult::Unknown(ReasonUnknown::Incomplete),
+            ],
+        );
+        prover.add_provable(&Bool::from_bool(&ctx, false));
+
+        let res = prover.check_proof_with_retry(Duration::from_millis(1), 5);
+        assert!(matches!(res, Ok(ProveResult::Proof)));
+        assert_eq!(prover.timeout, Some(Duration::from_millis(4)));
+        let res = prover.check_proof_with_retry(Duration::from_millis(1), 0);
+        assert!(matches!(
+            res,
+            Ok(ProveResult::Unknown(ReasonUnknown::Timeout))
+        ));
+        // more time does not help for other reasons
+        let res = prover.check_proof_with_retry(Duration::from_millis(1), 5);
+        assert!(matches!(
+            res,
+            Ok(ProveResult::Unknown(ReasonUnknown::Incomplete))
+        ));
+        assert_eq!(prover.timeout, Some(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn test_reason_unknown_raw() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        add_pigeonhole(&ctx, &mut prover);
+        set_rlimit(&mut prover, 1);
+        let Ok(ProveResult::Unknown(reason)) = prover.check_proof() else {
+            panic!("expected an unknown result");
+        };
+        // the raw reason is kept next to the parsed one
+        let (parsed, raw) = prover.get_reason_unknown_raw().unwrap();
+        assert_eq!(parsed, reason);
+        assert_eq!(raw.parse::<ReasonUnknown>(), Ok(reason));
+    }
+
+    #[test]
+    fn test_prover_builder() {
+        let ctx = Context::new(&Config::default());
+        let dir = tempfile::tempdir().unwrap();
+        let mut prover = ProverBuilder::new()
+            .timeout(Duration::from_millis(1))
+            .random_seed(42)
+            .logic("QF_UF")
+            .dump_on_unknown(dir.path())
+            .build(&ctx)
+            .unwrap();
+        assert_eq!(prover.timeout, Some(Duration::from_millis(1)));
+        add_pigeonhole(&ctx, &mut prover);
+        set_rlimit(&mut prover, 1);
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Unknown(_))));
+        let dump = std::fs::read_to_string(dir.path().join("unknown-1.smt2")).unwrap();
+        assert!(dump.contains("(check-sat)"));
+
+        let res = ProverBuilder::new()
+            .solver_type(SolverType::CVC5)
+            .random_seed(42)
+            .build(&ctx);
+        assert!(matches!(
+            res,
+            Err(ProverCommandError::InvalidConfiguration(_))
+        ));
+        let res = ProverBuilder::new().swine_path("/opt/swine").build(&ctx);
+        assert!(matches!(
+            res,
+            Err(ProverCommandError::InvalidConfiguration(_))
+        ));
+        let res = ProverBuilder::new().logic("NO_SUCH_LOGIC").build(&ctx);
+        assert!(matches!(
+            res,
+            Err(ProverCommandError::InvalidConfiguration(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_proof_assuming_core() {
+        for mode in [IncrementalMode::Native, IncrementalMode::Emulated] {
+            let ctx = Context::new(&Config::default());
+            let mut prover = Prover::new(&ctx, mode, SolverType::InternalZ3);
+            let x = Int::new_const(&ctx, "x");
+            let y = Int::new_const(&ctx, "y");
+            prover.add_provable(&x.gt(&y));
+
+            let assumptions = [
+                x.gt(&Int::from_i64(&ctx, 5)),
+                y.lt(&Int::from_i64(&ctx, 0)),
+                y.lt(&Int::from_i64(&ctx, 3)),
+            ];
+            let (res, core) = prover.check_proof_assuming_core(&assumptions[..2]).unwrap();
+            assert!(matches!(res, ProveResult::Proof));
+            assert_eq!(core, vec![0, 1]);
+
+            let (res, core) = prover
+                .check_proof_assuming_core(&[
+                    assumptions[0].clone(),
+                    Bool::from_bool(&ctx, true),
+                    assumptions[2].clone(),
+                ])
+                .unwrap();
+            assert!(matches!(res, ProveResult::Proof));
+            assert_eq!(core, vec![0, 2]);
+
+            let (res, core) = prover.check_proof_assuming_core(&assumptions[1..]).unwrap();
+            assert!(matches!(res, ProveResult::Counterexample));
+            assert!(core.is_empty());
+            assert_eq!(prover.level(), 0);
+        }
+    }
+
+    #[test]
+    fn test_core_without_solver() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        let assumptions = [x.gt(&Int::from_i64(&ctx, 5))];
+        prover.push();
+        prover.add_provable(&x.gt(&Int::from_i64(&ctx, 0)));
+        let (res, core) = prover.check_proof_assuming_core(&assumptions).unwrap();
+        assert!(matches!(res, ProveResult::Proof));
+        assert_eq!(core, vec![0]);
+        // no provables, so the core of the last check must not be reused
+        prover.pop();
+        let (res, core) = prover.check_proof_assuming_core(&assumptions).unwrap();
+        assert!(matches!(res, ProveResult::Proof));
+        assert!(core.is_empty());
+
+        let mut prover = Prover::new_mock(&ctx, [ProveResult::Proof]);
+        prover.add_provable(&x.gt(&Int::from_i64(&ctx, 0)));
+        let (res, core) = prover.check_proof_assuming_core(&assumptions).unwrap();
+        assert!(matches!(res, ProveResult::Proof));
+        assert!(core.is_empty());
+    }
+
+    #[test]
+    fn test_minimize_assumptions() {
+        for mode in [IncrementalMode::Native, IncrementalMode::Emulated] {
+            let ctx = Context::new(&Config::default());
+            let mut prover = Prover::new(&ctx, mode, SolverType::InternalZ3);
+            let x = Int::new_const(&ctx, "x");
+            prover.add_provable(&x.gt(&Int::from_i64(&ctx, 0)));
+
+            let assumptions = [
+                x.gt(&Int::from_i64(&ctx, 2)),
+                x.gt(&Int::from_i64(&ctx, 5)),
+                x.lt(&Int::from_i64(&ctx, 10)),
+                x.gt(&Int::from_i64(&ctx, 7)),
+            ];
+            let minimal = prover.minimize_assumptions(&assumptions).unwrap();
+            assert_eq!(minimal.len(), 1);
+            assert!(minimal.len() < assumptions.len());
+            assert_ne!(minimal, vec![2]);
+
+            let minimal = prover.minimize_assumptions(&assumptions[2..3]).unwrap();
+            assert_eq!(minimal, vec![0]);
+            assert_eq!(prover.level(), 0);
+        }
+    }
+
+    #[test]
+    fn test_get_context() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let a = Bool::new_const(prover.get_context(), "a");
+        prover.add_provable(&a.implies(&a));
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+    }
+
+    #[test]
+    fn test_describe() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        prover.add_provable(&x.gt(&Int::from_i64(&ctx, 0)));
+        let res = prover.check_proof().unwrap();
+        assert!(matches!(res, ProveResult::Counterexample));
+        let model = prover.get_model().unwrap();
+        let description = res.describe(Some(&model));
+        assert!(description.starts_with("Counterexample"));
+        assert!(description.lines().any(|line| line.starts_with("x -> ")));
+
+        assert_eq!(ProveResult::Proof.describe(None), "Proof");
+    }
+
+    #[test]
+    fn test_compare_results() {
+        let unknown = || ProveResult::Unknown(ReasonUnknown::Timeout);
+        let compare = |z3, swine| match compare_results(z3, swine) {
+            CrossCheckResult::Agree(res) => Ok(res.to_string()),
+            CrossCheckResult::Disagree { z3, swine } => Err((z3.to_string(), swine.to_string())),
+        };
+        let proof = ProveResult::Proof.to_string();
+        let cex = ProveResult::Counterexample.to_string();
+        assert_eq!(
+            compare(ProveResult::Proof, ProveResult::Proof),
+            Ok(proof.clone())
+        );
+        assert_eq!(
+            compare(ProveResult::Proof, ProveResult::Counterexample),
+            Err((proof.clone(), cex.clone()))
+        );
+        assert_eq!(
+            compare(ProveResult::Counterexample, ProveResult::Proof),
+            Err((cex.clone(), proof.clone()))
+        );
+        assert_eq!(compare(unknown(), ProveResult::Counterexample), Ok(cex));
+        assert_eq!(compare(ProveResult::Proof, unknown()), Ok(proof));
+        assert_eq!(compare(unknown(), unknown()), Ok(unknown().to_string()));
+    }
+
+    #[test]
+    fn test_cross_check() {
+        // SwInE might not be installed
+        if check_backend(&SolverType::SWINE, None).is_err() {
+            return;
+        }
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 0)));
+        prover.add_provable(&x.ge(&Int::from_i64(&ctx, 1)));
+        assert!(matches!(
+            prover.cross_check(&[]),
+            Ok(CrossCheckResult::Agree(ProveResult::Proof))
+        ));
+        assert_eq!(prover.check_count(), 2);
+    }
+
+    #[test]
+    fn test_combine_results() {
+        let unknown = |reason: &str| ProveResult::Unknown(ReasonUnknown::Other(reason.to_owned()));
+        assert!(matches!(
+            combine_results([ProveResult::Proof, ProveResult::Proof]),
+            ProveResult::Proof
+        ));
+        assert!(matches!(combine_results([]), ProveResult::Proof));
+        assert!(matches!(
+            combine_results([
+                unknown("a"),
+                ProveResult::Counterexample,
+                ProveResult::Proof
+            ]),
+            ProveResult::Counterexample
+        ));
+        assert_eq!(
+            combine_results([ProveResult::Proof, unknown("a"), unknown("b")]).to_string(),
+            unknown("a").to_string()
+        );
+
+        // later checks are skipped after a counterexample
+        let mut checked = 0;
+        let results = [ProveResult::Counterexample, ProveResult::Proof]
+            .into_iter()
+            .inspect(|_| checked += 1);
+        combine_results(results);
+        assert_eq!(checked, 1);
+    }
+
+    #[test]
+    fn test_check_sat_model() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 3)));
+        let (res, model) = prover.check_sat_model().unwrap();
+        assert_eq!(res, SatResult::Sat);
+        let model = model.unwrap();
+        assert_eq!(model.consistency(), ModelConsistency::Consistent);
+        assert!(model.eval_ast(&x, true).unwrap().as_i64().unwrap() > 3);
+
+        prover.add_assumption(&x.lt(&Int::from_i64(&ctx, 0)));
+        let (res, model) = prover.check_sat_model().unwrap();
+        assert_eq!(res, SatResult::Unsat);
+        assert!(model.is_none());
+    }
+
+    #[test]
+    fn test_assumptions_obviously_unsat() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let a = Bool::new_const(&ctx, "a");
+        let b = Bool::new_const(&ctx, "b");
+        let x = Int::new_const(&ctx, "x");
+        prover.add_assumption(&Bool::and(&ctx, &[&a, &x.gt(&Int::from_i64(&ctx, 0))]));
+        prover.add_assumption(&Bool::or(&ctx, &[&a, &b]));
+        assert!(!prover.assumptions_obviously_unsat());
+        assert_eq!(prover.check_sat(), Ok(SatResult::Sat));
+
+        prover.push();
+        prover.add_assumption(&b.not());
+        assert!(!prover.assumptions_obviously_unsat());
+        prover.add_assumption(&a.not());
+        assert!(prover.assumptions_obviously_unsat());
+        prover.pop();
+
+        prover.push();
+        prover.add_assumption(&Bool::from_bool(&ctx, false));
+        assert!(prover.assumptions_obviously_unsat());
+        prover.pop();
+        assert!(!prover.assumptions_obviously_unsat());
+    }
+
+    #[test]
+    fn test_mock() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new_mock(
+            &ctx,
+            [
+                ProveResult::Proof,
+                ProveResult::Counterexample,
+                ProveResult::Unknown(ReasonUnknown::Timeout),
+            ],
+        );
+        let a = Bool::new_const(&ctx, "a");
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+
+        prover.push();
+        prover.add_provable(&a);
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        assert!(matches!(
+            prover.check_proof(),
+            Ok(ProveResult::Counterexample)
+        ));
+        prover.pop();
+
+        // no provables left after the pop, so no result is consumed
+        assert!(!prover.has_provables());
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+
+        prover.add_provable(&a);
+        assert!(matches!(
+            prover.check_proof(),
+            Ok(ProveResult::Unknown(ReasonUnknown::Timeout))
+        ));
+        assert_eq!(
+            prover.check_proof().unwrap_err(),
+            ProverCommandError::MockExhausted
+        );
+    }
+
+    #[test]
+    fn test_external_unsat_core() {
+        if std::process::Command::new("z3")
+            .arg("-version")
+            .output()
+            .is_err()
+        {
+            return;
+        }
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::ExternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        let y = Int::new_const(&ctx, "y");
+        prover.add_provable(&x.gt(&Int::from_i64(&ctx, 0)));
+        let assumptions = [y.gt(&Int::from_i64(&ctx, 0)), x.gt(&Int::from_i64(&ctx, 5))];
+        let (res, core) = prover.check_proof_assuming_core(&assumptions).unwrap();
+        assert!(matches!(res, ProveResult::Proof));
+        assert_eq!(core, vec![1]);
+        assert_eq!(prover.get_unsat_core(), vec![assumptions[1].clone()]);
+
+        // a counterexample comes from the same check
+        let checks = prover.check_count();
+        let (res, core) = prover.check_proof_assuming_core(&assumptions[..1]).unwrap();
+        assert!(matches!(res, ProveResult::Counterexample));
+        assert!(core.is_empty());
+        assert_eq!(prover.check_count(), checks + 1);
+        assert!(prover.get_model().is_some());
+    }
+
+    #[test]
+    fn test_query_log() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        {
+            let ctx = Context::new(&Config::default());
+            let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+            prover.set_query_log(file.reopen().unwrap());
+            prover.set_query_log_flush(FlushPolicy::OnDrop);
+            let x = Int::new_const(&ctx, "x");
+            for i in 0..100 {
+                prover.push();
+                prover.add_provable(&x.gt(&Int::from_i64(&ctx, i)));
+                assert!(matches!(
+                    prover.check_proof(),
+                    Ok(ProveResult::Counterexample)
+                ));
+                // answered from the cache
+                prover.check_proof().unwrap();
+                prover.pop();
+            }
+        }
+        let log = std::fs::read_to_string(file.path()).unwrap();
+        assert_eq!(log.matches("(check-sat)").count(), 100);
+        assert!(log.contains("; query 100\n"));
+    }
+
+    #[test]
+    fn test_check_sat_assuming_smtlib() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::ExternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        let b = Bool::new_const(&ctx, "b");
+        prover.add_provable(&x.gt(&Int::from_i64(&ctx, 0)));
+        let smtlib = prover
+            .generate_smtlib(&[b.not(), x.gt(&Int::from_i64(&ctx, 1))])
+            .unwrap();
+        assert!(smtlib.contains("(declare-const assumption!1 Bool)"));
+        assert!(smtlib.contains("(assert (= assumption!1 (> x 1)))"));
+        assert!(smtlib.ends_with("(check-sat-assuming ((not b) assumption!1))"));
+    }
+
+    #[test]
+    fn test_counterexample_smtlib() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        let y = Int::new_const(&ctx, "y");
+        prover.add_assumption(&x.gt(&y));
+        prover.add_provable(&x.gt(&Int::from_i64(&ctx, 10)));
+        assert!(matches!(
+            prover.check_proof(),
+            Ok(ProveResult::Counterexample)
+        ));
+        let model = prover.get_model().unwrap();
+        let smtlib = prover.counterexample_smtlib(&model);
+        assert!(smtlib.contains("(assert (= x "));
+        assert!(smtlib.contains("(assert (= y "));
+        assert!(smtlib.ends_with("(check-sat)"));
+        assert_eq!(model.iter_unaccessed().count(), 2);
+
+        let solver = Solver::new(&ctx);
+        solver.from_string(smtlib);
+        assert_eq!(solver.check(), SatResult::Sat);
+    }
+
+    #[test]
+    fn test_deadline() {
+        let ctx = Context::new(&Config::default());
+        // a mock without results fails if the solver is called
+        let mut prover = Prover::new_mock(&ctx, []);
+        assert_eq!(prover.remaining_budget(), None);
+        prover.add_provable(&Bool::new_const(&ctx, "a"));
+
+        prover.set_deadline(Instant::now() + Duration::from_secs(3600));
+        assert!(prover.remaining_budget().unwrap() > Duration::from_secs(3500));
+        assert_eq!(
+            prover.check_proof().unwrap_err(),
+            ProverCommandError::MockExhausted
+        );
+
+        prover.set_deadline(Instant::now() - Duration::from_millis(1));
+        assert_eq!(prover.remaining_budget(), Some(Duration::ZERO));
+        assert!(matches!(
+            prover.check_proof(),
+            Ok(ProveResult::Unknown(ReasonUnknown::Timeout))
+        ));
+    }
+
+    #[test]
+    fn test_check_count() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        assert_eq!(prover.check_count(), 0);
+
+        prover.push();
+        prover.add_provable(&Bool::from_bool(&ctx, true));
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        assert_eq!(prover.check_count(), 0);
+        prover.pop();
+
+        prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 0)));
+        prover.add_provable(&x.ge(&Int::from_i64(&ctx, 1)));
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        assert_eq!(prover.check_count(), 1);
+        // the result is cached
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        assert_eq!(prover.check_count(), 1);
+        let a = Bool::new_const(&ctx, "a");
+        assert!(matches!(
+            prover.check_proof_assuming(&[a]),
+            Ok(ProveResult::Proof)
+        ));
+        assert_eq!(prover.check_count(), 2);
+    }
+
+    #[test]
+    fn test_all_provables_trivial() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 0)));
+        prover.add_provable(&x._eq(&x));
+        prover.add_provable_split(&Bool::and(&ctx, &[Bool::from_bool(&ctx, true), x.le(&x)]));
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        assert!(prover.all_provables_trivial());
+        assert!(!prover.last_proof_was_vacuous());
+        assert_eq!(prover.check_count(), 0);
+
+        prover.add_provable(&x.ge(&Int::from_i64(&ctx, 1)));
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        assert!(!prover.all_provables_trivial());
+
+        // without provables, the proof is vacuous instead
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        assert!(!prover.all_provables_trivial());
+    }
+
+    #[test]
+    fn test_core_labels() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        let x = Int::new_const(&ctx, "x");
+        let y = Int::new_const(&ctx, "y");
+        prover.add_provable(&x.gt(&Int::from_i64(&ctx, 0)));
+        prover.push_labeled("x positive").unwrap();
+        prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 5)));
+        prover.push_labeled("y positive").unwrap();
+        prover.add_assumption(&y.gt(&Int::from_i64(&ctx, 0)));
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        assert_eq!(prover.core_labels(), vec!["x positive".to_owned()]);
+        assert_eq!(prover.check_sat(), Ok(SatResult::Unsat));
+
+        // exported and copied assertions are still unsat
+        let solver = Solver::new(&ctx);
+        solver.from_string(prover.get_smtlib().into_string());
+        assert_eq!(solver.check(), SatResult::Unsat);
+        let mut merged = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        merged.merge_from(&prover);
+        assert!(matches!(merged.check_proof(), Ok(ProveResult::Proof)));
+
+        // the literal of a level without assertions is not exported
+        prover.push_labeled("y").unwrap();
+        let smtlib = prover.get_smtlib().into_string();
+        assert!(!smtlib.contains("frame!y!"));
+        let solver = Solver::new(&ctx);
+        solver.from_string(smtlib);
+        assert_eq!(solver.check(), SatResult::Unsat);
+        prover.pop();
+
+        prover.pop();
+        prover.pop();
+        assert!(prover.core_labels().is_empty());
+        assert!(matches!(
+            prover.check_proof(),
+            Ok(ProveResult::Counterexample)
+        ));
+
+        // the emulated mode re-asserts the implications
+        let mut prover = Prover::new(&ctx, IncrementalMode::Emulated, SolverType::InternalZ3);
+        prover.add_provable(&x.gt(&Int::from_i64(&ctx, 0)));
+        prover.push_labeled("x positive").unwrap();
+        prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 5)));
+        prover.push();
+        prover.add_assumption(&y.gt(&Int::from_i64(&ctx, 0)));
+        prover.pop();
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        assert_eq!(prover.core_labels(), vec!["x positive".to_owned()]);
+
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::CVC5);
+        assert!(matches!(
+            prover.push_labeled("x positive"),
+            Err(ProverCommandError::InvalidConfiguration(_))
+        ));
+        assert_eq!(prover.level(), 0);
+    }
+
+    #[test]
+    fn test_merge_from() {
+        let ctx = Context::new(&Config::default());
+        let x = Int::new_const(&ctx, "x");
+        let mut sub_prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        sub_prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 0)));
+        sub_prover.add_provable(&x.ge(&Int::from_i64(&ctx, 1)));
+
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        prover.merge_from(&sub_prover);
+        assert_eq!(prover.num_assumptions(), 1);
+        assert_eq!(prover.num_provables(), 1);
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        assert!(!prover.last_proof_was_vacuous());
+
+        let mut sub_prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        sub_prover.add_provable(&x.ge(&Int::from_i64(&ctx, 2)));
+        prover.push();
+        prover.merge_from(&sub_prover);
+        assert_eq!(prover.num_provables(), 2);
+        assert!(matches!(
+            prover.check_proof(),
+            Ok(ProveResult::Counterexample)
+        ));
+        prover.pop();
+        assert_eq!(prover.num_provables(), 1);
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+    }
+
+    #[test]
+    fn test_last_proof_was_vacuous() {
+        let ctx = Context::new(&Config::default());
+        let mut prover = Prover::new(&ctx, IncrementalMode::Native, SolverType::InternalZ3);
+        assert!(!prover.last_proof_was_vacuous());
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        assert!(prover.last_proof_was_vacuous());
+
+        let x = Int::new_const(&ctx, "x");
+        prover.add_assumption(&x.gt(&Int::from_i64(&ctx, 0)));
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        assert!(prover.last_proof_was_vacuous());
+
+        prover.add_provable(&x.ge(&Int::from_i64(&ctx, 1)));
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        assert!(!prover.last_proof_was_vacuous());
+    }
+
+    #[test]
+    fn test_trivial_provables() {
+        let ctx = Context::new(&Config::default());
+        // a mock without results fails if the solver is called
+        let mut prover = Prover::new_mock(&ctx, []);
+        let a = Bool::new_const(&ctx, "a");
+
+        prover.push();
+        prover.add_provable(&Bool::from_bool(&ctx, true));
+        assert!(prover.has_provables());
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        prover.add_provable(&a);
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        prover.pop();
+
+        prover.add_provable(&a);
+        prover.push();
+        prover.add_assumption(&Bool::and(&ctx, &[&a, &a.not()]));
+        assert!(matches!(prover.check_proof(), Ok(ProveResult::Proof)));
+        prover.pop();
+
+        // the provable a is not trivial
+        assert_eq!(
+            prover.check_proof().unwrap_err(),
+            ProverCommandError::MockExhausted
+        );
+    }
 }
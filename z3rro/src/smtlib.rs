@@ -1,10 +1,22 @@
 //! Pretty-printing SMT-LIB code.
 
-use std::{io::Write, process::Command};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::{self, Display},
+    io::Write,
+    ops::Range,
+    process::Command,
+    str::FromStr,
+};
 
+use itertools::Itertools;
+use num::{BigInt, BigRational, Zero};
 use tempfile::NamedTempFile;
 use thiserror::Error;
-use z3::Solver;
+use z3::{
+    ast::{Ast, Dynamic},
+    Solver, Sort, SortKind,
+};
 
 use crate::{prover::ProveResult, util::PrefixWriter};
 
@@ -20,9 +32,156 @@ pub enum RacoReadError {
 #[derive(Debug, Clone)]
 pub struct Smtlib(String);
 
+/// Options for [`Smtlib::from_solver_with_options`]. The default options
+/// export the solver like [`Smtlib::from_solver`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmtlibExportOptions {
+    /// Emit a `(set-logic ...)` command with this logic.
+    pub logic: Option<String>,
+    /// Emit `(set-option :produce-models true)`.
+    pub produce_models: bool,
+    /// Emit `(set-option :produce-proofs true)`.
+    pub produce_proofs: bool,
+    /// Emit `(set-option :produce-unsat-cores true)`.
+    pub produce_unsat_cores: bool,
+    /// Keep the comments printed by the solver. Only whole-line comments are
+    /// removed otherwise.
+    pub include_comments: bool,
+    /// Remove declarations and definitions that repeat an earlier one.
+    pub dedup_declarations: bool,
+}
+
+impl Default for SmtlibExportOptions {
+    fn default() -> Self {
+        SmtlibExportOptions {
+            logic: None,
+            produce_models: false,
+            produce_proofs: false,
+            produce_unsat_cores: false,
+            include_comments: true,
+            dedup_declarations: false,
+        }
+    }
+}
+
 impl Smtlib {
     pub fn from_solver(solver: &Solver<'_>) -> Self {
-        Smtlib(format!("{}", solver))
+        Smtlib::from_solver_with_options(solver, &SmtlibExportOptions::default())
+    }
+
+    /// Like [`Self::from_solver`], but configured by `options`. Options are
+    /// emitted first, followed by the logic and the solver's commands.
+    pub fn from_solver_with_options(solver: &Solver<'_>, options: &SmtlibExportOptions) -> Self {
+        let mut smtlib = Smtlib(format!("{}", solver));
+        smtlib.declare_missing_sorts(solver);
+        smtlib.apply_options(options);
+        smtlib
+    }
+
+    fn apply_options(&mut self, options: &SmtlibExportOptions) {
+        if !options.include_comments {
+            let lines: Vec<&str> = self
+                .0
+                .lines()
+                .filter(|line| !line.trim_start().starts_with(';'))
+                .collect();
+            self.0 = lines.join("\n");
+        }
+        if options.dedup_declarations {
+            self.dedup_declarations();
+        }
+        if let Some(logic) = &options.logic {
+            self.0 = format!("(set-logic {})\n{}", logic, self.0);
+        }
+        // options must precede set-logic
+        if options.produce_unsat_cores {
+            self.set_produce_unsat_cores(true);
+        }
+        if options.produce_proofs {
+            self.set_produce_proofs(true);
+        }
+        if options.produce_models {
+            self.prepend_option("produce-models", true);
+        }
+    }
+
+    /// Remove declarations and definitions that are equal to an earlier one
+    /// modulo whitespace, together with the rest of their line if it is
+    /// empty.
+    fn dedup_declarations(&mut self) {
+        let mut seen = HashSet::new();
+        let mut duplicates = Vec::new();
+        for span in command_spans(&self.0) {
+            let Some(command) = SExpr::parse_all(&self.0[span.clone()])
+                .ok()
+                .and_then(|mut exprs| exprs.pop())
+            else {
+                continue;
+            };
+            let command = command.to_string();
+            if is_declaration(&command) && !seen.insert(command) {
+                duplicates.push(span);
+            }
+        }
+        for mut span in duplicates.into_iter().rev() {
+            if self.0[span.end..].starts_with('\n') {
+                span.end += 1;
+            }
+            self.0.replace_range(span, "");
+        }
+    }
+
+    /// Z3 prints declarations for datatypes and uninterpreted sorts it finds
+    /// in the assertions, but external solvers reject the whole file if a sort
+    /// is used without being declared. So we check that every uninterpreted
+    /// sort referenced by the assertions is declared and prepend a
+    /// `declare-sort` command otherwise.
+    fn declare_missing_sorts(&mut self, solver: &Solver<'_>) {
+        let mut sorts = Vec::new();
+        let mut visited = HashSet::new();
+        for assertion in solver.get_assertions() {
+            collect_sorts(&Dynamic::from_ast(&assertion), &mut visited, &mut sorts);
+        }
+        let missing: Vec<String> = sorts
+            .iter()
+            .filter(|sort| sort.kind() == SortKind::Uninterpreted)
+            .map(|sort| sort.to_string())
+            .filter(|name| {
+                !self.0.contains(&format!("(declare-sort {} ", name))
+                    && !self.0.contains(&format!("(declare-sort {})", name))
+            })
+            .collect();
+        if !missing.is_empty() {
+            let declarations: Vec<String> = missing
+                .iter()
+                .map(|name| format!("(declare-sort {} 0)", name))
+                .collect();
+            self.0 = format!("{}\n{}", declarations.join("\n"), self.0);
+        }
+    }
+
+    /// Set the `:produce-proofs` option. The option is placed at the very
+    /// beginning since it must be set before any assertions.
+    pub fn set_produce_proofs(&mut self, value: bool) {
+        self.prepend_option("produce-proofs", value);
+    }
+
+    /// Set the `:produce-unsat-cores` option. The option is placed at the
+    /// very beginning since it must be set before any assertions.
+    pub fn set_produce_unsat_cores(&mut self, value: bool) {
+        self.prepend_option("produce-unsat-cores", value);
+    }
+
+    /// Prepend a `(set-option :name value)` command, replacing a previous
+    /// setting of the same option.
+    fn prepend_option(&mut self, name: &str, value: bool) {
+        let prefix = format!("(set-option :{} ", name);
+        let rest: Vec<&str> = self
+            .0
+            .lines()
+            .filter(|line| !line.starts_with(&prefix))
+            .collect();
+        self.0 = format!("{}{})\n{}", prefix, value, rest.join("\n"));
     }
 
     /// Add a `(check-sat)` command at the end.
@@ -30,16 +189,28 @@ impl Smtlib {
         self.0.push_str("\n(check-sat)");
     }
 
-    /// Add a `(check-sat-assuming)` command at the end
-    pub fn add_check_sat_assuming(&mut self, assumptions: Vec<String>) {
-        let assumptions_str: Vec<String> = assumptions.iter().map(|a| a.to_string()).collect();
-
+    /// Add a `(check-sat-assuming (a1 a2 ...))` command at the end. SMT-LIB
+    /// only allows Boolean constants and their negations as assumptions, so
+    /// other terms must be tracked by named literals first (see
+    /// [`Self::add_declare_const`]).
+    pub fn add_check_sat_assuming(&mut self, assumptions: &[&str]) {
         self.0.push_str(&format!(
             "\n(check-sat-assuming ({}))",
-            assumptions_str.join(" ").as_str()
+            assumptions.join(" ")
         ));
     }
 
+    /// Add a `(declare-const name sort)` command at the end.
+    pub fn add_declare_const(&mut self, name: &str, sort: &str) {
+        self.0
+            .push_str(&format!("\n(declare-const {} {})", name, sort));
+    }
+
+    /// Add a `(get-model)` command at the end.
+    pub fn add_get_model(&mut self) {
+        self.0.push_str("\n(get-model)");
+    }
+
     /// Add a `(get-model)` command at the end for counterexamples and a `(get-info :reason-unknown)` for unknown results.
     pub fn add_details_query(&mut self, prove_result: &ProveResult) {
         match prove_result {
@@ -72,6 +243,31 @@ impl Smtlib {
         Ok(())
     }
 
+    /// Add an `assert` command at the end. Its attributes are emitted as an
+    /// `(! term ...)` annotation.
+    pub fn add_assertion(&mut self, assertion: &Assertion) {
+        self.0.push_str(&format!("\n{}", assertion));
+    }
+
+    /// The top-level `assert` commands of this SMT-LIB. Attributes of an
+    /// outermost `(! term ...)` annotation, e.g. those printed by the solver
+    /// in [`Self::from_solver`] or added via [`Self::add_assertion`], are
+    /// recovered. Returns an empty list if the text cannot be parsed.
+    pub fn assertions(&self) -> Vec<Assertion> {
+        let Ok(exprs) = SExpr::parse_all(&self.0) else {
+            return Vec::new();
+        };
+        exprs
+            .iter()
+            .filter_map(|expr| match expr {
+                SExpr::List(list) if list.len() == 2 && list[0].as_atom() == Some("assert") => {
+                    Some(Assertion::from_sexpr(&list[1]))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Return the underlying String.
     pub fn into_string(self) -> String {
         self.0
@@ -81,4 +277,842 @@ impl Smtlib {
     pub fn comment_writer<W>(writer: W) -> PrefixWriter<'static, W> {
         PrefixWriter::new(b"; ", writer)
     }
+
+    /// Insert a comment before the top-level command matching each `(command,
+    /// comment)` pair. Commands are compared modulo whitespace. If a command
+    /// occurs multiple times, the pairs for it annotate the occurrences in
+    /// order. Pairs whose command is not found are ignored.
+    pub fn add_comments(&mut self, comments: &[(String, String)]) {
+        let mut pending: Vec<(String, &str)> = comments
+            .iter()
+            .filter_map(|(command, comment)| {
+                let command = SExpr::parse_all(command).ok()?.pop()?;
+                Some((command.to_string(), comment.as_str()))
+            })
+            .collect();
+        let mut insertions = Vec::new();
+        for span in command_spans(&self.0) {
+            let Some(command) = SExpr::parse_all(&self.0[span.clone()])
+                .ok()
+                .and_then(|mut exprs| exprs.pop())
+            else {
+                continue;
+            };
+            let command = command.to_string();
+            if let Some(pos) = pending.iter().position(|(other, _)| *other == command) {
+                let (_, comment) = pending.remove(pos);
+                insertions.push((span.start, comment));
+            }
+        }
+        for (offset, comment) in insertions.into_iter().rev() {
+            let text: String = comment
+                .lines()
+                .map(|line| format!("; {}\n", line))
+                .collect();
+            self.0.insert_str(offset, &text);
+        }
+    }
+
+    /// Split this SMT-LIB into its top-level commands with normalized
+    /// whitespace. If the text cannot be parsed, each non-empty line is
+    /// considered a command.
+    fn commands(&self) -> Vec<String> {
+        match SExpr::parse_all(&self.0) {
+            Ok(exprs) => exprs.iter().map(|expr| expr.to_string()).collect(),
+            Err(_) => self
+                .0
+                .lines()
+                .map(|line| line.split_whitespace().join(" "))
+                .filter(|line| !line.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Compare the commands of this SMT-LIB with the commands of `other`.
+    /// Declarations and definitions are compared regardless of their order,
+    /// while the order of all other commands (e.g. `push`, `pop` and
+    /// `check-sat`) is significant.
+    pub fn diff(&self, other: &Smtlib) -> SmtlibDiff {
+        let (old_decls, old_cmds): (Vec<_>, Vec<_>) = self
+            .commands()
+            .into_iter()
+            .enumerate()
+            .partition(|(_, cmd)| is_declaration(cmd));
+        let (new_decls, new_cmds): (Vec<_>, Vec<_>) = other
+            .commands()
+            .into_iter()
+            .enumerate()
+            .partition(|(_, cmd)| is_declaration(cmd));
+
+        let mut removed = Vec::new();
+        let mut common = Vec::new();
+        let mut added = Vec::new();
+
+        let mut unmatched_decls = new_decls;
+        for (i, decl) in old_decls {
+            match unmatched_decls.iter().position(|(_, new)| *new == decl) {
+                Some(pos) => {
+                    unmatched_decls.remove(pos);
+                    common.push((i, decl));
+                }
+                None => removed.push((i, decl)),
+            }
+        }
+        added.extend(unmatched_decls);
+
+        // longest common subsequence of the order-sensitive commands
+        let (n, m) = (old_cmds.len(), new_cmds.len());
+        let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if old_cmds[i].1 == new_cmds[j].1 {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_cmds[i].1 == new_cmds[j].1 {
+                common.push(old_cmds[i].clone());
+                i += 1;
+                j += 1;
+            } else if j == m || (i < n && lcs[i + 1][j] >= lcs[i][j + 1]) {
+                removed.push(old_cmds[i].clone());
+                i += 1;
+            } else {
+                added.push(new_cmds[j].clone());
+                j += 1;
+            }
+        }
+
+        let sorted = |mut cmds: Vec<(usize, String)>| {
+            cmds.sort_by_key(|(i, _)| *i);
+            cmds.into_iter().map(|(_, cmd)| cmd).collect()
+        };
+        SmtlibDiff {
+            added: sorted(added),
+            removed: sorted(removed),
+            common: sorted(common),
+        }
+    }
+}
+
+/// An `assert` command whose term may carry attributes like `:named`, i.e.
+/// `(assert (! term :keyword value ...))`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assertion {
+    pub term: String,
+    /// The attributes with their keywords including the leading colon.
+    pub attributes: Vec<(String, Option<String>)>,
+}
+
+impl Assertion {
+    pub fn new(term: impl Into<String>) -> Self {
+        Assertion {
+            term: term.into(),
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Add the attribute `:keyword value`.
+    pub fn with_attribute(mut self, keyword: &str, value: Option<&str>) -> Self {
+        self.attributes
+            .push((format!(":{}", keyword), value.map(str::to_owned)));
+        self
+    }
+
+    /// The value of the `:named` attribute.
+    pub fn name(&self) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(keyword, _)| keyword == ":named")
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    fn from_sexpr(term: &SExpr) -> Self {
+        let (inner, rest) = match term {
+            SExpr::List(list) if list.len() >= 2 && list[0].as_atom() == Some("!") => {
+                (&list[1], &list[2..])
+            }
+            _ => return Assertion::new(term.to_string()),
+        };
+        let mut attributes: Vec<(String, Option<String>)> = Vec::new();
+        for expr in rest {
+            match (expr.as_atom(), attributes.last_mut()) {
+                (Some(keyword), _) if keyword.starts_with(':') => {
+                    attributes.push((keyword.to_owned(), None))
+                }
+                (_, Some((_, value @ None))) => *value = Some(expr.to_string()),
+                // not a well-formed annotation, so keep the term as it is
+                _ => return Assertion::new(term.to_string()),
+            }
+        }
+        Assertion {
+            term: inner.to_string(),
+            attributes,
+        }
+    }
+}
+
+impl Display for Assertion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.attributes.is_empty() {
+            return write!(f, "(assert {})", self.term);
+        }
+        write!(f, "(assert (! {}", self.term)?;
+        for (keyword, value) in &self.attributes {
+            write!(f, " {}", keyword)?;
+            if let Some(value) = value {
+                write!(f, " {}", value)?;
+            }
+        }
+        f.write_str("))")
+    }
+}
+
+/// The byte ranges of the top-level s-expressions in `input`, skipping
+/// comments, string literals and quoted symbols. Unbalanced input yields the
+/// spans found until the error.
+fn command_spans(input: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut chars = input.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '(' => {
+                if depth == 0 {
+                    start = i;
+                }
+                depth += 1;
+            }
+            ')' => {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+                if depth == 0 {
+                    spans.push(start..i + 1);
+                }
+            }
+            ';' => {
+                chars.by_ref().find(|&(_, next)| next == '\n');
+            }
+            // a doubled quote in a string is treated as the end of one literal
+            // and the start of the next
+            '|' | '"' => {
+                chars.by_ref().find(|&(_, next)| next == c);
+            }
+            _ => {}
+        }
+    }
+    spans
+}
+
+fn is_declaration(command: &str) -> bool {
+    command.starts_with("(declare-") || command.starts_with("(define-")
+}
+
+/// The difference between two [`Smtlib`]s, see [`Smtlib::diff`]. All commands
+/// have normalized whitespace.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SmtlibDiff {
+    /// Commands only present in the other SMT-LIB.
+    pub added: Vec<String>,
+    /// Commands only present in this SMT-LIB.
+    pub removed: Vec<String>,
+    /// Commands present in both.
+    pub common: Vec<String>,
+}
+
+impl SmtlibDiff {
+    /// Whether both SMT-LIBs have the same commands.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Collect the sorts of all subterms of `expr` in dependency order, i.e. the
+/// components of an array sort come before the array sort itself.
+fn collect_sorts<'ctx>(
+    expr: &Dynamic<'ctx>,
+    visited: &mut HashSet<Dynamic<'ctx>>,
+    sorts: &mut Vec<Sort<'ctx>>,
+) {
+    if !visited.insert(expr.clone()) {
+        return;
+    }
+    if expr.is_app() {
+        for child in expr.children() {
+            collect_sorts(&child, visited, sorts);
+        }
+    }
+    add_sort(expr.get_sort(), sorts);
+}
+
+fn add_sort<'ctx>(sort: Sort<'ctx>, sorts: &mut Vec<Sort<'ctx>>) {
+    if sorts.contains(&sort) {
+        return;
+    }
+    if let (Some(domain), Some(range)) = (sort.array_domain(), sort.array_range()) {
+        add_sort(domain, sorts);
+        add_sort(range, sorts);
+    }
+    sorts.push(sort);
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("unexpected end of input")]
+    UnexpectedEof,
+    #[error("unexpected closing parenthesis")]
+    UnexpectedClose,
+    #[error("unexpected expression: {0}")]
+    UnexpectedExpr(String),
+}
+
+/// A parsed SMT-LIB s-expression. Quoted symbols and string literals are kept
+/// as atoms including their delimiters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum SExpr {
+    Atom(String),
+    List(Vec<SExpr>),
+}
+
+impl SExpr {
+    /// Parse all top-level s-expressions from the input. Comments (starting
+    /// with `;`) are skipped.
+    pub(crate) fn parse_all(input: &str) -> Result<Vec<SExpr>, ParseError> {
+        let mut stack: Vec<Vec<SExpr>> = vec![Vec::new()];
+        let mut chars = input.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '(' => stack.push(Vec::new()),
+                ')' => {
+                    if stack.len() == 1 {
+                        return Err(ParseError::UnexpectedClose);
+                    }
+                    let list = stack.pop().unwrap();
+                    stack.last_mut().unwrap().push(SExpr::List(list));
+                }
+                ';' => {
+                    for c in chars.by_ref() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                }
+                c if c.is_whitespace() => {}
+                '|' | '"' => {
+                    let mut atom = String::from(c);
+                    loop {
+                        let next = chars.next().ok_or(ParseError::UnexpectedEof)?;
+                        atom.push(next);
+                        if next == c {
+                            // SMT-LIB escapes quotes in strings by doubling them
+                            if c == '"' && chars.peek() == Some(&'"') {
+                                atom.push(chars.next().unwrap());
+                                continue;
+                            }
+                            break;
+                        }
+                    }
+                    stack.last_mut().unwrap().push(SExpr::Atom(atom));
+                }
+                c => {
+                    let mut atom = String::from(c);
+                    while let Some(&next) = chars.peek() {
+                        if next.is_whitespace() || matches!(next, '(' | ')' | ';') {
+                            break;
+                        }
+                        atom.push(next);
+                        chars.next();
+                    }
+                    stack.last_mut().unwrap().push(SExpr::Atom(atom));
+                }
+            }
+        }
+        if stack.len() != 1 {
+            return Err(ParseError::UnexpectedEof);
+        }
+        Ok(stack.pop().unwrap())
+    }
+
+    fn as_atom(&self) -> Option<&str> {
+        match self {
+            SExpr::Atom(atom) => Some(atom),
+            SExpr::List(_) => None,
+        }
+    }
+}
+
+impl Display for SExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SExpr::Atom(atom) => f.write_str(atom),
+            SExpr::List(list) => {
+                f.write_str("(")?;
+                for (i, expr) in list.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+                    write!(f, "{}", expr)?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+/// Remove the `|...|` quotes around a quoted symbol.
+fn unquote_symbol(symbol: &str) -> &str {
+    symbol
+        .strip_prefix('|')
+        .and_then(|s| s.strip_suffix('|'))
+        .unwrap_or(symbol)
+}
+
+/// A value from a [`TextModel`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextValue {
+    Bool(bool),
+    Int(BigInt),
+    Real(BigRational),
+    /// Any value we do not understand, as SMT-LIB text.
+    Other(String),
+}
+
+impl TextValue {
+    fn from_sexpr(expr: &SExpr) -> TextValue {
+        Self::parse_numeric(expr)
+            .or_else(|| match expr.as_atom() {
+                Some("true") => Some(TextValue::Bool(true)),
+                Some("false") => Some(TextValue::Bool(false)),
+                _ => None,
+            })
+            .unwrap_or_else(|| TextValue::Other(expr.to_string()))
+    }
+
+    /// Parse numerals (`3`), decimals (`1.5`), negations (`(- 3)`) and
+    /// divisions (`(/ 1.0 3.0)`).
+    fn parse_numeric(expr: &SExpr) -> Option<TextValue> {
+        match expr {
+            SExpr::Atom(atom) => {
+                if let Some((int_part, frac_part)) = atom.split_once('.') {
+                    if !is_numeral(int_part) || !is_numeral(frac_part) {
+                        return None;
+                    }
+                    let denom = BigInt::from(10).pow(frac_part.len() as u32);
+                    let numer = BigInt::from_str(&format!("{}{}", int_part, frac_part)).ok()?;
+                    Some(TextValue::Real(BigRational::new(numer, denom)))
+                } else if is_numeral(atom) {
+                    Some(TextValue::Int(BigInt::from_str(atom).ok()?))
+                } else {
+                    None
+                }
+            }
+            SExpr::List(list) => match list.as_slice() {
+                [op, operand] if op.as_atom() == Some("-") => match Self::parse_numeric(operand)? {
+                    TextValue::Int(int) => Some(TextValue::Int(-int)),
+                    TextValue::Real(real) => Some(TextValue::Real(-real)),
+                    _ => None,
+                },
+                [op, numer, denom] if op.as_atom() == Some("/") => {
+                    let numer = Self::parse_numeric(numer)?.to_rational()?;
+                    let denom = Self::parse_numeric(denom)?.to_rational()?;
+                    if denom.is_zero() {
+                        return None;
+                    }
+                    Some(TextValue::Real(numer / denom))
+                }
+                _ => None,
+            },
+        }
+    }
+
+    fn to_rational(&self) -> Option<BigRational> {
+        match self {
+            TextValue::Int(int) => Some(BigRational::from_integer(int.clone())),
+            TextValue::Real(real) => Some(real.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a numeric SMT-LIB value like those accepted in a [`TextModel`] into
+/// a rational in lowest terms.
+pub(crate) fn parse_rational(input: &str) -> Option<BigRational> {
+    match SExpr::parse_all(input).ok()?.as_slice() {
+        [expr] => TextValue::parse_numeric(expr)?.to_rational(),
+        _ => None,
+    }
+}
+
+fn is_numeral(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+impl Display for TextValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextValue::Bool(b) => write!(f, "{}", b),
+            TextValue::Int(int) => write!(f, "{}", int),
+            TextValue::Real(real) => write!(f, "{}", real),
+            TextValue::Other(other) => f.write_str(other),
+        }
+    }
+}
+
+/// The assignment of a constant in a [`TextModel`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextAssignment {
+    /// The SMT-LIB sort of the constant.
+    pub sort: String,
+    pub value: TextValue,
+}
+
+/// A model parsed from the textual output of an SMT solver's `(get-model)`
+/// command. Only constants are recorded, definitions of functions with
+/// parameters are skipped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextModel {
+    assignments: BTreeMap<String, TextAssignment>,
+}
+
+impl TextModel {
+    /// Get the assignment of the constant with the given name.
+    pub fn get(&self, name: &str) -> Option<&TextAssignment> {
+        self.assignments.get(name)
+    }
+
+    /// Iterate over all assignments, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &TextAssignment)> {
+        self.assignments
+            .iter()
+            .map(|(name, assignment)| (name.as_str(), assignment))
+    }
+
+    pub fn len(&self) -> usize {
+        self.assignments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assignments.is_empty()
+    }
+
+    fn add_definition(&mut self, expr: &SExpr) -> Result<(), ParseError> {
+        let SExpr::List(list) = expr else {
+            return Err(ParseError::UnexpectedExpr(expr.to_string()));
+        };
+        // skip everything that is not a definition, e.g. declarations of
+        // universe elements for uninterpreted sorts
+        if list.first().and_then(SExpr::as_atom) != Some("define-fun") {
+            return Ok(());
+        }
+        let [_, name, SExpr::List(params), sort, value] = list.as_slice() else {
+            return Err(ParseError::UnexpectedExpr(expr.to_string()));
+        };
+        let name = name
+            .as_atom()
+            .ok_or_else(|| ParseError::UnexpectedExpr(name.to_string()))?;
+        if !params.is_empty() {
+            return Ok(());
+        }
+        let sort = sort.to_string();
+        let mut value = TextValue::from_sexpr(value);
+        // solvers may print integral reals without a decimal point
+        if let (TextValue::Int(int), "Real") = (&value, sort.as_str()) {
+            value = TextValue::Real(BigRational::from_integer(int.clone()));
+        }
+        self.assignments.insert(
+            unquote_symbol(name).to_owned(),
+            TextAssignment { sort, value },
+        );
+        Ok(())
+    }
+}
+
+/// Parse the output of a `(get-model)` command, i.e. a list of `(define-fun
+/// name () Sort value)` forms. A preceding `sat` line as well as the `model`
+/// keyword of older Z3 versions are accepted.
+pub fn parse_smtlib_model(output: &str) -> Result<TextModel, ParseError> {
+    let mut model = TextModel::default();
+    for expr in SExpr::parse_all(output)? {
+        match expr {
+            SExpr::Atom(atom) if atom == "sat" || atom == "unknown" => {}
+            SExpr::List(list) => {
+                let definitions = match list.split_first() {
+                    Some((head, rest)) if head.as_atom() == Some("model") => rest,
+                    _ => &list,
+                };
+                for definition in definitions {
+                    model.add_definition(definition)?;
+                }
+            }
+            other => return Err(ParseError::UnexpectedExpr(other.to_string())),
+        }
+    }
+    Ok(model)
+}
+
+#[cfg(test)]
+mod test {
+    use num::{BigInt, BigRational};
+    use z3::{
+        ast::{Ast, Bool, Datatype, Dynamic},
+        Config, Context, DatatypeAccessor, DatatypeBuilder, Solver, Sort,
+    };
+
+    use super::{
+        parse_smtlib_model, Assertion, ParseError, Smtlib, SmtlibDiff, SmtlibExportOptions,
+        TextValue,
+    };
+
+    #[test]
+    fn test_diff() {
+        let old = Smtlib(
+            "(declare-fun x () Int)\n(declare-fun y () Int)\n(assert (> x 0))\n(push 1)\n(assert (>  y\n  x))\n(check-sat)"
+                .to_owned(),
+        );
+        let new = Smtlib(
+            "(declare-fun y () Int)\n(declare-fun x () Int)\n(assert (> x 0))\n(push 1)\n(assert (< y x))\n(check-sat)"
+                .to_owned(),
+        );
+        assert_eq!(
+            old.diff(&new),
+            SmtlibDiff {
+                added: vec!["(assert (< y x))".to_owned()],
+                removed: vec!["(assert (> y x))".to_owned()],
+                common: vec![
+                    "(declare-fun x () Int)".to_owned(),
+                    "(declare-fun y () Int)".to_owned(),
+                    "(assert (> x 0))".to_owned(),
+                    "(push 1)".to_owned(),
+                    "(check-sat)".to_owned(),
+                ],
+            }
+        );
+        assert!(old.diff(&old).is_empty());
+
+        // the order of push and check-sat matters
+        let reordered = Smtlib("(check-sat)\n(push 1)".to_owned());
+        let diff = Smtlib("(push 1)\n(check-sat)".to_owned()).diff(&reordered);
+        assert_eq!(diff.common.len(), 1);
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn test_add_comments() {
+        let mut smtlib = Smtlib(
+            "; header (with parens\n(declare-fun x () Int)\n(assert (> x 0))\n(assert |a)b|)\n(assert\n  (> x 0))"
+                .to_owned(),
+        );
+        smtlib.add_comments(&[
+            (
+                "(assert (>  x 0))".to_owned(),
+                "location: a.heyvl:1".to_owned(),
+            ),
+            (
+                "(assert (> x 0))".to_owned(),
+                "location: a.heyvl:2".to_owned(),
+            ),
+            (
+                "(assert |a)b|)".to_owned(),
+                "location: a.heyvl:3".to_owned(),
+            ),
+            (
+                "(assert false)".to_owned(),
+                "location: a.heyvl:4".to_owned(),
+            ),
+        ]);
+        assert_eq!(
+            smtlib.into_string(),
+            "; header (with parens\n(declare-fun x () Int)\n; location: a.heyvl:1\n(assert (> x 0))\n; location: a.heyvl:3\n(assert |a)b|)\n; location: a.heyvl:2\n(assert\n  (> x 0))"
+        );
+    }
+
+    #[test]
+    fn test_assertion_attributes() {
+        let mut smtlib = Smtlib("(declare-fun x () Int)\n(assert (> x 0))".to_owned());
+        let named = Assertion::new("(< x 10)")
+            .with_attribute("named", Some("upper"))
+            .with_attribute("weight", Some("2"));
+        smtlib.add_assertion(&named);
+        let text = smtlib.into_string();
+        assert_eq!(
+            text,
+            "(declare-fun x () Int)\n(assert (> x 0))\n(assert (! (< x 10) :named upper :weight 2))"
+        );
+
+        let assertions = Smtlib(text).assertions();
+        assert_eq!(assertions, vec![Assertion::new("(> x 0)"), named]);
+        assert_eq!(assertions[0].name(), None);
+        assert_eq!(assertions[1].name(), Some("upper"));
+    }
+
+    #[test]
+    fn test_declared_sorts() {
+        let ctx = Context::new(&Config::default());
+        let loc_sort = Sort::uninterpreted(&ctx, "Loc".into());
+        let datatype = DatatypeBuilder::new(&ctx, "Cell")
+            .variant(
+                "mk-cell",
+                vec![("cell-loc", DatatypeAccessor::Sort(loc_sort.clone()))],
+            )
+            .finish();
+        let loc = Dynamic::new_const(&ctx, "loc", &loc_sort);
+        let cell = Datatype::new_const(&ctx, "cell", &datatype.sort);
+        let solver = Solver::new(&ctx);
+        solver.assert(&datatype.variants[0].accessors[0].apply(&[&cell])._eq(&loc));
+
+        let res = Smtlib::from_solver(&solver).into_string();
+        let first_assert = res.find("(assert").unwrap();
+        let loc_decl = res.find("(declare-sort Loc").unwrap();
+        let cell_decl = res.find("(declare-datatypes").unwrap();
+        assert!(loc_decl < cell_decl);
+        assert!(cell_decl < first_assert);
+        assert_eq!(res.matches("(declare-sort Loc").count(), 1);
+    }
+
+    #[test]
+    fn test_produce_options() {
+        let mut smtlib = Smtlib("(declare-fun x () Int)\n(assert (> x 0))".to_owned());
+        smtlib.set_produce_unsat_cores(true);
+        smtlib.set_produce_proofs(true);
+        smtlib.add_check_sat();
+        assert_eq!(
+            smtlib.clone().into_string(),
+            "(set-option :produce-proofs true)\n(set-option :produce-unsat-cores true)\n(declare-fun x () Int)\n(assert (> x 0))\n(check-sat)"
+        );
+
+        smtlib.set_produce_proofs(false);
+        let res = smtlib.into_string();
+        assert_eq!(res.matches(":produce-proofs").count(), 1);
+        assert!(res.starts_with("(set-option :produce-proofs false)\n"));
+        assert!(res.find(":produce-unsat-cores").unwrap() < res.find("(assert").unwrap());
+    }
+
+    #[test]
+    fn test_export_options() {
+        let text = "; generated\n(declare-fun x () Int)\n(assert (> x 0))\n(declare-fun  x () Int)\n(assert (< x 5))";
+        let export = |options: SmtlibExportOptions| {
+            let mut smtlib = Smtlib(text.to_owned());
+            smtlib.apply_options(&options);
+            smtlib.into_string()
+        };
+
+        assert_eq!(export(SmtlibExportOptions::default()), text);
+        assert_eq!(
+            export(SmtlibExportOptions {
+                include_comments: false,
+                ..Default::default()
+            }),
+            "(declare-fun x () Int)\n(assert (> x 0))\n(declare-fun  x () Int)\n(assert (< x 5))"
+        );
+        assert_eq!(
+            export(SmtlibExportOptions {
+                dedup_declarations: true,
+                ..Default::default()
+            }),
+            "; generated\n(declare-fun x () Int)\n(assert (> x 0))\n(assert (< x 5))"
+        );
+        assert_eq!(
+            export(SmtlibExportOptions {
+                logic: Some("QF_LIA".to_owned()),
+                produce_models: true,
+                ..Default::default()
+            }),
+            format!(
+                "(set-option :produce-models true)\n(set-logic QF_LIA)\n{}",
+                text
+            )
+        );
+        let res = export(SmtlibExportOptions {
+            produce_proofs: true,
+            produce_unsat_cores: true,
+            include_comments: false,
+            ..Default::default()
+        });
+        assert!(res.starts_with(
+            "(set-option :produce-proofs true)\n(set-option :produce-unsat-cores true)\n(declare-fun"
+        ));
+    }
+
+    #[test]
+    fn test_from_solver_with_options() {
+        let ctx = Context::new(&Config::default());
+        let solver = Solver::new(&ctx);
+        solver.assert(&Bool::new_const(&ctx, "a"));
+        let options = SmtlibExportOptions {
+            logic: Some("QF_UF".to_owned()),
+            include_comments: false,
+            ..Default::default()
+        };
+        let res = Smtlib::from_solver_with_options(&solver, &options).into_string();
+        assert!(res.starts_with("(set-logic QF_UF)\n"));
+        assert!(!res.contains(';'));
+        assert!(res.contains("(assert a)"));
+        assert_eq!(
+            Smtlib::from_solver(&solver).into_string(),
+            format!("{}", solver)
+        );
+    }
+
+    #[test]
+    fn test_parse_model() {
+        let output = r#"sat
+(
+  (define-fun b () Bool
+    false)
+  (define-fun x () Int
+    (- 3))
+  (define-fun y () Real
+    (/ 1.0 3.0))
+  (define-fun |z w| () Real
+    (- 2.5))
+  (define-fun r () Real 4)
+  (define-fun f ((x!0 Int)) Int
+    (ite (= x!0 1) 2 3))
+)
+"#;
+        let model = parse_smtlib_model(output).unwrap();
+        assert_eq!(model.len(), 5);
+        assert_eq!(model.get("b").unwrap().value, TextValue::Bool(false));
+        assert_eq!(model.get("x").unwrap().sort, "Int");
+        assert_eq!(
+            model.get("x").unwrap().value,
+            TextValue::Int(BigInt::from(-3))
+        );
+        assert_eq!(
+            model.get("y").unwrap().value,
+            TextValue::Real(BigRational::new(1.into(), 3.into()))
+        );
+        assert_eq!(
+            model.get("z w").unwrap().value,
+            TextValue::Real(BigRational::new((-5).into(), 2.into()))
+        );
+        assert_eq!(
+            model.get("r").unwrap().value,
+            TextValue::Real(BigRational::from_integer(4.into()))
+        );
+        assert!(model.get("f").is_none());
+    }
+
+    #[test]
+    fn test_parse_model_errors() {
+        assert_eq!(
+            parse_smtlib_model("((define-fun x () Int 3)"),
+            Err(ParseError::UnexpectedEof)
+        );
+        assert_eq!(
+            parse_smtlib_model("((define-fun x () Int 3)))"),
+            Err(ParseError::UnexpectedClose)
+        );
+        assert!(matches!(
+            parse_smtlib_model("((define-fun x Int 3))"),
+            Err(ParseError::UnexpectedExpr(_))
+        ));
+    }
 }
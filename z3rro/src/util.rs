@@ -121,9 +121,42 @@ impl<W: std::io::Write> std::io::Write for PrefixWriter<'_, W> {
 pub enum ReasonUnknown {
     Interrupted,
     Timeout,
+    /// The solver ran out of memory (SMT-LIB's `memout`).
+    MemoryLimit,
+    /// The problem is outside of what the solver can decide, e.g. because of
+    /// unsupported constructs (SMT-LIB's `incomplete`).
+    Incomplete,
     Other(String),
 }
 
+impl ReasonUnknown {
+    /// Interpret the output of SWINE after an `unknown` result, i.e. the
+    /// response to `(get-info :reason-unknown)` and any messages before it.
+    /// SWINE does not use fixed reason strings, so this looks for keywords
+    /// and falls back to [`ReasonUnknown::Other`] with the reported reason.
+    pub fn from_swine(output: &str) -> ReasonUnknown {
+        let output = output.trim();
+        let reason = output
+            .strip_prefix("(:reason-unknown")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .map(|reason| reason.trim().trim_matches('"'))
+            .unwrap_or(output);
+        let lowercase = output.to_lowercase();
+        let contains_any = |keywords: &[&str]| keywords.iter().any(|kw| lowercase.contains(kw));
+        if contains_any(&["timeout", "time limit", "timed out"]) {
+            ReasonUnknown::Timeout
+        } else if contains_any(&["memout", "out of memory", "memory limit"]) {
+            ReasonUnknown::MemoryLimit
+        } else if contains_any(&["incomplete", "unsupported", "not supported"]) {
+            ReasonUnknown::Incomplete
+        } else if contains_any(&["interrupted", "canceled"]) {
+            ReasonUnknown::Interrupted
+        } else {
+            ReasonUnknown::Other(reason.to_owned())
+        }
+    }
+}
+
 impl FromStr for ReasonUnknown {
     type Err = ();
 
@@ -131,6 +164,8 @@ impl FromStr for ReasonUnknown {
         match s {
             "interrupted from keyboard" | "canceled" => Ok(ReasonUnknown::Interrupted),
             "timeout" => Ok(ReasonUnknown::Timeout),
+            "memout" => Ok(ReasonUnknown::MemoryLimit),
+            "incomplete" => Ok(ReasonUnknown::Incomplete),
             other => Ok(ReasonUnknown::Other(other.to_owned())),
         }
     }
@@ -141,6 +176,8 @@ impl Display for ReasonUnknown {
         match self {
             ReasonUnknown::Interrupted => f.write_str("interrupted from keyboard"),
             ReasonUnknown::Timeout => f.write_str("timeout"),
+            ReasonUnknown::MemoryLimit => f.write_str("memout"),
+            ReasonUnknown::Incomplete => f.write_str("incomplete"),
             ReasonUnknown::Other(reason) => f.write_str(reason),
         }
     }
@@ -242,6 +279,8 @@ mod test {
     fn test_reason_unknown_parse_fmt() {
         let values = [
             ReasonUnknown::Interrupted,
+            ReasonUnknown::MemoryLimit,
+            ReasonUnknown::Incomplete,
             ReasonUnknown::Other("x".to_owned()),
         ];
         for value in &values {
@@ -250,6 +289,33 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_reason_unknown_from_swine() {
+        let cases = [
+            ("(:reason-unknown incomplete)", ReasonUnknown::Incomplete),
+            (
+                "(:reason-unknown \"unsupported: exponentiation with non-constant base\")",
+                ReasonUnknown::Incomplete,
+            ),
+            ("(:reason-unknown memout)", ReasonUnknown::MemoryLimit),
+            (
+                "warning: out of memory\n(:reason-unknown \"giving up\")",
+                ReasonUnknown::MemoryLimit,
+            ),
+            ("(:reason-unknown \"timeout\")", ReasonUnknown::Timeout),
+            ("time limit exceeded", ReasonUnknown::Timeout),
+            ("canceled", ReasonUnknown::Interrupted),
+            (
+                "(:reason-unknown \"max iterations reached\")",
+                ReasonUnknown::Other("max iterations reached".to_owned()),
+            ),
+            ("", ReasonUnknown::Other("".to_owned())),
+        ];
+        for (output, expected) in cases {
+            assert_eq!(ReasonUnknown::from_swine(output), expected, "{}", output);
+        }
+    }
+
     #[test]
     fn test_pretty_rational() {
         let test_cases = [